@@ -0,0 +1,92 @@
+use std::path::{Path, PathBuf};
+
+use cargo_metadata::camino::Utf8Path;
+use toml_edit::{Array, ArrayOfTables, DocumentMut, Item, Table, value};
+
+use crate::config::{CUSTOMS_FILE_NAME, CustomsFile, find_customs_file, parse_customs_toml};
+use crate::{Error, Result};
+
+/// A regulation as given on the command line.
+#[derive(Debug, Clone, clap::Args)]
+pub struct NewRegulation {
+    /// Job of the regulation, may be given multiple times.
+    #[arg(id = "job", long = "job", value_name = "JOB", required = true)]
+    jobs: Vec<String>,
+
+    /// Platform target of the regulation, may be given multiple times.
+    #[arg(long = "target", value_name = "TRIPLE")]
+    platform_targets: Vec<String>,
+
+    /// Build target of the regulation, may be given multiple times.
+    #[arg(long = "build-target", value_name = "BUILD_TARGET")]
+    build_targets: Vec<String>,
+}
+
+/// Appends the regulation to the nearest customs file, which is created next to the manifest if missing.
+pub fn add(directory: &Path, regulation: &NewRegulation) -> Result<PathBuf> {
+    let mut table = Table::new();
+    let fields = [
+        ("jobs", &regulation.jobs),
+        ("platform-targets", &regulation.platform_targets),
+        ("build-targets", &regulation.build_targets),
+    ];
+    for (key, values) in fields {
+        if !values.is_empty() {
+            table.insert(key, value(values.iter().collect::<Array>()));
+        }
+    }
+
+    append(directory, vec![table])
+}
+
+/// Appends regulations to the nearest customs file, which is created next to the manifest if missing.
+///
+/// Comments and formatting of the existing file are preserved.
+pub fn append(directory: &Path, tables: Vec<Table>) -> Result<PathBuf> {
+    let path = nearest_customs_file(directory)?;
+    let data = match std::fs::read_to_string(path.as_path()) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+
+    // Only valid files are extended
+    parse_customs_toml::<CustomsFile>(path.as_path(), data.as_str())?;
+
+    let mut document = data.parse::<DocumentMut>().map_err(anyhow::Error::from)?;
+    let regulations = document
+        .entry("regulation")
+        .or_insert(Item::ArrayOfTables(ArrayOfTables::new()))
+        .as_array_of_tables_mut()
+        .ok_or_else(|| anyhow::anyhow!("regulations in {} are an inline array", path.display()))?;
+    regulations.extend(tables);
+
+    let data = document.to_string();
+    parse_customs_toml::<CustomsFile>(path.as_path(), data.as_str())?;
+    std::fs::write(path.as_path(), data)?;
+
+    Ok(path)
+}
+
+/// The closest customs file up to the package, or where it would be created.
+///
+/// A `Customs.json` or `Customs.yaml` is not edited, nor shadowed by a new `Customs.toml` next to it.
+fn nearest_customs_file(directory: &Path) -> Result<PathBuf> {
+    for directory in directory.ancestors() {
+        let existing = match Utf8Path::from_path(directory) {
+            Some(directory) => find_customs_file(directory)?,
+            None => None,
+        };
+        match existing {
+            Some(path) if path.file_name() == Some(CUSTOMS_FILE_NAME) => return Ok(path.into()),
+            Some(path) => return Err(Error::NotAppendable(path.to_string())),
+            None => {}
+        }
+
+        if std::fs::exists(directory.join("Cargo.toml"))? {
+            return Ok(directory.join(CUSTOMS_FILE_NAME));
+        }
+    }
+
+    Err(Error::CustomsMissing)
+}
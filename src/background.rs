@@ -0,0 +1,43 @@
+use std::process::{Command, Stdio};
+
+/// Lowers the CPU and I/O priority of customs, which all checks inherit.
+///
+/// Uses the system tools instead of system calls, without them customs runs at normal priority.
+pub fn lower_priority() {
+    let pid = std::process::id().to_string();
+
+    #[cfg(unix)]
+    let commands = vec![
+        vec!["renice", "-n", "10", "-p", pid.as_str()],
+        // Idle I/O class, Linux only
+        #[cfg(target_os = "linux")]
+        vec!["ionice", "-c", "3", "-p", pid.as_str()],
+    ];
+
+    #[cfg(windows)]
+    let script = format!("(Get-Process -Id {pid}).PriorityClass = 'BelowNormal'");
+    #[cfg(windows)]
+    let commands = vec![vec![
+        "powershell",
+        "-NoProfile",
+        "-Command",
+        script.as_str(),
+    ]];
+
+    #[cfg(not(any(unix, windows)))]
+    let commands: Vec<Vec<&str>> = Vec::new();
+
+    for command in commands {
+        let status = Command::new(command[0])
+            .args(&command[1..])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        if !status.is_ok_and(|e| e.success()) {
+            eprintln!(
+                "Cannot lower the priority of the checks with {}",
+                command[0]
+            );
+        }
+    }
+}
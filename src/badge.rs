@@ -0,0 +1,63 @@
+use std::path::Path;
+
+use serde_json::json;
+
+use crate::Result;
+use crate::run::RunInfo;
+
+/// Writes the status of the run as a shields.io endpoint badge, e.g. `customs | passing`.
+///
+/// `suite` names the suite that ran, which is part of the label. The run failed if `result` is an error,
+/// even if no check did, e.g. because a tool was missing.
+pub fn write(
+    path: &Path,
+    suite: Option<&str>,
+    run_info: &RunInfo,
+    result: &Result<()>,
+) -> Result<()> {
+    let failed = run_info.outcomes.values().filter(|e| !e.passed).count();
+    let (message, color) = status(failed, run_info.outcomes.len(), result);
+    let label = match suite {
+        Some(suite) => format!("customs {suite}"),
+        None => "customs".to_string(),
+    };
+
+    let badge = json!({
+        "schemaVersion": 1,
+        "label": label,
+        "message": message,
+        "color": color,
+    });
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let json = serde_json::to_string_pretty(&badge).map_err(anyhow::Error::from)?;
+    std::fs::write(path, json)?;
+    Ok(())
+}
+
+/// Message and color of the badge.
+fn status(failed: usize, checks: usize, result: &Result<()>) -> (String, &'static str) {
+    match (failed, result) {
+        (0, Err(_)) => ("error".to_string(), "red"),
+        (0, Ok(())) if checks == 0 => ("no checks".to_string(), "lightgrey"),
+        (0, Ok(())) => ("passing".to_string(), "brightgreen"),
+        _ => (format!("{failed} failed"), "red"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+
+    #[test]
+    fn status_fails_on_errors_without_failed_checks() {
+        let error = Err(Error::ToolNotFound("typos".into()));
+        assert_eq!(status(0, 2, &error), ("error".into(), "red"));
+        assert_eq!(status(0, 0, &error), ("error".into(), "red"));
+        assert_eq!(status(1, 2, &error), ("1 failed".into(), "red"));
+        assert_eq!(status(0, 2, &Ok(())), ("passing".into(), "brightgreen"));
+        assert_eq!(status(0, 0, &Ok(())), ("no checks".into(), "lightgrey"));
+    }
+}
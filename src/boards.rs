@@ -0,0 +1,84 @@
+use std::collections::BTreeMap;
+
+use cargo_metadata::camino::{Utf8Path, Utf8PathBuf};
+use serde::Deserialize;
+
+use crate::config::{Regulation, parse_customs_toml};
+use crate::{Error, Result};
+
+const FILE_NAME: &str = "boards.toml";
+
+/// A hardware variant, e.g. a development board, of an embedded project.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "kebab-case")]
+pub struct Board {
+    /// The platform target triple of the board.
+    target: String,
+
+    /// Features selecting the board, added to every feature set.
+    #[serde(default)]
+    features: Vec<String>,
+
+    /// Runner for `cargo run` and `cargo test`, e.g. `probe-rs run --chip STM32F446RETx`.
+    runner: Option<String>,
+}
+
+/// A `boards.toml` listing boards by name.
+#[derive(Debug)]
+pub struct Boards {
+    path: Utf8PathBuf,
+    boards: BTreeMap<String, Board>,
+}
+
+/// Finds the closest `boards.toml` in the directory or any of its ancestors.
+pub fn find_boards_file(directory: &Utf8Path) -> Result<Option<Boards>> {
+    for directory in directory.ancestors() {
+        let path = directory.join(FILE_NAME);
+        if !std::fs::exists(path.as_std_path())? {
+            continue;
+        }
+
+        let data = std::fs::read_to_string(path.as_std_path())?;
+        let boards = parse_customs_toml(path.as_std_path(), data.as_str())?;
+        return Ok(Some(Boards { path, boards }));
+    }
+
+    Ok(None)
+}
+
+/// Replaces the boards of a regulation by one regulation per board,
+/// with the platform target, features and runner of the board.
+pub fn expand(regulation: Regulation, boards: Option<&Boards>) -> Result<Vec<Regulation>> {
+    if regulation.boards.is_empty() {
+        return Ok(vec![regulation]);
+    }
+
+    regulation
+        .boards
+        .iter()
+        .map(|name| {
+            let board =
+                boards
+                    .and_then(|e| e.boards.get(name))
+                    .ok_or_else(|| Error::UnknownBoard {
+                        board: name.clone(),
+                        file: boards.map(|e| e.path.to_string()),
+                    })?;
+
+            let mut expanded = regulation.clone();
+            expanded.boards = Vec::new();
+            expanded.board = Some(name.clone());
+            expanded.runner = board.runner.clone();
+            expanded.platform_targets = vec![board.target.clone()];
+            for features in expanded.feature_sets.iter_mut() {
+                for feature in board.features.iter() {
+                    if !features.contains(feature) {
+                        features.push(feature.clone());
+                    }
+                }
+            }
+            Ok(expanded)
+        })
+        .collect()
+}
@@ -0,0 +1,122 @@
+use std::collections::{HashMap, HashSet};
+
+use cargo_metadata::camino::{Utf8Path, Utf8PathBuf};
+
+use crate::Result;
+use crate::discovery::Exclusions;
+use crate::git::git;
+use crate::plan::{Plan, SkipReason};
+use crate::run::Outcome;
+
+/// Skips checks until the rest fits into `budget` seconds.
+///
+/// Checks that failed in the last run come first, then checks of packages with uncommitted changes,
+/// then the shortest checks. Checks that never passed are estimated by how long they took to fail,
+/// other checks without a recorded duration are skipped, they could take arbitrarily long.
+pub fn apply(
+    plan: &mut Plan,
+    budget: f64,
+    workspace_root: &Utf8Path,
+    exclusions: &Exclusions,
+) -> Result<()> {
+    let failed = last_failures(plan);
+    // Changes of vendored code do not make the package enclosing it a changed one
+    let changed = changed_files(workspace_root)
+        .into_iter()
+        .filter(|e| !exclusions.is_vendored(e))
+        .collect::<Vec<_>>();
+
+    let duration = |index: usize| {
+        let check = &plan.checks[index];
+        check
+            .predicted_duration
+            .or_else(|| failed.get(check.id.as_str()).copied())
+    };
+    let priority = |index: usize| {
+        let check = &plan.checks[index];
+        let directory = check
+            .manifest_path
+            .parent()
+            .expect("manifest is in a directory");
+        (
+            !failed.contains_key(check.id.as_str()),
+            !changed.iter().any(|e| e.starts_with(directory)),
+            duration(index).unwrap_or(f64::INFINITY),
+        )
+    };
+
+    let mut candidates = (0..plan.checks.len())
+        .filter(|e| plan.checks[*e].skipped.is_none())
+        .collect::<Vec<_>>();
+    candidates.sort_by(|a, b| {
+        priority(*a)
+            .partial_cmp(&priority(*b))
+            .expect("durations are not NaN")
+    });
+
+    let mut remaining = budget;
+    let mut selected = HashSet::new();
+    for index in candidates.iter() {
+        if let Some(duration) = duration(*index)
+            && duration <= remaining
+        {
+            remaining -= duration;
+            selected.insert(*index);
+        }
+    }
+
+    for index in candidates.into_iter().filter(|e| !selected.contains(e)) {
+        plan.checks[index].skipped = Some(SkipReason::OverBudget);
+    }
+    plan.update_predicted_duration();
+    Ok(())
+}
+
+/// Ids of the checks that failed in the last recorded run, with how long they took.
+fn last_failures(plan: &Plan) -> HashMap<String, f64> {
+    #[derive(serde::Deserialize)]
+    struct LastRun {
+        #[serde(default)]
+        outcomes: std::collections::BTreeMap<String, Outcome>,
+    }
+
+    let path = plan.output_directory.join("run.json");
+    let Ok(data) = std::fs::read_to_string(path.as_std_path()) else {
+        return HashMap::new();
+    };
+    // Like missing durations, a corrupt report only worsens the selection
+    let Ok(run) = serde_json::from_str::<LastRun>(data.as_str()) else {
+        return HashMap::new();
+    };
+
+    run.outcomes
+        .into_iter()
+        // Cut short, so it did not really fail
+        .filter(|(_, outcome)| !outcome.passed && !outcome.cancelled)
+        .map(|(id, outcome)| (id, outcome.seconds))
+        .collect()
+}
+
+/// Absolute paths of files with uncommitted changes, empty outside of git repositories.
+fn changed_files(workspace_root: &Utf8Path) -> Vec<Utf8PathBuf> {
+    let modified = git(
+        workspace_root,
+        &["diff", "--name-only", "--relative", "HEAD"],
+    );
+    let untracked = git(
+        workspace_root,
+        &["ls-files", "--others", "--exclude-standard"],
+    );
+
+    match (modified, untracked) {
+        (Ok(modified), Ok(untracked)) => modified
+            .lines()
+            .chain(untracked.lines())
+            .map(|e| workspace_root.join(e))
+            .collect(),
+        (Err(e), _) | (_, Err(e)) => {
+            log::debug!("Cannot determine changed files: {e}");
+            Vec::new()
+        }
+    }
+}
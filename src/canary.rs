@@ -0,0 +1,175 @@
+use std::path::Path;
+use std::process::{Command, Output};
+
+use cargo_metadata::Metadata;
+use cargo_metadata::camino::Utf8Path;
+
+use crate::{Error, Result, config};
+
+/// Options of the built-in `canary` job.
+#[derive(Debug, Clone, clap::Args)]
+pub struct Options {
+    /// Name of the dependency to replace.
+    #[arg(long)]
+    dependency: String,
+
+    /// Repository to take the dependency from.
+    #[arg(long, required_unless_present = "version")]
+    git: Option<String>,
+
+    /// Branch, tag or commit of the repository.
+    #[arg(long, requires = "git")]
+    rev: Option<String>,
+
+    /// Pre-release to update the dependency to instead.
+    #[arg(long, conflicts_with = "git")]
+    version: Option<String>,
+
+    /// Cargo command to run with the replaced dependency.
+    #[arg(long, default_value = "test")]
+    command: String,
+
+    /// Arguments of the cargo command.
+    #[arg(last = true)]
+    cargo_args: Vec<String>,
+}
+
+/// Warning of cargo if a patch does not match the version requirement of any dependency.
+const UNUSED_PATCH: &str = "was not used in the crate graph";
+
+/// Runs the cargo command with the dependency replaced by its upcoming release.
+///
+/// The command runs in a scratch copy of the workspace with its own lock file and target directory,
+/// so neither the lock file nor the artifacts of the other checks change, even if the run is interrupted.
+/// Path dependencies outside of the workspace are not part of the copy.
+/// `cargo_config` is passed as `--config` to every cargo invocation.
+pub fn check(metadata: &Metadata, options: &Options, cargo_config: &[String]) -> Result<()> {
+    let current_dir = std::env::current_dir()?;
+    let package = current_dir
+        .strip_prefix(metadata.workspace_root.as_std_path())
+        .unwrap_or(Path::new(""));
+    // Per package, checks of different packages may run at the same time
+    let directory = metadata
+        .target_directory
+        .join("customs")
+        .join("canary")
+        .join(
+            config::package_at(metadata, current_dir.as_path())
+                .map_or("workspace", |e| e.name.as_str()),
+        );
+    let workspace = directory.join("workspace");
+    if std::fs::exists(workspace.as_std_path())? {
+        std::fs::remove_dir_all(workspace.as_std_path())?;
+    }
+    copy_workspace(
+        metadata.workspace_root.as_std_path(),
+        workspace.as_std_path(),
+        metadata.target_directory.as_std_path(),
+    )?;
+
+    let result = run(
+        &directory,
+        &workspace.as_std_path().join(package),
+        options,
+        cargo_config,
+    );
+    std::fs::remove_dir_all(workspace.as_std_path())?;
+    result
+}
+
+/// Copies the sources of a workspace, without the `skipped` target directory and version control.
+fn copy_workspace(from: &Path, to: &Path, skipped: &Path) -> Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path == skipped || entry.file_name() == ".git" {
+            continue;
+        }
+        let destination = to.join(entry.file_name());
+        if path.is_dir() {
+            copy_workspace(&path, &destination, skipped)?;
+        } else {
+            std::fs::copy(&path, &destination)?;
+        }
+    }
+    Ok(())
+}
+
+fn run(
+    directory: &Utf8Path,
+    current_dir: &Path,
+    options: &Options,
+    cargo_config: &[String],
+) -> Result<()> {
+    let dependency = options.dependency.as_str();
+    let failed = |reason: &str| Error::CheckFailed(format!("canary of {dependency} ({reason})"));
+
+    let mut patch = Vec::new();
+    if let Some(git) = options.git.as_deref() {
+        patch.push(format!(
+            "patch.crates-io.{dependency}.git={}",
+            toml_string(git)
+        ));
+    }
+    if let Some(rev) = options.rev.as_deref() {
+        patch.push(format!(
+            "patch.crates-io.{dependency}.rev={}",
+            toml_string(rev)
+        ));
+    }
+    let cargo = |args: &[&str]| {
+        let mut command = Command::new("cargo");
+        for config in cargo_config {
+            command.arg("--config").arg(config);
+        }
+        command.args(args).current_dir(current_dir);
+        for patch in patch.iter() {
+            command.arg("--config").arg(patch);
+        }
+        command.env("CARGO_TARGET_DIR", directory.join("target"));
+        command
+    };
+
+    if let Some(version) = options.version.as_deref() {
+        let output = cargo(&["update", "--package", dependency, "--precise", version]).output()?;
+        echo(&output)?;
+        if !output.status.success() {
+            return Err(failed(format!("no update to {version}").as_str()));
+        }
+    }
+
+    // The patch always changes the lock file
+    let cargo_args = options.cargo_args.iter().filter_map(|e| match e.as_str() {
+        "--locked" => None,
+        "--frozen" => Some("--offline"),
+        e => Some(e),
+    });
+    let output = cargo(&[options.command.as_str()])
+        .args(cargo_args)
+        .output()?;
+    echo(&output)?;
+
+    // Otherwise the current release was checked once more
+    if String::from_utf8_lossy(&output.stderr).contains(UNUSED_PATCH) {
+        return Err(failed(
+            "the repository does not match the version requirement",
+        ));
+    }
+    if !output.status.success() {
+        return Err(failed(format!("{} failed", options.command).as_str()));
+    }
+
+    Ok(())
+}
+
+fn echo(output: &Output) -> Result<()> {
+    use std::io::Write;
+    std::io::stdout().write_all(&output.stdout)?;
+    std::io::stderr().write_all(&output.stderr)?;
+    Ok(())
+}
+
+fn toml_string(text: &str) -> String {
+    toml_edit::Value::from(text).to_string()
+}
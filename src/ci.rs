@@ -0,0 +1,78 @@
+/// CI systems customs adapts its output to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ci {
+    GithubActions,
+    Gitlab,
+    Buildkite,
+    Jenkins,
+    /// Any other CI, only recognized by the conventional `CI` variable.
+    Other,
+}
+
+impl Ci {
+    /// Detects the CI from its environment variables, `force` overrides the detection.
+    pub fn detect(force: Option<bool>) -> Option<Ci> {
+        let set = |key: &str| std::env::var_os(key).is_some();
+
+        let detected = if set("GITHUB_ACTIONS") {
+            Some(Ci::GithubActions)
+        } else if set("GITLAB_CI") {
+            Some(Ci::Gitlab)
+        } else if set("BUILDKITE") {
+            Some(Ci::Buildkite)
+        } else if set("JENKINS_URL") {
+            Some(Ci::Jenkins)
+        } else if set("CI") {
+            Some(Ci::Other)
+        } else {
+            None
+        };
+
+        match force {
+            Some(true) => detected.or(Some(Ci::Other)),
+            Some(false) => None,
+            None => detected,
+        }
+    }
+
+    /// Starts a collapsible section of the log.
+    pub fn begin_section(self, id: &str, title: &str) {
+        match self {
+            Ci::GithubActions => eprintln!("::group::{title}"),
+            Ci::Gitlab => eprintln!(
+                "section_start:{}:{}[collapsed=true]\r\x1b[0K{title}",
+                timestamp(),
+                section_name(id)
+            ),
+            Ci::Buildkite => eprintln!("--- {title}"),
+            Ci::Jenkins | Ci::Other => eprintln!("{title}"),
+        }
+    }
+
+    pub fn end_section(self, id: &str) {
+        match self {
+            Ci::GithubActions => eprintln!("::endgroup::"),
+            Ci::Gitlab => eprintln!("section_end:{}:{}\r\x1b[0K", timestamp(), section_name(id)),
+            Ci::Buildkite | Ci::Jenkins | Ci::Other => {}
+        }
+    }
+}
+
+fn timestamp() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|e| e.as_secs())
+        .unwrap_or_default()
+}
+
+/// GitLab only allows a few characters in section names.
+fn section_name(id: &str) -> String {
+    id.chars()
+        .map(
+            |e| match e.is_ascii_alphanumeric() || e == '.' || e == '-' {
+                true => e,
+                false => '_',
+            },
+        )
+        .collect()
+}
@@ -0,0 +1,73 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::history::format_duration;
+use crate::run::Outcome;
+use crate::{Error, Result};
+
+/// Duration changes below this are considered noise.
+const SIGNIFICANT_SECONDS: f64 = 1.0;
+const SIGNIFICANT_RATIO: f64 = 0.2;
+
+/// The parts of a `run.json` a comparison looks at.
+#[derive(Debug, Deserialize)]
+struct RecordedRun {
+    rustc_version: String,
+    cargo_version: String,
+    #[serde(default)]
+    outcomes: BTreeMap<String, Outcome>,
+}
+
+fn read(path: &Path) -> Result<RecordedRun> {
+    let data = std::fs::read_to_string(path)?;
+    serde_json::from_str(data.as_str())
+        .map_err(|e| Error::InvalidRunReport(path.display().to_string(), e))
+}
+
+/// Prints what changed from run `a` to run `b`, one check per line.
+pub fn compare(a: &Path, b: &Path) -> Result<()> {
+    let a = read(a)?;
+    let b = read(b)?;
+
+    if a.rustc_version != b.rustc_version {
+        println!("rustc: {} -> {}", a.rustc_version, b.rustc_version);
+    }
+    if a.cargo_version != b.cargo_version {
+        println!("cargo: {} -> {}", a.cargo_version, b.cargo_version);
+    }
+
+    for (id, new) in b.outcomes.iter() {
+        let Some(old) = a.outcomes.get(id) else {
+            println!("+ {id}");
+            continue;
+        };
+
+        match (old.passed, new.passed) {
+            (true, false) => println!("failed {id}"),
+            (false, true) => println!("fixed  {id}"),
+            // Durations of failed checks say little about the check
+            (true, true) => {
+                let delta = new.seconds - old.seconds;
+                if delta.abs() >= SIGNIFICANT_SECONDS
+                    && delta.abs() >= old.seconds * SIGNIFICANT_RATIO
+                {
+                    let direction = if delta > 0.0 { "slower" } else { "faster" };
+                    println!(
+                        "{direction} {id} ({} -> {})",
+                        format_duration(old.seconds),
+                        format_duration(new.seconds)
+                    );
+                }
+            }
+            (false, false) => {}
+        }
+    }
+
+    for id in a.outcomes.keys().filter(|e| !b.outcomes.contains_key(*e)) {
+        println!("- {id}");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,1249 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use cargo_metadata::camino::{Utf8Path, Utf8PathBuf};
+use cargo_metadata::{Metadata, Package};
+use itertools::Itertools;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::discovery::Exclusions;
+use crate::history::parse_duration;
+use crate::plan::SkipReason;
+use crate::preset::{Preset, PresetSet};
+use crate::retention::Retention;
+use crate::{Error, RegulationCheck, Result};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "kebab-case")]
+pub struct CustomsFile {
+    pub default: Option<Regulation>,
+
+    #[serde(default)]
+    pub regulation: Vec<Regulation>,
+
+    /// Built-in sets of regulations added to those of the file, e.g. `lib-quality`.
+    #[serde(default)]
+    pub presets: Vec<PresetSet>,
+
+    /// Shell command printing the effective customs file (TOML or JSON) to stdout,
+    /// run in the directory of the file.
+    pub generator: Option<String>,
+
+    /// Default of cargo's `-j` for all checks, only read from the customs file of the workspace root.
+    pub parallelism: Option<Parallelism>,
+
+    /// Age after which logs, reports and history in `target/customs` are removed, e.g. `7d`,
+    /// only read from the customs file of the workspace root.
+    pub max_log_age: Option<String>,
+
+    /// Whether customs files and changes in git submodules are ignored, like those of vendored code,
+    /// only read from the customs file of the workspace root.
+    pub exclude_submodules: Option<bool>,
+
+    /// Total size in bytes `target/customs` is kept below by removing the oldest files,
+    /// only read from the customs file of the workspace root.
+    pub max_artifacts_size: Option<u64>,
+
+    /// Named groups of checks run with `cargo customs suite <name>`,
+    /// only read from the customs file of the workspace root.
+    #[serde(default)]
+    pub suite: BTreeMap<String, Suite>,
+}
+
+/// The checks of the regulations with any of the names or tags, e.g. `[suite.smoke]`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Suite {
+    #[serde(default)]
+    pub regulations: Vec<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl Suite {
+    pub fn contains(&self, check: &RegulationCheck) -> bool {
+        check
+            .regulation
+            .as_ref()
+            .is_some_and(|e| self.regulations.contains(e))
+            || check.tags.iter().any(|e| self.tags.contains(e))
+    }
+}
+
+/// A number of parallel jobs, absolute or relative to the CPUs of the host.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Parallelism {
+    Count(usize),
+    /// `num-cpus` or a fraction like `num-cpus/2`.
+    Relative(String),
+}
+
+impl Parallelism {
+    pub fn resolve(&self) -> Result<usize> {
+        let expression = match self {
+            Parallelism::Count(count) => return Ok((*count).max(1)),
+            Parallelism::Relative(expression) => expression.as_str(),
+        };
+
+        let invalid = || Error::InvalidParallelism(expression.to_string());
+        let divisor = match expression.strip_prefix("num-cpus") {
+            Some("") => 1,
+            Some(rest) => rest
+                .strip_prefix('/')
+                .and_then(|e| e.trim().parse::<usize>().ok())
+                .filter(|e| *e > 0)
+                .ok_or_else(invalid)?,
+            None => return Err(invalid()),
+        };
+        let cpus = std::thread::available_parallelism().map_or(1, usize::from);
+        Ok((cpus / divisor).max(1))
+    }
+}
+
+/// The parallelism set in the customs file of the workspace root, if any.
+pub fn workspace_parallelism(metadata: &Metadata, files: &CustomsFiles) -> Result<Option<usize>> {
+    let Some(path) = find_customs_file(&metadata.workspace_root)? else {
+        return Ok(None);
+    };
+    files
+        .read(path.as_std_path())?
+        .parallelism
+        .map(|e| e.resolve())
+        .transpose()
+}
+
+/// The suite of the customs file of the workspace root with the given name.
+pub fn workspace_suite(metadata: &Metadata, files: &CustomsFiles, name: &str) -> Result<Suite> {
+    let mut suites = match find_customs_file(&metadata.workspace_root)? {
+        Some(path) => files.read(path.as_std_path())?.suite,
+        None => BTreeMap::new(),
+    };
+    suites
+        .remove(name)
+        .ok_or_else(|| Error::UnknownSuite(name.to_string(), suites.keys().join(", ")))
+}
+
+/// Whether the workspace root or any of its members has a customs file.
+pub fn workspace_has_customs(metadata: &Metadata) -> Result<bool> {
+    let directories = std::iter::once(metadata.workspace_root.as_path()).chain(
+        metadata
+            .workspace_packages()
+            .into_iter()
+            .filter_map(|e| e.manifest_path.parent()),
+    );
+    for directory in directories {
+        if find_customs_file(directory)?.is_some() {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// The workspace member whose manifest is in `directory`, e.g. the package a built-in job runs for.
+pub fn package_at<'a>(metadata: &'a Metadata, directory: &Path) -> Option<&'a Package> {
+    metadata
+        .workspace_packages()
+        .into_iter()
+        .find(|e| e.manifest_path.parent().is_some_and(|e| e == directory))
+}
+
+/// Whether the customs file of the workspace root excludes git submodules from discovery.
+pub fn workspace_excludes_submodules(metadata: &Metadata, files: &CustomsFiles) -> Result<bool> {
+    let Some(path) = find_customs_file(&metadata.workspace_root)? else {
+        return Ok(false);
+    };
+    Ok(files
+        .read(path.as_std_path())?
+        .exclude_submodules
+        .unwrap_or(false))
+}
+
+/// The retention limits set in the customs file of the workspace root, if any.
+pub fn workspace_retention(metadata: &Metadata, files: &CustomsFiles) -> Result<Retention> {
+    let Some(path) = find_customs_file(&metadata.workspace_root)? else {
+        return Ok(Retention::default());
+    };
+    let file = files.read(path.as_std_path())?;
+    let max_age = file
+        .max_log_age
+        .map(|e| parse_duration(e.as_str()).map_err(Error::InvalidRetention))
+        .transpose()?
+        .map(Duration::from_secs_f64);
+    Ok(Retention {
+        max_age,
+        max_size: file.max_artifacts_size,
+    })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "kebab-case")]
+pub struct Regulation {
+    /// Name of the regulation in reports, e.g. `embedded`.
+    pub name: Option<String>,
+
+    /// Labels grouping checks in reports across regulations, e.g. `lint`.
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    // TODO strongly type the strings
+    #[serde(default)]
+    pub platform_targets: Vec<String>,
+
+    #[serde(default)]
+    pub build_targets: Vec<String>,
+
+    #[serde(default)]
+    pub jobs: Jobs,
+
+    /// Each a list of features or in cargo's syntax, e.g. `"no-std,alloc"`, `"default"` or `"--no-default-features"`.
+    /// Also accepted as `features`.
+    #[serde(
+        alias = "features",
+        default = "default_feature_sets",
+        deserialize_with = "deserialize_feature_sets"
+    )]
+    pub feature_sets: Vec<Vec<String>>,
+
+    /// Whether the default features are enabled, like `default-features` of a dependency.
+    pub default_features: Option<bool>,
+
+    /// Rustup toolchain to run the jobs with, e.g. `nightly`.
+    pub toolchain: Option<String>,
+
+    /// Additional cargo config file for the checks of this regulation only,
+    /// relative to the package.
+    pub config_file: Option<String>,
+
+    /// Host operating systems the regulation applies to, e.g. `linux`, `macos` or `windows`.
+    /// An empty list applies to all.
+    #[serde(default)]
+    pub host_os: Vec<String>,
+
+    /// Wrapper around rustc, e.g. `sccache`, passed to cargo as `RUSTC_WRAPPER`.
+    pub rustc_wrapper: Option<String>,
+
+    /// The rustc to compile with, e.g. a custom compiler build, relative to the package.
+    pub rustc: Option<String>,
+
+    /// Additional `--cfg` options passed to rustc via `RUSTFLAGS`, e.g. `loom`.
+    #[serde(default)]
+    pub cfgs: Vec<String>,
+
+    /// Additional flags passed to rustc via `RUSTFLAGS`.
+    #[serde(default)]
+    pub rustflags: Vec<String>,
+
+    /// Built-in regulation filling in all fields not set here.
+    pub preset: Option<Preset>,
+
+    /// Lines of the output echoed when a check fails, the full output is in the log file.
+    pub failure_log_lines: Option<usize>,
+
+    /// Gives each check its own temporary directory, removed after the check.
+    pub isolated_tmp: Option<bool>,
+
+    /// Gives each check its own `CARGO_HOME` with only the dependencies `cargo fetch` downloads,
+    /// removed after the check.
+    pub isolated_cargo_home: Option<bool>,
+
+    /// Passes `--locked` to cargo, i.e. the lock file must be up to date.
+    pub locked: Option<bool>,
+
+    /// Passes `--offline` to cargo, i.e. no network access.
+    pub offline: Option<bool>,
+
+    /// Passes `--ignore-rust-version` to cargo, e.g. to test a toolchain older than the `rust-version`.
+    pub ignore_rust_version: Option<bool>,
+
+    /// Changes of jobs on particular platform targets, by platform target and job name.
+    #[serde(default)]
+    pub job_overrides: BTreeMap<String, BTreeMap<String, JobOverride>>,
+
+    /// How the checks are trimmed when not running in CI, e.g. `first-platform-target`.
+    #[serde(default)]
+    pub local_reduction: Vec<LocalReduction>,
+
+    /// Boards from the closest `boards.toml`, each checked with its platform target, features and runner.
+    #[serde(default)]
+    pub boards: Vec<String>,
+
+    /// The board a regulation was expanded for.
+    #[serde(skip)]
+    pub board: Option<String>,
+
+    /// Runner of the board, passed to cargo for the platform target.
+    #[serde(skip)]
+    pub runner: Option<String>,
+}
+
+/// Reduces a regulation for quick local runs, CI always runs everything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LocalReduction {
+    /// Only check the first of the platform targets.
+    FirstPlatformTarget,
+    /// Only check the first of the feature sets.
+    FirstFeatureSet,
+    /// Skip the regulation entirely, e.g. for slow checks.
+    Skip,
+}
+
+fn default_feature_sets() -> Vec<Vec<String>> {
+    vec![vec![]]
+}
+
+/// Feature sets given as lists or as strings in cargo's syntax, the latter split into lists.
+fn deserialize_feature_sets<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Vec<Vec<String>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum FeatureSet {
+        List(Vec<String>),
+        Cargo(String),
+    }
+
+    let sets = Vec::<FeatureSet>::deserialize(deserializer)?;
+    Ok(sets
+        .into_iter()
+        .map(|e| match e {
+            FeatureSet::List(features) => features,
+            FeatureSet::Cargo(features) => features
+                .split([',', ' '])
+                .filter(|e| !e.is_empty())
+                .map(str::to_string)
+                .collect(),
+        })
+        .collect())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum Jobs {
+    Short(Vec<String>),
+    Detailed(BTreeMap<String, JobParameters>),
+}
+
+impl Default for Jobs {
+    fn default() -> Self {
+        Jobs::Short(Vec::new())
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "kebab-case")]
+pub struct JobParameters {
+    #[serde(default)]
+    args: Vec<String>,
+
+    /// What to compare against for the `semver-checks` and `public-api` jobs,
+    /// e.g. a released version or a git revision.
+    baseline: Option<String>,
+
+    /// Minimum documentation coverage in percent for the `doc-coverage` job.
+    min_doc_coverage: Option<u8>,
+
+    /// Minimum line coverage in percent for the `coverage` job.
+    min_coverage: Option<u8>,
+
+    /// Whether the line coverage of the `coverage` job must not drop below that of the last run.
+    coverage_ratchet: Option<bool>,
+
+    /// Maximum size in bytes of each cdylib and staticlib the `build` job produces.
+    max_artifact_size: Option<u64>,
+
+    /// How often the failed tests of `test` and `nextest` jobs are retried.
+    retry_failed_tests: Option<u8>,
+
+    /// Number of tests run in parallel by `test` and `nextest` jobs,
+    /// `1` for tests sharing global state.
+    test_threads: Option<u16>,
+
+    /// Whether `test`, `bench` and `nextest` jobs only compile the tests with `--no-run`,
+    /// e.g. for platform targets they cannot run on.
+    compile_only: Option<bool>,
+
+    /// Timeout in seconds of each test run of the `mutants` job.
+    timeout: Option<u64>,
+
+    /// Verbosity of cargo itself, independent of the verbosity of customs.
+    cargo_verbosity: Option<CargoVerbosity>,
+
+    /// Text removed from the artifacts before the `reproducible` job compares them.
+    #[serde(default)]
+    normalize: Vec<String>,
+
+    /// Input of the command, inline or as `file:<path>` relative to the package.
+    stdin: Option<String>,
+
+    /// Dependency the `canary` job replaces with its upcoming release.
+    dependency: Option<String>,
+
+    /// Repository the `canary` job takes the dependency from.
+    git: Option<String>,
+
+    /// Branch, tag or commit of `git`, its default branch if unset.
+    rev: Option<String>,
+
+    /// Pre-release of the dependency on its registry the `canary` job updates to instead of `git`.
+    version: Option<String>,
+
+    /// Cargo command the `canary` job runs with the replaced dependency, `test` if unset.
+    command: Option<String>,
+
+    /// Whether the job depends on the network, e.g. `audit` or `publish --dry-run`,
+    /// retrying with backoff when it fails with a network error.
+    network: Option<bool>,
+
+    /// Exit codes that pass the check with notes instead of failing it, e.g. `[0, 2]`
+    /// for tools exiting non-zero on findings that are only reported.
+    #[serde(default)]
+    success_exit_codes: Vec<i32>,
+
+    /// Changes of the job for particular build targets, by build target.
+    #[serde(default)]
+    build_target_overrides: BTreeMap<String, JobOverride>,
+}
+
+/// Verbosity of cargo for a job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CargoVerbosity {
+    Quiet,
+    Normal,
+    Verbose,
+    /// Also shows the output of build scripts.
+    VeryVerbose,
+}
+
+impl CargoVerbosity {
+    pub fn flag(self) -> Option<&'static str> {
+        match self {
+            CargoVerbosity::Quiet => Some("-q"),
+            CargoVerbosity::Normal => None,
+            CargoVerbosity::Verbose => Some("-v"),
+            CargoVerbosity::VeryVerbose => Some("-vv"),
+        }
+    }
+}
+
+/// Changes of a job for particular targets.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "kebab-case")]
+pub struct JobOverride {
+    /// Arguments added to those of the job.
+    #[serde(default)]
+    args: Vec<String>,
+
+    /// Whether `args` replace the arguments of the job instead.
+    #[serde(default)]
+    replace_args: bool,
+
+    /// Arguments for cargo itself rather than the tool, e.g. `--no-run`.
+    #[serde(default)]
+    cargo_args: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct Job {
+    pub name: String,
+    pub args: Vec<String>,
+    /// Arguments for cargo before the `--` of the tool arguments.
+    pub cargo_args: Vec<String>,
+    pub baseline: Option<String>,
+    pub min_doc_coverage: Option<u8>,
+    pub min_coverage: Option<u8>,
+    pub coverage_ratchet: Option<bool>,
+    pub max_artifact_size: Option<u64>,
+    pub retry_failed_tests: Option<u8>,
+    pub test_threads: Option<u16>,
+    pub compile_only: bool,
+    pub network: bool,
+    pub timeout: Option<u64>,
+    pub cargo_verbosity: Option<CargoVerbosity>,
+    pub normalize: Vec<String>,
+    pub success_exit_codes: Vec<i32>,
+    pub stdin: Option<String>,
+    pub dependency: Option<String>,
+    pub git: Option<String>,
+    pub rev: Option<String>,
+    pub version: Option<String>,
+    pub command: Option<String>,
+    /// Applied when expanding to the checks.
+    #[serde(skip)]
+    pub build_target_overrides: BTreeMap<String, JobOverride>,
+}
+
+impl Job {
+    fn from_short(name: String) -> Job {
+        Self {
+            name,
+            args: Vec::new(),
+            cargo_args: Vec::new(),
+            baseline: None,
+            min_doc_coverage: None,
+            min_coverage: None,
+            coverage_ratchet: None,
+            max_artifact_size: None,
+            retry_failed_tests: None,
+            test_threads: None,
+            compile_only: false,
+            network: false,
+            timeout: None,
+            cargo_verbosity: None,
+            normalize: Vec::new(),
+            success_exit_codes: Vec::new(),
+            stdin: None,
+            dependency: None,
+            git: None,
+            rev: None,
+            version: None,
+            command: None,
+            build_target_overrides: BTreeMap::new(),
+        }
+    }
+
+    fn from_parameters(name: String, parameters: JobParameters) -> Job {
+        Self {
+            name,
+            args: parameters.args,
+            cargo_args: Vec::new(),
+            baseline: parameters.baseline,
+            min_doc_coverage: parameters.min_doc_coverage,
+            min_coverage: parameters.min_coverage,
+            coverage_ratchet: parameters.coverage_ratchet,
+            max_artifact_size: parameters.max_artifact_size,
+            retry_failed_tests: parameters.retry_failed_tests,
+            test_threads: parameters.test_threads,
+            compile_only: parameters.compile_only.unwrap_or(false),
+            network: parameters.network.unwrap_or(false),
+            timeout: parameters.timeout,
+            cargo_verbosity: parameters.cargo_verbosity,
+            normalize: parameters.normalize,
+            success_exit_codes: parameters.success_exit_codes,
+            stdin: parameters.stdin,
+            dependency: parameters.dependency,
+            git: parameters.git,
+            rev: parameters.rev,
+            version: parameters.version,
+            command: parameters.command,
+            build_target_overrides: parameters.build_target_overrides,
+        }
+    }
+
+    /// Whether the tests are built, but not run.
+    pub fn compiles_tests_only(&self) -> bool {
+        self.compile_only && matches!(self.name.as_str(), "test" | "bench" | "nextest")
+    }
+
+    fn apply_override(&mut self, job_override: &JobOverride) {
+        if job_override.replace_args {
+            self.args.clear();
+        }
+        self.args.extend(job_override.args.iter().cloned());
+        self.cargo_args
+            .extend(job_override.cargo_args.iter().cloned());
+    }
+
+    /// Position of the job when running jobs on the same platform target, build target and features.
+    ///
+    /// `check` and `clippy` share the metadata of dependencies, `build` and `test`
+    /// share the compiled dependencies, so running them back to back maximizes reuse.
+    /// `clean` runs first, so the following jobs build from scratch.
+    /// Unknown jobs run last in the order they are defined.
+    fn reuse_order(&self) -> usize {
+        const ORDER: &[&str] = &[
+            "clean", "fmt", "check", "clippy", "build", "doc", "test", "bench",
+        ];
+        ORDER
+            .iter()
+            .position(|e| *e == self.name)
+            .unwrap_or(ORDER.len())
+    }
+}
+
+impl Jobs {
+    fn into_jobs(self) -> Vec<Job> {
+        match self {
+            Jobs::Short(items) => items.into_iter().map(Job::from_short).collect(),
+            Jobs::Detailed(map) => map
+                .into_iter()
+                .map(|(name, parameters)| Job::from_parameters(name, parameters))
+                .collect(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        match self {
+            Jobs::Short(items) => items.is_empty(),
+            Jobs::Detailed(map) => map.is_empty(),
+        }
+    }
+}
+
+pub const CUSTOMS_FILE_NAME: &str = "Customs.toml";
+
+/// For generated configuration, which is awkward to write as TOML.
+#[cfg(feature = "json")]
+const JSON_CUSTOMS_FILE_NAME: &str = "Customs.json";
+
+/// For configuration shared with other YAML-based tooling.
+#[cfg(feature = "yaml")]
+const YAML_CUSTOMS_FILE_NAMES: &[&str] = &["Customs.yaml", "Customs.yml"];
+
+/// The syntax of a customs file, all with the same schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Toml,
+    /// Only with the `json` feature.
+    Json,
+    /// Only with the `yaml` feature.
+    Yaml,
+}
+
+impl Format {
+    fn of(path: &Path) -> Format {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") if cfg!(feature = "json") => Format::Json,
+            Some("yaml" | "yml") if cfg!(feature = "yaml") => Format::Yaml,
+            _ => Format::Toml,
+        }
+    }
+}
+
+/// The customs file in `directory`, if any.
+pub fn find_customs_file(directory: &Utf8Path) -> Result<Option<Utf8PathBuf>> {
+    let path = directory.join(CUSTOMS_FILE_NAME);
+    if std::fs::exists(path.as_std_path())? {
+        return Ok(Some(path));
+    }
+
+    #[cfg(feature = "json")]
+    {
+        let path = directory.join(JSON_CUSTOMS_FILE_NAME);
+        if std::fs::exists(path.as_std_path())? {
+            return Ok(Some(path));
+        }
+    }
+
+    #[cfg(feature = "yaml")]
+    for name in YAML_CUSTOMS_FILE_NAMES {
+        let path = directory.join(name);
+        if std::fs::exists(path.as_std_path())? {
+            return Ok(Some(path));
+        }
+    }
+
+    Ok(None)
+}
+
+/// The customs files read in a run, so each is parsed and, with a `generator`, generated only once.
+///
+/// Every part of the plan sees the same file, even of a slow or non-deterministic generator.
+#[derive(Debug, Default)]
+pub struct CustomsFiles {
+    /// By canonical path.
+    files: RefCell<BTreeMap<PathBuf, CustomsFile>>,
+}
+
+impl CustomsFiles {
+    fn read(&self, path: &Path) -> Result<CustomsFile> {
+        let key = std::fs::canonicalize(path)?;
+        if let Some(customs) = self.files.borrow().get(&key) {
+            return Ok(customs.clone());
+        }
+
+        let customs = read_customs_file(path)?;
+        self.files.borrow_mut().insert(key, customs.clone());
+        Ok(customs)
+    }
+}
+
+fn read_customs_file(path: &Path) -> Result<CustomsFile> {
+    let data = std::fs::read_to_string(path)?;
+    let customs = parse_customs_file(path, data.as_str(), Format::of(path))?;
+
+    match customs.generator.as_ref() {
+        Some(generator) => generate_customs_file(path, generator),
+        None => Ok(customs),
+    }
+}
+
+fn generate_customs_file(path: &Path, generator: &str) -> Result<CustomsFile> {
+    let directory = path.parent().expect("customs file is in a directory");
+    let output = shell_command(generator)
+        .current_dir(directory)
+        .stderr(std::process::Stdio::inherit())
+        .output()?;
+
+    if !output.status.success() {
+        return Err(Error::GeneratorFailed(
+            path.display().to_string(),
+            output.status.to_string(),
+        ));
+    }
+
+    let data = String::from_utf8_lossy(&output.stdout);
+    let generated = PathBuf::from(format!("{} (generated)", path.display()));
+    // In the syntax of the declaring file, unless it is clearly JSON
+    let format = match cfg!(feature = "json") && data.trim_start().starts_with('{') {
+        true => Format::Json,
+        false => Format::of(path),
+    };
+    let mut customs = parse_customs_file(&generated, data.as_ref(), format)?;
+
+    if customs.generator.is_some() {
+        return Err(Error::GeneratorFailed(
+            path.display().to_string(),
+            "the generated file must not declare a generator".into(),
+        ));
+    }
+
+    // Marks the customs as generated
+    customs.generator = Some(generator.to_string());
+    Ok(customs)
+}
+
+fn shell_command(command: &str) -> std::process::Command {
+    if cfg!(windows) {
+        let mut shell = std::process::Command::new("cmd");
+        shell.args(["/C", command]);
+        shell
+    } else {
+        let mut shell = std::process::Command::new("sh");
+        shell.args(["-c", command]);
+        shell
+    }
+}
+
+/// JSON and YAML are only accepted with the `json` and `yaml` features.
+fn parse_customs_file(path: &Path, data: &str, format: Format) -> Result<CustomsFile> {
+    #[cfg(feature = "json")]
+    if format == Format::Json {
+        return serde_json::from_str(data)
+            .map_err(|e| Error::InvalidJson(path.display().to_string(), e));
+    }
+    #[cfg(not(feature = "json"))]
+    debug_assert!(
+        format != Format::Json,
+        "JSON customs files need the json feature"
+    );
+
+    #[cfg(feature = "yaml")]
+    if format == Format::Yaml {
+        return serde_yaml::from_str(data)
+            .map_err(|e| Error::InvalidYaml(path.display().to_string(), e));
+    }
+    #[cfg(not(feature = "yaml"))]
+    debug_assert!(
+        format != Format::Yaml,
+        "YAML customs files need the yaml feature"
+    );
+
+    parse_customs_toml(path, data)
+}
+
+/// Parses (parts of) a customs file, errors carry the path and a hint for common mistakes.
+pub fn parse_customs_toml<T: DeserializeOwned>(path: &Path, data: &str) -> Result<T> {
+    toml::from_str(data).map_err(|source| Error::InvalidToml {
+        path: path.display().to_string(),
+        hint: hint_for(data, &source),
+        source: Box::new(source),
+    })
+}
+
+fn hint_for(data: &str, error: &toml::de::Error) -> Option<&'static str> {
+    let message = error.message();
+
+    if data.lines().any(|e| e.trim() == "[regulation]") {
+        return Some(
+            "regulations are an array of tables, use `[[regulation]]` instead of `[regulation]`",
+        );
+    }
+
+    if message.starts_with("unknown field") && message.contains('_') {
+        return Some("keys are written in kebab-case, e.g. `platform-targets`");
+    }
+
+    if message.contains("did not match any variant of untagged enum Jobs") {
+        return Some(
+            "jobs are either a list of names, e.g. `jobs = [\"build\"]`, or a table of `[regulation.jobs]`",
+        );
+    }
+
+    None
+}
+
+/// Regulations for packages without a customs file with `--implicit`, to try customs without configuring it.
+const IMPLICIT_CUSTOMS: &str = r#"
+[[regulation]]
+platform-targets = ["host"]
+build-targets = ["all"]
+jobs = ["check", "test", "clippy"]
+"#;
+
+/// Stands in for the path of the customs file of implicit regulations.
+pub const IMPLICIT_CUSTOMS_PATH: &str = "<implicit>";
+
+pub fn implicit_customs() -> Result<CustomsFile> {
+    parse_customs_toml(Path::new(IMPLICIT_CUSTOMS_PATH), IMPLICIT_CUSTOMS)
+}
+
+/// The customs of a single package with defaults applied.
+#[derive(Debug, Clone)]
+pub struct PackageCustoms {
+    pub path: Utf8PathBuf,
+    pub customs: CustomsFile,
+    /// All customs files with a default section that were considered for this package.
+    pub defaults: Vec<Utf8PathBuf>,
+    /// The customs file whose default filled in unset fields of any regulation.
+    pub applied_default: Option<Utf8PathBuf>,
+}
+
+pub fn load_customs(
+    package: &Package,
+    metadata: &Metadata,
+    exclusions: &Exclusions,
+    files: &CustomsFiles,
+) -> Result<Option<PackageCustoms>> {
+    let workspace_root = metadata.workspace_root.clone();
+
+    let crate_customs_path = find_customs_file(
+        package
+            .manifest_path
+            .parent()
+            .expect("manifest must be in directory"),
+    )?;
+
+    // e.g. a vendored crate that is a member of the workspace
+    let Some(crate_customs_path) = crate_customs_path.filter(|e| !exclusions.contains(e)) else {
+        return Ok(None);
+    };
+
+    let mut crate_customs = files.read(crate_customs_path.as_std_path())?;
+    crate_customs.expand_presets()?;
+
+    // Take all Customs.toml upwards (and including) the current crate
+    // to find the next applicable default
+    let ancestor_defaults = package
+        .manifest_path
+        .ancestors()
+        .take_while(|e| e.as_std_path() != workspace_root.as_std_path())
+        // Safety: because the iterator is below the workspace root,
+        // there is at least the workspace root as a parent.
+        .map(|e| find_customs_file(e.parent().unwrap()))
+        .filter_map(Result::transpose)
+        .filter_ok(|e| !exclusions.contains(e))
+        .map(|e| {
+            e.and_then(|e| {
+                files
+                    .read(e.as_std_path())
+                    .map(|customs| (e, customs.default))
+            })
+        })
+        .filter_map_ok(|(path, default)| default.map(|default| (path, default)))
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .rev()
+        .collect::<Vec<_>>();
+
+    let defaults = ancestor_defaults.iter().map(|(e, _)| e.clone()).collect();
+    let mut applied_default = None;
+
+    // A preset is more specific than a default, so it takes precedence
+    for regulation in crate_customs.regulation.iter_mut() {
+        regulation.apply_preset()?;
+    }
+
+    // fill any empty sets with defaults
+    if let Some((default_path, default)) = ancestor_defaults.last() {
+        let mut applied = false;
+        for regulation in crate_customs.regulation.iter_mut() {
+            applied |= regulation.fill_from_default(default);
+            // the default may have selected a preset
+            regulation.apply_preset()?;
+        }
+        if applied {
+            applied_default = Some(default_path.clone());
+        }
+    }
+
+    Ok(Some(PackageCustoms {
+        path: crate_customs_path,
+        customs: crate_customs,
+        defaults,
+        applied_default,
+    }))
+}
+
+impl CustomsFile {
+    /// Adds the regulations of the preset sets, except those the file redefines by name.
+    fn expand_presets(&mut self) -> Result<()> {
+        for set in self.presets.iter() {
+            for regulation in set.regulations()? {
+                let overridden = self
+                    .regulation
+                    .iter()
+                    .any(|e| e.name.is_some() && e.name == regulation.name);
+                if !overridden {
+                    self.regulation.push(regulation);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Regulation {
+    fn apply_preset(&mut self) -> Result<()> {
+        if let Some(preset) = self.preset {
+            self.fill_from_default(&preset.regulation()?);
+        }
+
+        Ok(())
+    }
+
+    /// Fills any unset fields from `default`, returns whether anything was filled in.
+    fn fill_from_default(&mut self, default: &Regulation) -> bool {
+        let mut filled = false;
+
+        if self.platform_targets.is_empty() && !default.platform_targets.is_empty() {
+            self.platform_targets = default.platform_targets.clone();
+            filled = true;
+        }
+
+        if self.build_targets.is_empty() && !default.build_targets.is_empty() {
+            self.build_targets = default.build_targets.clone();
+            filled = true;
+        }
+
+        if self.jobs.is_empty() && !default.jobs.is_empty() {
+            self.jobs = default.jobs.clone();
+            filled = true;
+        }
+
+        if self.default_features.is_none() && default.default_features.is_some() {
+            self.default_features = default.default_features;
+            filled = true;
+        }
+
+        if self.toolchain.is_none() && default.toolchain.is_some() {
+            self.toolchain = default.toolchain.clone();
+            filled = true;
+        }
+
+        if self.config_file.is_none() && default.config_file.is_some() {
+            self.config_file = default.config_file.clone();
+            filled = true;
+        }
+
+        if self.host_os.is_empty() && !default.host_os.is_empty() {
+            self.host_os = default.host_os.clone();
+            filled = true;
+        }
+
+        if self.rustc_wrapper.is_none() && default.rustc_wrapper.is_some() {
+            self.rustc_wrapper = default.rustc_wrapper.clone();
+            filled = true;
+        }
+
+        if self.rustc.is_none() && default.rustc.is_some() {
+            self.rustc = default.rustc.clone();
+            filled = true;
+        }
+
+        if self.cfgs.is_empty() && !default.cfgs.is_empty() {
+            self.cfgs = default.cfgs.clone();
+            filled = true;
+        }
+
+        if self.rustflags.is_empty() && !default.rustflags.is_empty() {
+            self.rustflags = default.rustflags.clone();
+            filled = true;
+        }
+
+        if self.preset.is_none() && default.preset.is_some() {
+            self.preset = default.preset;
+            filled = true;
+        }
+
+        if self.failure_log_lines.is_none() && default.failure_log_lines.is_some() {
+            self.failure_log_lines = default.failure_log_lines;
+            filled = true;
+        }
+
+        if self.isolated_tmp.is_none() && default.isolated_tmp.is_some() {
+            self.isolated_tmp = default.isolated_tmp;
+            filled = true;
+        }
+
+        if self.isolated_cargo_home.is_none() && default.isolated_cargo_home.is_some() {
+            self.isolated_cargo_home = default.isolated_cargo_home;
+            filled = true;
+        }
+
+        if self.locked.is_none() && default.locked.is_some() {
+            self.locked = default.locked;
+            filled = true;
+        }
+
+        if self.offline.is_none() && default.offline.is_some() {
+            self.offline = default.offline;
+            filled = true;
+        }
+
+        if self.ignore_rust_version.is_none() && default.ignore_rust_version.is_some() {
+            self.ignore_rust_version = default.ignore_rust_version;
+            filled = true;
+        }
+
+        if self.job_overrides.is_empty() && !default.job_overrides.is_empty() {
+            self.job_overrides = default.job_overrides.clone();
+            filled = true;
+        }
+
+        if self.local_reduction.is_empty() && !default.local_reduction.is_empty() {
+            self.local_reduction = default.local_reduction.clone();
+            filled = true;
+        }
+
+        if self.boards.is_empty() && !default.boards.is_empty() {
+            self.boards = default.boards.clone();
+            filled = true;
+        }
+
+        filled
+    }
+
+    pub fn skip_reason(&self) -> Option<SkipReason> {
+        if !self.host_os.is_empty() && !self.host_os.iter().any(|e| e == std::env::consts::OS) {
+            return Some(SkipReason::HostOs);
+        }
+
+        None
+    }
+
+    /// The job as run for the given targets, with the overrides for these applied.
+    fn job_for_targets(
+        job_overrides: &BTreeMap<String, BTreeMap<String, JobOverride>>,
+        job: &Job,
+        platform_target: &str,
+        build_target: &str,
+    ) -> Job {
+        let mut job = job.clone();
+        let build_target_overrides = std::mem::take(&mut job.build_target_overrides);
+
+        if let Some(job_override) = job_overrides
+            .get(platform_target)
+            .and_then(|e| e.get(job.name.as_str()))
+        {
+            job.apply_override(job_override);
+        }
+        if let Some(job_override) = build_target_overrides.get(build_target) {
+            job.apply_override(job_override);
+        }
+        job
+    }
+
+    /// Expands the regulation along with why each check is skipped, if so.
+    pub fn expand_with_skip_reasons(
+        self,
+        local: bool,
+    ) -> Vec<(RegulationCheck, Option<SkipReason>)> {
+        let skipped = self.skip_reason();
+        let reductions = match local {
+            true => self.local_reduction.clone(),
+            false => Vec::new(),
+        };
+        let first_platform_target = self.platform_targets.first().cloned();
+        let first_feature_set = self.feature_axis().first().cloned();
+
+        self.expand()
+            .into_iter()
+            .map(|check| {
+                let reduced = reductions.iter().any(|e| match e {
+                    LocalReduction::FirstPlatformTarget => {
+                        Some(&check.platform_target) != first_platform_target.as_ref()
+                    }
+                    LocalReduction::FirstFeatureSet => {
+                        Some(&(check.features.clone(), check.default_features))
+                            != first_feature_set.as_ref()
+                    }
+                    LocalReduction::Skip => true,
+                });
+                let skipped = skipped.or(reduced.then_some(SkipReason::LocalRun));
+                (check, skipped)
+            })
+            .collect()
+    }
+
+    /// Each feature set of the matrix with whether the default features are enabled.
+    fn feature_axis(&self) -> Vec<(Vec<String>, bool)> {
+        let default_features = self.default_features.unwrap_or(true);
+        self.feature_sets
+            .iter()
+            .map(|set| {
+                let mut features = Vec::new();
+                let mut enabled = default_features;
+                for feature in set.iter().flat_map(|e| e.split([',', ' '])) {
+                    match feature {
+                        "" => {}
+                        "--no-default-features" => enabled = false,
+                        "default" => enabled = true,
+                        _ => features.push(feature.to_string()),
+                    }
+                }
+                (features, enabled)
+            })
+            .collect()
+    }
+
+    pub fn expand(self) -> Vec<RegulationCheck> {
+        let build_targets = self.build_targets.clone();
+        const ALL_BUILD_TARGETS_DESIGNATOR: &str = "all";
+        if build_targets
+            .iter()
+            .any(|e| e == ALL_BUILD_TARGETS_DESIGNATOR)
+            && build_targets.len() != 1
+        {
+            panic!("build-targets all can only be alone");
+        }
+
+        let features = self.feature_axis();
+        let mut jobs = self.jobs.into_jobs();
+        jobs.sort_by_key(Job::reuse_order);
+
+        let toolchain = self.toolchain.clone();
+        let config_file = self.config_file.clone();
+        let rustc_wrapper = self.rustc_wrapper.clone();
+        let rustc = self.rustc.clone();
+        let cfgs = self.cfgs.clone();
+        let rustflags = self.rustflags.clone();
+        let preset = self.preset;
+        let failure_log_lines = self.failure_log_lines;
+        let job_overrides = self.job_overrides.clone();
+        let isolated_tmp = self.isolated_tmp.unwrap_or(false);
+        let isolated_cargo_home = self.isolated_cargo_home.unwrap_or(false);
+        let locked = self.locked.unwrap_or(false);
+        let offline = self.offline.unwrap_or(false);
+        let ignore_rust_version = self.ignore_rust_version.unwrap_or(false);
+        let board = self.board.clone();
+        let runner = self.runner.clone();
+        let name = self.name.clone();
+        let tags = self.tags.clone();
+        // Jobs vary fastest, so jobs sharing artifacts run right after each other.
+        self.platform_targets
+            .iter()
+            .cartesian_product(build_targets.iter())
+            .cartesian_product(features.iter())
+            .cartesian_product(jobs.iter())
+            // Compile-fail tests are meaningless to cross compile
+            .filter(|(((p, b), _), _)| !b.starts_with(crate::UI_TEST_PREFIX) || *p == "host")
+            .map(|(((p, b), (f, default_features)), j)| RegulationCheck {
+                platform_target: p.clone(),
+                build_target: b.clone(),
+                job: Regulation::job_for_targets(&job_overrides, j, p, b),
+                features: f.clone(),
+                default_features: *default_features,
+                toolchain: toolchain.clone(),
+                config_file: config_file.clone(),
+                rustc_wrapper: rustc_wrapper.clone(),
+                rustc: rustc.clone(),
+                cfgs: cfgs.clone(),
+                rustflags: rustflags.clone(),
+                rustdocflags: Vec::new(),
+                preset,
+                failure_log_lines,
+                isolated_tmp,
+                isolated_cargo_home,
+                locked,
+                offline,
+                ignore_rust_version,
+                board: board.clone(),
+                runner: runner.clone(),
+                regulation: name.clone(),
+                tags: tags.clone(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parallelism_resolves_relative_to_cpus() -> Result<()> {
+        let cpus = std::thread::available_parallelism().map_or(1, usize::from);
+        let relative = |e: &str| Parallelism::Relative(e.into()).resolve();
+
+        assert_eq!(Parallelism::Count(4).resolve()?, 4);
+        assert_eq!(Parallelism::Count(0).resolve()?, 1);
+        assert_eq!(relative("num-cpus")?, cpus);
+        assert_eq!(relative("num-cpus/2")?, (cpus / 2).max(1));
+        assert_eq!(relative(&format!("num-cpus/{}", cpus + 1))?, 1);
+        for invalid in ["num-cpus/0", "num-cpus*2", "cpus"] {
+            assert!(matches!(
+                relative(invalid),
+                Err(Error::InvalidParallelism(_))
+            ));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn preset_sets_add_regulations_not_redefined_by_name() -> Result<()> {
+        let mut file: CustomsFile = parse_customs_toml(
+            Path::new(CUSTOMS_FILE_NAME),
+            "presets = [\"lib-quality\"]\n\n\
+             [[regulation]]\nname = \"lib-quality/tests\"\njobs = [\"build\"]\n",
+        )?;
+        file.expand_presets()?;
+
+        let names = file
+            .regulation
+            .iter()
+            .map(|e| e.name.as_deref().unwrap_or_default())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            ["lib-quality/tests", "lib-quality/lints", "lib-quality/docs"]
+        );
+        assert!(matches!(&file.regulation[0].jobs, Jobs::Short(e) if e == &["build"]));
+        Ok(())
+    }
+
+    #[test]
+    fn features_are_feature_sets_in_cargo_syntax() -> Result<()> {
+        let file: CustomsFile = parse_customs_toml(
+            Path::new(CUSTOMS_FILE_NAME),
+            "[[regulation]]\nfeatures = [\"a\", \"b,c\", \"--no-default-features\"]\n",
+        )?;
+
+        let regulation = &file.regulation[0];
+        assert_eq!(
+            regulation.feature_sets,
+            [vec!["a"], vec!["b", "c"], vec!["--no-default-features"]]
+        );
+        assert_eq!(
+            regulation.feature_axis(),
+            [
+                (vec!["a".to_string()], true),
+                (vec!["b".to_string(), "c".to_string()], true),
+                (vec![], false),
+            ]
+        );
+        Ok(())
+    }
+}
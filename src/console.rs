@@ -0,0 +1,206 @@
+use std::io::IsTerminal;
+use std::time::Duration;
+
+use anstyle::{AnsiColor, Style};
+use clap::ValueEnum;
+
+use crate::ci::Ci;
+use crate::transcript::Transcript;
+
+const HEADER: Style = Style::new()
+    .bold()
+    .fg_color(Some(anstyle::Color::Ansi(AnsiColor::Green)));
+const WARNING: Style = Style::new()
+    .bold()
+    .fg_color(Some(anstyle::Color::Ansi(AnsiColor::Yellow)));
+
+/// Width assumed if the terminal does not tell.
+const DEFAULT_WIDTH: usize = 80;
+
+/// How much of the checks' output is shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Check headers and the output of failed checks.
+    Normal,
+    /// Also the output of successful checks, streamed while running.
+    Verbose,
+    /// Also the command line and environment of each check.
+    Trace,
+}
+
+impl Verbosity {
+    pub fn from_count(count: u8) -> Verbosity {
+        match count {
+            0 => Verbosity::Normal,
+            1 => Verbosity::Verbose,
+            _ => Verbosity::Trace,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ColorChoice {
+    /// Colored if stderr is a terminal.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Everything customs and the checks print to the user.
+#[derive(Debug, Clone)]
+pub struct Console {
+    pub verbosity: Verbosity,
+    color: bool,
+    pub ci: Option<Ci>,
+    /// Interval of the "still running" lines of checks with captured output.
+    pub heartbeat: Option<Duration>,
+    /// Silence after which a check with captured output is reported as possibly hanging.
+    pub stall_warning: Option<Duration>,
+    /// Whether the resource usage of the host is sampled during each check.
+    pub telemetry: bool,
+    /// Whether checks run at the same time, so each line they stream is prefixed with their ID.
+    pub concurrent: bool,
+    /// Where the whole run is logged with `--log-file`.
+    pub transcript: Option<Transcript>,
+}
+
+impl Console {
+    pub fn new(verbosity: Verbosity, color: ColorChoice, ci: Option<Ci>) -> Console {
+        let color = match color {
+            ColorChoice::Auto => std::io::stderr().is_terminal(),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        };
+        Console {
+            verbosity,
+            color,
+            ci,
+            heartbeat: None,
+            stall_warning: None,
+            telemetry: false,
+            concurrent: false,
+            transcript: None,
+        }
+    }
+
+    /// `CARGO_TERM_COLOR` for the checks.
+    ///
+    /// The output of checks is often captured, so cargo cannot detect the terminal itself.
+    pub fn child_color(&self) -> &'static str {
+        match self.color {
+            true => "always",
+            false => "never",
+        }
+    }
+
+    fn paint(&self, style: Style, text: &str) -> String {
+        match self.color {
+            true => format!("{style}{text}{style:#}"),
+            false => text.to_string(),
+        }
+    }
+
+    /// Appends a line to the transcript of the run, if any.
+    pub fn transcript(&self, text: &str) {
+        if let Some(transcript) = self.transcript.as_ref() {
+            transcript.line(text);
+        }
+    }
+
+    /// Announces a check, in CI its output is collapsed into a section.
+    pub fn begin_check(&self, id: &str) {
+        match self.ci {
+            Some(ci) => ci.begin_section(id, format!("Checking {id}").as_str()),
+            None => self.header_line("Checking", id),
+        }
+    }
+
+    pub fn end_check(&self, id: &str) {
+        if let Some(ci) = self.ci {
+            ci.end_section(id);
+        }
+    }
+
+    pub fn header_line(&self, title: &str, text: &str) {
+        eprintln!("{} {text}", self.paint(HEADER, title));
+    }
+
+    /// Prints two aligned columns, falling back to a line per cell if they do not fit.
+    pub fn table(&self, title: &str, rows: &[(String, String)]) {
+        eprintln!("{}", self.paint(WARNING, title));
+
+        let key_width = rows.iter().map(|e| e.0.len() + 1).max().unwrap_or_default();
+        let value_width = rows.iter().map(|e| e.1.len()).max().unwrap_or_default();
+        let fits = 2 + key_width + 1 + value_width <= terminal_width();
+
+        for (key, value) in rows {
+            let key = format!("{key}:");
+            match fits {
+                true => eprintln!("  {key:key_width$} {value}"),
+                false => eprintln!("  {key}\n    {value}"),
+            }
+        }
+    }
+
+    /// Prints rows of cells in aligned columns, the first row being the heading.
+    pub fn columns(&self, title: &str, rows: &[Vec<String>]) {
+        eprintln!("{}", self.paint(WARNING, title));
+
+        let count = rows.iter().map(Vec::len).max().unwrap_or_default();
+        let widths = (0..count)
+            .map(|i| {
+                rows.iter()
+                    .filter_map(|e| e.get(i))
+                    .map(String::len)
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<_>>();
+        for row in rows {
+            let line = row
+                .iter()
+                .zip(widths.iter())
+                .map(|(cell, width)| format!("{cell:width$}"))
+                .collect::<Vec<_>>()
+                .join("  ");
+            eprintln!("  {}", line.trim_end());
+        }
+    }
+}
+
+/// The width of the terminal, `COLUMNS` takes precedence if the shell exports it.
+fn terminal_width() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|e| e.parse().ok())
+        .or_else(tty_width)
+        .unwrap_or(DEFAULT_WIDTH)
+}
+
+/// The width of the terminal on stderr according to `stty`, a system tool instead of a system call.
+#[cfg(unix)]
+fn tty_width() -> Option<usize> {
+    if !std::io::stderr().is_terminal() {
+        return None;
+    }
+    let tty = std::fs::File::open("/dev/tty").ok()?;
+    let output = std::process::Command::new("stty")
+        .arg("size")
+        .stdin(tty)
+        .stderr(std::process::Stdio::null())
+        .output()
+        .ok()?;
+    // "<rows> <columns>"
+    String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .nth(1)?
+        .parse()
+        .ok()
+        .filter(|e| *e > 0)
+}
+
+#[cfg(not(unix))]
+fn tty_width() -> Option<usize> {
+    None
+}
@@ -0,0 +1,86 @@
+use std::process::{Command, Stdio};
+
+use cargo_metadata::Metadata;
+use cargo_metadata::camino::{Utf8Path, Utf8PathBuf};
+
+use crate::Result;
+use crate::config::{self, CustomsFiles};
+use crate::git::git;
+
+/// Written by `cargo vendor` into each crate it vendors, regardless of the name of the vendor directory.
+const VENDOR_CHECKSUM_FILE: &str = ".cargo-checksum.json";
+
+/// Paths whose customs files and changes are not the workspace's own.
+///
+/// These are the target directory, crates vendored by `cargo vendor`, paths ignored by git and, if the customs file
+/// of the workspace root sets `exclude-submodules = true`, git submodules.
+#[derive(Debug)]
+pub struct Exclusions {
+    workspace_root: Utf8PathBuf,
+    directories: Vec<Utf8PathBuf>,
+}
+
+impl Exclusions {
+    pub fn new(metadata: &Metadata, files: &CustomsFiles) -> Result<Exclusions> {
+        let workspace_root = metadata.workspace_root.clone();
+        let mut directories = vec![metadata.target_directory.clone()];
+        if config::workspace_excludes_submodules(metadata, files)? {
+            directories.extend(submodules(&workspace_root));
+        }
+        Ok(Exclusions {
+            workspace_root,
+            directories,
+        })
+    }
+
+    /// Whether the path is vendored or ignored by git.
+    pub fn contains(&self, path: &Utf8Path) -> bool {
+        self.is_vendored(path) || ignored(&self.workspace_root, path)
+    }
+
+    /// Whether the path is in the target directory, a vendored crate or an excluded submodule.
+    ///
+    /// Unlike [`Exclusions::contains`], this needs no git invocation, e.g. for many changed files.
+    pub fn is_vendored(&self, path: &Utf8Path) -> bool {
+        if self.directories.iter().any(|e| path.starts_with(e)) {
+            return true;
+        }
+        // A member that merely lives in a directory called `vendor` is the workspace's own
+        path.ancestors()
+            .skip(1)
+            .take_while(|e| e.starts_with(&self.workspace_root) && *e != self.workspace_root)
+            .any(|e| e.join(VENDOR_CHECKSUM_FILE).is_file())
+    }
+}
+
+/// Absolute paths of the submodules of the repository at the workspace root, if any.
+fn submodules(workspace_root: &Utf8Path) -> Vec<Utf8PathBuf> {
+    let paths = git(
+        workspace_root,
+        &[
+            "config",
+            "--file",
+            ".gitmodules",
+            "--get-regexp",
+            r"^submodule\..*\.path$",
+        ],
+    );
+    // Without submodules, git fails to find the key
+    paths
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|e| e.split_once(' '))
+        .map(|(_, path)| workspace_root.join(path))
+        .collect()
+}
+
+fn ignored(workspace_root: &Utf8Path, path: &Utf8Path) -> bool {
+    // Exits with 1 if not ignored, and with 128 outside of git repositories
+    Command::new("git")
+        .args(["check-ignore", "--quiet", path.as_str()])
+        .current_dir(workspace_root.as_std_path())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|e| e.success())
+}
@@ -0,0 +1,65 @@
+use cargo_metadata::Package;
+use serde::Deserialize;
+
+use crate::{Error, RegulationCheck, Result};
+
+/// The toolchain docs.rs builds with, a dated nightly of a regulation is kept.
+const TOOLCHAIN: &str = "nightly";
+
+/// `[package.metadata.docs.rs]`, as far as it changes the build.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct Metadata {
+    #[serde(default)]
+    features: Vec<String>,
+    #[serde(default)]
+    all_features: bool,
+    #[serde(default)]
+    no_default_features: bool,
+    default_target: Option<String>,
+    #[serde(default)]
+    targets: Vec<String>,
+    #[serde(default)]
+    rustc_args: Vec<String>,
+    #[serde(default)]
+    rustdoc_args: Vec<String>,
+    #[serde(default)]
+    cargo_args: Vec<String>,
+}
+
+/// Configures a `docs-rs` check like docs.rs builds the documentation of the package.
+pub fn apply(package: &Package, check: &mut RegulationCheck) -> Result<()> {
+    let metadata: Metadata = match package.metadata.pointer("/docs/rs") {
+        Some(value) => serde_json::from_value(value.clone())
+            .map_err(|e| Error::InvalidDocsRsMetadata(package.manifest_path.to_string(), e))?,
+        None => Metadata::default(),
+    };
+
+    if metadata.all_features {
+        check.features = package.features.keys().cloned().collect();
+    } else {
+        check.features.extend(metadata.features);
+    }
+    check.default_features &= !metadata.no_default_features;
+
+    // docs.rs builds the default target first, which is the one failing a release
+    if let Some(target) = metadata
+        .default_target
+        .or_else(|| metadata.targets.first().cloned())
+    {
+        check.platform_target = target;
+    }
+
+    check.rustflags.extend(metadata.rustc_args);
+    check.rustdocflags.extend(metadata.rustdoc_args);
+    check.job.cargo_args.extend(metadata.cargo_args);
+    // Flags like `--cfg docsrs` need nightly, whatever toolchain the package is checked with otherwise
+    if !check
+        .toolchain
+        .as_deref()
+        .is_some_and(|e| e.starts_with(TOOLCHAIN))
+    {
+        check.toolchain = Some(TOOLCHAIN.into());
+    }
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+use cargo_metadata::MetadataCommand;
+
+use crate::cancellation::Cancellation;
+use crate::config::CustomsFiles;
+use crate::executor::Executor;
+use crate::plan::{Plan, build_plan};
+use crate::run::Outcome;
+use crate::{Cli, Error, Result};
+
+/// Plans and runs checks from another program, e.g. an IDE extension or a watch mode embedding customs.
+///
+/// ```no_run
+/// use cargo_customs::{Cancellation, Options, Subprocess};
+///
+/// let options = Options::parse(["--package", "foo", "--keep-going"])?;
+/// let plan = options.plan()?;
+/// let cancellation = Cancellation::default();
+/// let outcome = options.execute(&plan, &Subprocess, &cancellation);
+/// for (id, check) in outcome.checks.iter() {
+///     println!("{id}: passed {}, cancelled {}", check.passed, check.cancelled);
+/// }
+/// # Ok::<(), cargo_customs::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct Options {
+    /// Where customs runs, like the current directory of `cargo customs`, the current directory if `None`.
+    pub directory: Option<PathBuf>,
+    args: Cli,
+}
+
+/// What a run started with [`Options::execute`] resulted in.
+#[derive(Debug)]
+pub struct RunOutcome {
+    /// Fails like `cargo customs`, e.g. with [`Error::CheckFailed`] or [`Error::Cancelled`].
+    pub result: Result<()>,
+    /// Outcomes of the executed checks by ID, checks stopped by the cancellation are marked [`Outcome::cancelled`].
+    pub checks: BTreeMap<String, Outcome>,
+}
+
+impl Options {
+    /// Options from the arguments of `cargo customs`, e.g. `["--package", "foo", "--jobs", "4"]`.
+    pub fn parse<I, T>(args: I) -> Result<Options>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let args = std::iter::once(OsString::from("cargo-customs"))
+            .chain(args.into_iter().map(Into::into));
+        Ok(Options {
+            directory: None,
+            args: Cli::try_parse_args(args).map_err(Error::InvalidArguments)?,
+        })
+    }
+
+    /// The checks `cargo customs` would run in the directory.
+    pub fn plan(&self) -> Result<Plan> {
+        let mut command = MetadataCommand::new();
+        if let Some(directory) = self.directory.as_ref() {
+            command.current_dir(directory);
+        }
+        let metadata = command.exec().map_err(|e| match e {
+            cargo_metadata::Error::CargoMetadata { stderr } => Error::Cargo(stderr),
+            _ => Error::Unexpected(e.into()),
+        })?;
+
+        build_plan(&self.args, &metadata, &CustomsFiles::default())
+    }
+
+    /// Runs the checks of the plan with the executor until they are done or `cancellation` is cancelled.
+    pub fn execute(
+        &self,
+        plan: &Plan,
+        executor: &dyn Executor,
+        cancellation: &Cancellation,
+    ) -> RunOutcome {
+        let mut checks = BTreeMap::new();
+        let result = self.args.console().and_then(|console| {
+            plan.execute(
+                executor,
+                cancellation,
+                None,
+                &console,
+                self.args.schedule(),
+                &mut checks,
+            )
+        });
+        RunOutcome { result, checks }
+    }
+}
@@ -0,0 +1,49 @@
+use std::collections::HashSet;
+
+use crate::plan::{Plan, PlannedCheck};
+
+/// Replaces the command of every check with the rendered `template`.
+///
+/// Checks rendering to the same command in the same directory are only kept once,
+/// e.g. a template only using `{package}` runs once per package.
+pub fn exec_plan(plan: Plan, template: &[String]) -> Plan {
+    let mut seen = HashSet::new();
+
+    let checks = plan
+        .checks
+        .into_iter()
+        .filter(|e| e.skipped.is_none())
+        .filter_map(|mut check| {
+            let mut args = template
+                .iter()
+                .map(|e| render(e, &check))
+                .collect::<Vec<_>>();
+            check.command.program = args.remove(0);
+            check.command.args = args;
+            check.evaluation = None;
+
+            let key = (
+                check.command.current_dir.clone(),
+                check.command.program.clone(),
+                check.command.args.clone(),
+            );
+            seen.insert(key).then_some(check)
+        })
+        .collect();
+
+    Plan { checks, ..plan }
+}
+
+/// Substitutes the placeholders describing the check.
+fn render(template: &str, check: &PlannedCheck) -> String {
+    template
+        .replace("{package}", check.package.as_str())
+        .replace("{target}", check.check.platform_target.as_str())
+        .replace("{build-target}", check.check.build_target.as_str())
+        .replace("{job}", check.check.job.name.as_str())
+        .replace("{features}", check.check.features.join(",").as_str())
+        .replace(
+            "{dir}",
+            check.command.current_dir.to_string_lossy().as_ref(),
+        )
+}
@@ -0,0 +1,197 @@
+use std::collections::BTreeMap;
+
+use cargo_metadata::camino::Utf8Path;
+
+use crate::Result;
+use crate::cancellation::Cancellation;
+use crate::console::Console;
+use crate::plan::PlannedCheck;
+use crate::test_results::TestOutcome;
+
+/// Runs single checks for [`crate::Options::execute`], which only decides what runs when.
+///
+/// With `--jobs`, several checks are executed at the same time from different threads.
+///
+/// Other implementations may run the command of a check elsewhere, e.g. in a container or on a remote host,
+/// or replace running it in tests:
+///
+/// ```no_run
+/// use cargo_customs::{Cancellation, CheckOutcome, Console, Executor, Options, PlannedCheck};
+/// use cargo_metadata::camino::Utf8Path;
+///
+/// /// Runs the command of each check in a container.
+/// struct Docker;
+///
+/// impl Executor for Docker {
+///     fn execute(
+///         &self,
+///         check: &PlannedCheck,
+///         _console: &Console,
+///         _output_directory: &Utf8Path,
+///         _cancellation: &Cancellation,
+///     ) -> CheckOutcome {
+///         let status = std::process::Command::new("docker")
+///             .args(["run", "--rm", "rust", check.command.program.as_str()])
+///             .args(check.command.args.iter())
+///             .status();
+///         let result = match status {
+///             Ok(e) if e.success() => Ok(()),
+///             Ok(_) => Err(cargo_customs::Error::CheckFailed(check.id.clone())),
+///             Err(e) => Err(e.into()),
+///         };
+///         CheckOutcome { result, tests: Default::default() }
+///     }
+/// }
+///
+/// let options = Options::parse(["--workspace"])?;
+/// let plan = options.plan()?;
+/// let outcome = options.execute(&plan, &Docker, &Cancellation::default());
+/// # Ok::<(), cargo_customs::Error>(())
+/// ```
+pub trait Executor: Sync {
+    /// Runs the check, its logs go to `output_directory`.
+    ///
+    /// Once `cancellation` is cancelled, the check should stop and fail with [`crate::Error::Cancelled`].
+    fn execute(
+        &self,
+        check: &PlannedCheck,
+        console: &Console,
+        output_directory: &Utf8Path,
+        cancellation: &Cancellation,
+    ) -> CheckOutcome;
+}
+
+/// What running a single check resulted in.
+#[derive(Debug)]
+pub struct CheckOutcome {
+    /// Fails with [`crate::Error::CheckFailed`] if the check itself failed,
+    /// with [`crate::Error::Cancelled`] if it was cancelled and with other errors if it could not be run.
+    pub result: Result<()>,
+    /// Results of the individual tests, if the job reports any.
+    pub tests: BTreeMap<String, TestOutcome>,
+}
+
+/// Runs the command of each check as a child process of customs.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Subprocess;
+
+impl Executor for Subprocess {
+    fn execute(
+        &self,
+        check: &PlannedCheck,
+        console: &Console,
+        output_directory: &Utf8Path,
+        cancellation: &Cancellation,
+    ) -> CheckOutcome {
+        let mut tests = BTreeMap::new();
+        let result = check.execute(console, output_directory, cancellation, &mut tests);
+        CheckOutcome { result, tests }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cargo_metadata::MetadataCommand;
+    use clap::Parser;
+
+    use super::*;
+    use crate::config::CustomsFiles;
+    use crate::console::{ColorChoice, Verbosity};
+    use crate::plan::{Schedule, build_plan};
+    use crate::{Cli, Error};
+
+    /// Fails all `build` checks and passes all others, without running anything.
+    struct FailingBuilds;
+
+    impl Executor for FailingBuilds {
+        fn execute(
+            &self,
+            check: &PlannedCheck,
+            _: &Console,
+            _: &Utf8Path,
+            _: &Cancellation,
+        ) -> CheckOutcome {
+            let result = match check.check.job.name.as_str() {
+                "build" => Err(Error::CheckFailed(check.id.clone())),
+                _ => Ok(()),
+            };
+            CheckOutcome {
+                result,
+                tests: BTreeMap::new(),
+            }
+        }
+    }
+
+    #[test]
+    fn plan_records_outcomes_of_the_executor() {
+        let metadata = MetadataCommand::new()
+            .current_dir("tests/workspace")
+            .exec()
+            .unwrap();
+        let args = Cli::parse_from(["cargo-customs", "--package", "foo"]);
+        let plan = build_plan(&args, &metadata, &CustomsFiles::default()).unwrap();
+        let console = Console::new(Verbosity::Normal, ColorChoice::Never, None);
+
+        let mut outcomes = BTreeMap::new();
+        let schedule = Schedule {
+            keep_going: true,
+            ..Schedule::default()
+        };
+        let result = plan.execute(
+            &FailingBuilds,
+            &Cancellation::default(),
+            None,
+            &console,
+            schedule,
+            &mut outcomes,
+        );
+
+        assert!(matches!(result, Err(Error::CheckFailed(id)) if id == "foo/build/host/all"));
+        assert!(outcomes["foo/fmt/host/all"].passed);
+        assert!(!outcomes["foo/build/host/all"].passed);
+    }
+
+    /// Cancels the run while executing the first check.
+    struct Cancelling;
+
+    impl Executor for Cancelling {
+        fn execute(
+            &self,
+            _: &PlannedCheck,
+            _: &Console,
+            _: &Utf8Path,
+            cancellation: &Cancellation,
+        ) -> CheckOutcome {
+            cancellation.cancel();
+            CheckOutcome {
+                result: Err(Error::Cancelled),
+                tests: BTreeMap::new(),
+            }
+        }
+    }
+
+    #[test]
+    fn cancelled_plan_starts_no_further_checks() {
+        let metadata = MetadataCommand::new()
+            .current_dir("tests/workspace")
+            .exec()
+            .unwrap();
+        let args = Cli::parse_from(["cargo-customs", "--package", "foo"]);
+        let plan = build_plan(&args, &metadata, &CustomsFiles::default()).unwrap();
+        let console = Console::new(Verbosity::Normal, ColorChoice::Never, None);
+
+        let mut outcomes = BTreeMap::new();
+        let result = plan.execute(
+            &Cancelling,
+            &Cancellation::default(),
+            None,
+            &console,
+            Schedule::default(),
+            &mut outcomes,
+        );
+
+        assert!(matches!(result, Err(Error::Cancelled)));
+        assert!(outcomes["foo/fmt/host/all"].cancelled);
+        assert!(!outcomes.contains_key("foo/build/host/all"));
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::BTreeSet;
+
+use cargo_metadata::Metadata;
+use cargo_metadata::camino::Utf8PathBuf;
+use toml_edit::{Array, DocumentMut, InlineTable, Item, Key, Table, TableLike, Value, value};
+
+use crate::config::{CUSTOMS_FILE_NAME, CustomsFile, CustomsFiles, parse_customs_toml};
+use crate::discovery::Exclusions;
+use crate::{Error, Result};
+
+/// Keys of a regulation in canonical order, unknown keys go last.
+const KEY_ORDER: &[&str] = &[
+    "name",
+    "tags",
+    "preset",
+    "jobs",
+    "platform-targets",
+    "build-targets",
+    "feature-sets",
+    "features",
+    "default-features",
+    "toolchain",
+    "config-file",
+    "host-os",
+    "rustc-wrapper",
+    "rustc",
+    "cfgs",
+    "rustflags",
+    "locked",
+    "offline",
+    "ignore-rust-version",
+    "failure-log-lines",
+    "isolated-tmp",
+    "isolated-cargo-home",
+    "job-overrides",
+    "local-reduction",
+    "boards",
+];
+
+/// Lists whose order does not matter.
+const SORTED_KEYS: &[&str] = &["platform-targets", "build-targets", "host-os"];
+
+/// Formats all customs files of the workspace, or with `check` only reports unformatted ones.
+pub fn fmt_config(metadata: &Metadata, check: bool) -> Result<()> {
+    let mut unformatted = 0;
+
+    let exclusions = Exclusions::new(metadata, &CustomsFiles::default())?;
+    for path in customs_files(metadata, &exclusions) {
+        let data = std::fs::read_to_string(path.as_std_path())?;
+        // Only valid files are formatted
+        parse_customs_toml::<CustomsFile>(path.as_std_path(), data.as_str())?;
+
+        let mut document = data.parse::<DocumentMut>().map_err(anyhow::Error::from)?;
+        format_document(&mut document);
+        let formatted = document.to_string();
+
+        if formatted == data {
+            continue;
+        }
+
+        unformatted += 1;
+        match check {
+            true => eprintln!("{path} is not formatted"),
+            false => {
+                std::fs::write(path.as_std_path(), formatted)?;
+                eprintln!("Formatted {path}");
+            }
+        }
+    }
+
+    if check && unformatted > 0 {
+        return Err(Error::UnformattedCustoms(unformatted));
+    }
+
+    Ok(())
+}
+
+/// The TOML customs files of all workspace members and their ancestors up to the workspace root,
+/// except vendored and ignored ones.
+fn customs_files(metadata: &Metadata, exclusions: &Exclusions) -> BTreeSet<Utf8PathBuf> {
+    metadata
+        .workspace_packages()
+        .into_iter()
+        .flat_map(|package| {
+            package
+                .manifest_path
+                .ancestors()
+                .skip(1)
+                .take_while(|e| e.starts_with(&metadata.workspace_root))
+                .map(|e| e.join(CUSTOMS_FILE_NAME))
+                .collect::<Vec<_>>()
+        })
+        .filter(|e| e.is_file() && !exclusions.contains(e))
+        .collect()
+}
+
+fn format_document(document: &mut DocumentMut) {
+    let regulations = document
+        .iter_mut()
+        .filter(|(key, _)| *key == "default" || *key == "regulation")
+        .map(|(_, item)| item);
+
+    for item in regulations {
+        match item {
+            Item::Table(table) => format_table(table),
+            Item::ArrayOfTables(tables) => tables.iter_mut().for_each(format_table),
+            Item::Value(Value::InlineTable(table)) => format_inline_table(table),
+            Item::Value(Value::Array(tables)) => tables
+                .iter_mut()
+                .filter_map(Value::as_inline_table_mut)
+                .for_each(format_inline_table),
+            _ => {}
+        }
+    }
+}
+
+fn rank(key: &Key) -> usize {
+    KEY_ORDER
+        .iter()
+        .position(|e| *e == key.get())
+        .unwrap_or(KEY_ORDER.len())
+}
+
+fn format_table(regulation: &mut Table) {
+    format_regulation(regulation);
+    regulation.sort_values_by(|a, _, b, _| rank(a).cmp(&rank(b)));
+}
+
+fn format_inline_table(regulation: &mut InlineTable) {
+    format_regulation(regulation);
+    regulation.sort_values_by(|a, _, b, _| rank(a).cmp(&rank(b)));
+}
+
+fn format_regulation(regulation: &mut dyn TableLike) {
+    for key in SORTED_KEYS {
+        if let Some(list) = regulation.get_mut(key).and_then(Item::as_array_mut) {
+            sort_strings(list);
+        }
+    }
+
+    // Detailed jobs without any parameters are written in the short syntax
+    if let Some(table) = regulation.get("jobs").and_then(Item::as_table_like)
+        && table
+            .iter()
+            .all(|(_, e)| e.as_table_like().is_some_and(TableLike::is_empty))
+    {
+        let names = table
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .collect::<Array>();
+        // Replaced to drop the formatting of the table
+        regulation.remove("jobs");
+        regulation.insert("jobs", value(names));
+    }
+}
+
+fn sort_strings(list: &mut Array) {
+    let mut values = list
+        .iter()
+        .filter_map(|e| e.as_str().map(str::to_string))
+        .collect::<Vec<_>>();
+
+    // Anything but strings is left alone
+    if values.len() != list.len() || values.is_sorted() {
+        return;
+    }
+
+    values.sort();
+    *list = values.into_iter().collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn formatted(data: &str) -> String {
+        let mut document = data.parse::<DocumentMut>().unwrap();
+        format_document(&mut document);
+        document.to_string()
+    }
+
+    #[test]
+    fn format_orders_keys_sorts_targets_and_shortens_jobs() {
+        let data = r#"# Comments are kept
+[[regulation]]
+build-targets = ["lib", "bins"]
+platform-targets = ["thumbv7em-none-eabihf", "host"]
+jobs = { clippy = {}, check = {} }
+name = "embedded"
+
+[[regulation]]
+jobs = { test = { args = ["--ignored"] } }
+"#;
+        assert_eq!(
+            formatted(data),
+            r#"# Comments are kept
+[[regulation]]
+name = "embedded"
+jobs = ["clippy", "check"]
+platform-targets = ["host", "thumbv7em-none-eabihf"]
+build-targets = ["bins", "lib"]
+
+[[regulation]]
+jobs = { test = { args = ["--ignored"] } }
+"#
+        );
+    }
+
+    #[test]
+    fn format_keeps_formatted_files() {
+        let data = "[[regulation]]\njobs = [\"fmt\"]\nplatform-targets = [\"host\"]\n";
+        assert_eq!(formatted(data), data);
+    }
+}
@@ -0,0 +1,22 @@
+use std::path::Path;
+use std::process::Command;
+
+use cargo_metadata::camino::Utf8Path;
+
+use crate::{Error, Result};
+
+/// Runs git in `directory` and returns its trimmed output.
+pub fn git(directory: &Utf8Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(Path::new(directory.as_str()))
+        .output()?;
+
+    if !output.status.success() {
+        return Err(Error::Git(
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
@@ -0,0 +1,319 @@
+use std::io::{Read, Write};
+use std::process::{Child, Command, Output};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::cancellation::Cancellation;
+use crate::console::Console;
+use crate::history::format_duration;
+use crate::lock_wait;
+use crate::transcript::Transcript;
+
+/// How often the process is polled, bounds the delay of the heartbeat.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Piped output that is still shown while the check runs, as if it was not captured.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Tee {
+    pub stdout: bool,
+    pub stderr: bool,
+    /// Whether each line shown is prefixed with the check ID.
+    pub prefixed: bool,
+}
+
+/// Output of a command, with the order in which its streams printed.
+#[derive(Debug)]
+pub struct Captured {
+    pub output: Output,
+    /// Chunks of stdout and stderr in the order they were read.
+    pub chunks: Vec<Chunk>,
+}
+
+/// A part of the output as read from one stream at once.
+#[derive(Debug, Clone, Copy)]
+pub struct Chunk {
+    /// Milliseconds since the start of the command.
+    pub millis: u64,
+    pub stderr: bool,
+    /// Byte range within the output of the stream.
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Captured {
+    /// Both streams interleaved as they were printed.
+    pub fn interleaved(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(self.output.stdout.len() + self.output.stderr.len());
+        for chunk in self.chunks.iter() {
+            let stream = match chunk.stderr {
+                true => &self.output.stderr,
+                false => &self.output.stdout,
+            };
+            data.extend_from_slice(&stream[chunk.start..chunk.end]);
+        }
+        data
+    }
+}
+
+/// Runs the command like [`Command::output`], telling the user that a long check is still alive.
+///
+/// Prints a line every heartbeat interval of the console and warns if captured output stalls
+/// for the stall interval. Streamed output speaks for itself, so both only apply to captured output.
+/// Captured output is also appended to the transcript of the console as it arrives.
+/// With `input`, the command must have a piped stdin, which is fed the input and closed.
+/// Once `cancellation` is cancelled, the command is killed.
+pub fn output(
+    command: &mut Command,
+    id: &str,
+    console: &Console,
+    cancellation: &Cancellation,
+    tee: Tee,
+    input: Option<Vec<u8>>,
+) -> std::io::Result<Captured> {
+    let start = Instant::now();
+    let mut child = command.spawn()?;
+    if let (Some(mut stdin), Some(input)) = (child.stdin.take(), input) {
+        // In the background, the command may not read all input before printing
+        std::thread::spawn(move || {
+            // A command exiting without reading all input is not an error of customs
+            let _ = stdin.write_all(&input);
+        });
+    }
+    // Milliseconds since the start until the last output
+    let last_output = Arc::new(AtomicU64::new(0));
+    let transcript = console.transcript.clone().map(|e| (e, id.to_string()));
+
+    let stdout = child.stdout.take().map(|e| {
+        let echo = tee
+            .stdout
+            .then(|| echo(std::io::stdout(), id, tee.prefixed));
+        collect(
+            e,
+            start,
+            last_output.clone(),
+            transcript.clone(),
+            echo,
+            None,
+        )
+    });
+    let captured = child.stderr.is_some() && !tee.stderr;
+    // cargo reports waiting for a lock on stderr
+    let waiter = (id.to_string(), child.id());
+    let stderr = child.stderr.take().map(|e| {
+        let echo = tee
+            .stderr
+            .then(|| echo(std::io::stderr(), id, tee.prefixed));
+        collect(
+            e,
+            start,
+            last_output.clone(),
+            transcript.clone(),
+            echo,
+            Some(waiter),
+        )
+    });
+
+    let status = watch(
+        &mut child,
+        id,
+        console,
+        cancellation,
+        captured,
+        start,
+        &last_output,
+    )?;
+
+    let join = |e: Option<JoinHandle<(Vec<u8>, Vec<Chunk>)>>| {
+        e.map(|e| e.join().expect("reader thread must not panic"))
+            .unwrap_or_default()
+    };
+    let (stdout, stdout_chunks) = join(stdout);
+    let (stderr, stderr_chunks) = join(stderr);
+    let mut chunks = [
+        stdout_chunks,
+        stderr_chunks
+            .into_iter()
+            .map(|e| Chunk { stderr: true, ..e })
+            .collect(),
+    ]
+    .concat();
+    // Stable, so chunks of the same stream stay in order
+    chunks.sort_by_key(|e| e.millis);
+
+    Ok(Captured {
+        output: Output {
+            status,
+            stdout,
+            stderr,
+        },
+        chunks,
+    })
+}
+
+/// Where teed output of a stream is shown.
+fn echo(stream: impl Write + Send + 'static, id: &str, prefixed: bool) -> Box<dyn Write + Send> {
+    match prefixed {
+        true => Box::new(Prefixed {
+            prefix: format!("{id}: ").into_bytes(),
+            inner: stream,
+            line: Vec::new(),
+        }),
+        false => Box::new(stream),
+    }
+}
+
+/// Only writes complete lines, each with a prefix, so lines of concurrent checks do not mix.
+struct Prefixed<W: Write> {
+    prefix: Vec<u8>,
+    inner: W,
+    /// The incomplete last line.
+    line: Vec<u8>,
+}
+
+impl<W: Write> Write for Prefixed<W> {
+    fn write(&mut self, buffer: &[u8]) -> std::io::Result<usize> {
+        self.line.extend_from_slice(buffer);
+        while let Some(end) = self.line.iter().position(|e| *e == b'\n') {
+            // At once, so other threads cannot write between prefix and line
+            let line = [self.prefix.as_slice(), &self.line[..=end]].concat();
+            self.inner.write_all(&line)?;
+            self.line.drain(..=end);
+        }
+        Ok(buffer.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for Prefixed<W> {
+    fn drop(&mut self) {
+        if !self.line.is_empty() {
+            let line = [self.prefix.as_slice(), &self.line, b"\n"].concat();
+            let _ = self.inner.write_all(&line).and_then(|_| self.inner.flush());
+        }
+    }
+}
+
+/// Waits for the child, only reporting heartbeats and stalls of `captured` output.
+fn watch(
+    child: &mut Child,
+    id: &str,
+    console: &Console,
+    cancellation: &Cancellation,
+    captured: bool,
+    start: Instant,
+    last_output: &AtomicU64,
+) -> std::io::Result<std::process::ExitStatus> {
+    let mut next_heartbeat = console.heartbeat.filter(|_| captured);
+    let mut stall_reported = false;
+
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        if cancellation.is_cancelled() {
+            // Already exited if killing fails
+            let _ = child.kill();
+            return child.wait();
+        }
+        std::thread::sleep(POLL_INTERVAL);
+
+        let elapsed = start.elapsed();
+        if let Some(heartbeat) = next_heartbeat
+            && elapsed >= heartbeat
+        {
+            eprintln!(
+                "{id}: still running ({} elapsed)",
+                format_duration(elapsed.as_secs_f64())
+            );
+            next_heartbeat = console.heartbeat.map(|e| heartbeat + e);
+        }
+
+        let silent =
+            elapsed.saturating_sub(Duration::from_millis(last_output.load(Ordering::Relaxed)));
+        match console.stall_warning.filter(|_| captured) {
+            Some(stall) if silent >= stall && !stall_reported => {
+                eprintln!(
+                    "{id}: no output for {}, it may hang",
+                    format_duration(silent.as_secs_f64())
+                );
+                stall_reported = true;
+            }
+            // Warn again on the next stall
+            Some(stall) if silent < stall => stall_reported = false,
+            _ => {}
+        }
+    }
+}
+
+/// Reads everything of a pipe in the background, recording when output arrived.
+///
+/// Complete lines go to the transcript right away, so it reflects when they were printed.
+/// With the check ID and process ID of a `waiter`, waiting for a lock of cargo is reported
+/// together with the process holding it.
+fn collect(
+    mut pipe: impl Read + Send + 'static,
+    start: Instant,
+    last_output: Arc<AtomicU64>,
+    transcript: Option<(Transcript, String)>,
+    mut echo: Option<Box<dyn Write + Send>>,
+    waiter: Option<(String, u32)>,
+) -> JoinHandle<(Vec<u8>, Vec<Chunk>)> {
+    std::thread::spawn(move || {
+        let mut data = Vec::new();
+        let mut chunks = Vec::new();
+        let mut buffer = [0; 8192];
+        // Start of the line not yet in the transcript
+        let mut pending = 0;
+        while let Ok(count) = pipe.read(&mut buffer) {
+            if count == 0 {
+                break;
+            }
+            let millis = start.elapsed().as_millis() as u64;
+            chunks.push(Chunk {
+                millis,
+                stderr: false,
+                start: data.len(),
+                end: data.len() + count,
+            });
+            data.extend_from_slice(&buffer[..count]);
+            last_output.store(millis, Ordering::Relaxed);
+
+            if let Some(echo) = echo.as_mut() {
+                let _ = echo.write_all(&buffer[..count]).and_then(|_| echo.flush());
+            }
+            if let Some(end) = data.iter().rposition(|e| *e == b'\n')
+                && end >= pending
+            {
+                let lines = &data[pending..=end];
+                if let Some((id, pid)) = waiter.clone()
+                    && let Some(lock) = lock_wait::detect(String::from_utf8_lossy(lines).as_ref())
+                {
+                    // Finding the holder takes a while, the pipe must be drained meanwhile
+                    let transcript = transcript.clone();
+                    std::thread::spawn(move || {
+                        let message = lock_wait::describe(id.as_str(), lock.as_str(), pid);
+                        eprintln!("{message}");
+                        if let Some((transcript, _)) = transcript.as_ref() {
+                            transcript.line(message.as_str());
+                        }
+                    });
+                }
+                if let Some((transcript, id)) = transcript.as_ref() {
+                    transcript.output(id, lines);
+                }
+                pending = end + 1;
+            }
+        }
+        if let Some((transcript, id)) = transcript.as_ref()
+            && pending < data.len()
+        {
+            transcript.output(id, &data[pending..]);
+        }
+        (data, chunks)
+    })
+}
@@ -0,0 +1,106 @@
+use std::collections::BTreeMap;
+
+use cargo_metadata::camino::{Utf8Path, Utf8PathBuf};
+
+use crate::Result;
+
+pub const FILE_NAME: &str = "durations.json";
+
+/// Wall time of the last successful run of each check, in seconds.
+#[derive(Debug, Default)]
+pub struct Durations {
+    path: Utf8PathBuf,
+    durations: BTreeMap<String, f64>,
+}
+
+impl Durations {
+    pub fn load(output_directory: &Utf8Path) -> Result<Durations> {
+        let path = output_directory.join(FILE_NAME);
+        let durations = match std::fs::read_to_string(path.as_std_path()) {
+            // A corrupt history is only a missing prediction
+            Ok(data) => serde_json::from_str(data.as_str()).unwrap_or_default(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Durations { path, durations })
+    }
+
+    pub fn get(&self, id: &str) -> Option<f64> {
+        self.durations.get(id).copied()
+    }
+
+    pub fn record(&mut self, id: &str, seconds: f64) -> Result<()> {
+        self.durations.insert(id.to_string(), seconds);
+
+        std::fs::create_dir_all(self.path.parent().expect("history is in a directory"))?;
+        let json = serde_json::to_string_pretty(&self.durations).map_err(anyhow::Error::from)?;
+        std::fs::write(self.path.as_std_path(), json)?;
+        Ok(())
+    }
+}
+
+pub const COVERAGE_FILE_NAME: &str = "coverage.json";
+
+/// Highest line coverage in percent of each `coverage` check, the ratchet of `coverage-ratchet`.
+#[derive(Debug, Default)]
+pub struct CoverageHistory {
+    path: Utf8PathBuf,
+    coverage: BTreeMap<String, f64>,
+}
+
+impl CoverageHistory {
+    pub fn load(output_directory: &Utf8Path) -> Result<CoverageHistory> {
+        let path = output_directory.join(COVERAGE_FILE_NAME);
+        let coverage = match std::fs::read_to_string(path.as_std_path()) {
+            Ok(data) => serde_json::from_str(data.as_str()).unwrap_or_default(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(CoverageHistory { path, coverage })
+    }
+
+    pub fn get(&self, id: &str) -> Option<f64> {
+        self.coverage.get(id).copied()
+    }
+
+    pub fn record(&mut self, id: &str, percent: f64) -> Result<()> {
+        self.coverage.insert(id.to_string(), percent);
+
+        std::fs::create_dir_all(self.path.parent().expect("history is in a directory"))?;
+        let json = serde_json::to_string_pretty(&self.coverage).map_err(anyhow::Error::from)?;
+        std::fs::write(self.path.as_std_path(), json)?;
+        Ok(())
+    }
+}
+
+/// Formats seconds like `1h 2m 3s`.
+pub fn format_duration(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    match (seconds / 3600, seconds / 60 % 60, seconds % 60) {
+        (0, 0, s) => format!("{s}s"),
+        (0, m, s) => format!("{m}m {s}s"),
+        (h, m, s) => format!("{h}h {m}m {s}s"),
+    }
+}
+
+/// Parses durations like `90s`, `5m`, `1h` or `7d`, plain numbers are seconds.
+pub fn parse_duration(input: &str) -> std::result::Result<f64, String> {
+    let (number, unit) = match input.char_indices().find(|(_, e)| e.is_alphabetic()) {
+        Some((index, _)) => input.split_at(index),
+        None => (input, "s"),
+    };
+    let number = number
+        .trim()
+        .parse::<f64>()
+        .map_err(|_| format!("invalid duration '{input}'"))?;
+
+    match unit {
+        "s" => Ok(number),
+        "m" => Ok(number * 60.0),
+        "h" => Ok(number * 3600.0),
+        "d" => Ok(number * 86400.0),
+        _ => Err(format!("unknown unit '{unit}', use s, m, h or d")),
+    }
+}
@@ -0,0 +1,143 @@
+use std::path::Path;
+use std::process::Command;
+
+use clap::ValueEnum;
+
+use crate::{Error, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Indent {
+    Spaces,
+    Tabs,
+    /// No policy.
+    Any,
+}
+
+/// Options of the built-in `hygiene` job, given as job arguments.
+#[derive(Debug, Clone, clap::Args)]
+pub struct Options {
+    /// Characters lines may be indented with.
+    #[arg(long, value_enum, default_value_t = Indent::Spaces)]
+    indent: Indent,
+
+    /// Accept Windows line endings.
+    #[arg(long)]
+    allow_crlf: bool,
+}
+
+/// Checks whitespace and line endings of all text files tracked by git in `directory`.
+pub fn check(directory: &Path, options: &Options) -> Result<()> {
+    let output = Command::new("git")
+        .args(["ls-files", "-z"])
+        .current_dir(directory)
+        .output()?;
+    if !output.status.success() {
+        return Err(Error::Git(
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        ));
+    }
+
+    let mut problems = 0;
+    let files = String::from_utf8_lossy(&output.stdout).into_owned();
+    for file in files.split('\0').filter(|e| !e.is_empty()) {
+        let Ok(data) = std::fs::read(directory.join(file)) else {
+            // Deleted, but not yet committed
+            continue;
+        };
+        // Binary files have no lines
+        if data.contains(&0) {
+            continue;
+        }
+
+        let text = String::from_utf8_lossy(&data);
+        for problem in check_text(text.as_ref(), options) {
+            println!("{file}:{problem}");
+            problems += 1;
+        }
+    }
+
+    if problems > 0 {
+        return Err(Error::CheckFailed(format!("hygiene ({problems} problems)")));
+    }
+
+    Ok(())
+}
+
+/// Problems of a file, prefixed with the line they occur in.
+fn check_text(text: &str, options: &Options) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    for (index, line) in text.split_inclusive('\n').enumerate() {
+        let number = index + 1;
+        let content = line.trim_end_matches('\n');
+
+        if content.ends_with('\r') && !options.allow_crlf {
+            problems.push(format!("{number}: CRLF line ending"));
+        }
+
+        let content = content.trim_end_matches('\r');
+        if content.ends_with([' ', '\t']) {
+            problems.push(format!("{number}: trailing whitespace"));
+        }
+
+        let indentation = content
+            .chars()
+            .take_while(|e| *e == ' ' || *e == '\t')
+            .collect::<String>();
+        match options.indent {
+            Indent::Spaces if indentation.contains('\t') => {
+                problems.push(format!("{number}: indented with tabs"));
+            }
+            Indent::Tabs if indentation.contains(' ') => {
+                problems.push(format!("{number}: indented with spaces"));
+            }
+            _ => {}
+        }
+    }
+
+    if !text.is_empty() && !text.ends_with('\n') {
+        problems.push(format!(
+            "{}: missing newline at end of file",
+            text.lines().count()
+        ));
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(indent: Indent, allow_crlf: bool) -> Options {
+        Options { indent, allow_crlf }
+    }
+
+    #[test]
+    fn check_text_reports_each_problem_with_its_line() {
+        let text = "fn main() {\r\n\tlet a = 1; \n    a\n}";
+        assert_eq!(
+            check_text(text, &options(Indent::Spaces, false)),
+            [
+                "1: CRLF line ending",
+                "2: trailing whitespace",
+                "2: indented with tabs",
+                "4: missing newline at end of file",
+            ]
+        );
+        assert_eq!(
+            check_text(text, &options(Indent::Tabs, true)),
+            [
+                "2: trailing whitespace",
+                "3: indented with spaces",
+                "4: missing newline at end of file",
+            ]
+        );
+    }
+
+    #[test]
+    fn check_text_accepts_clean_text() {
+        assert!(check_text("a\n\tb\n", &options(Indent::Any, false)).is_empty());
+        assert!(check_text("", &options(Indent::Spaces, false)).is_empty());
+    }
+}
@@ -0,0 +1,206 @@
+use std::path::Path;
+
+use itertools::Itertools;
+use toml_edit::{Array, InlineTable, Table, value};
+
+use crate::Result;
+
+/// Cargo subcommands that do not check anything.
+const IGNORED_SUBCOMMANDS: &[&str] = &[
+    "customs",
+    "fetch",
+    "generate-lockfile",
+    "install",
+    "login",
+    "metadata",
+    "publish",
+    "update",
+];
+
+/// A cargo invocation found in a CI workflow.
+#[derive(Debug, Default)]
+struct Invocation {
+    toolchain: Option<String>,
+    job: String,
+    platform_target: Option<String>,
+    build_targets: Vec<String>,
+    features: Vec<String>,
+    no_default_features: bool,
+    args: Vec<String>,
+}
+
+/// Regulations equivalent to the cargo invocations in the steps of a CI workflow.
+///
+/// The workflow is only scanned line by line for cargo commands, so expressions like
+/// `${{ matrix.target }}` are not resolved and such invocations are skipped.
+pub fn import(workflow: &Path) -> Result<Vec<Table>> {
+    let data = std::fs::read_to_string(workflow)?;
+
+    let invocations = data
+        .lines()
+        .map(|e| e.trim().trim_start_matches("- ").trim_start_matches("run:"))
+        .flat_map(|e| e.split("&&").flat_map(|e| e.split(';')).collect::<Vec<_>>())
+        .filter_map(|e| {
+            let invocation = parse_invocation(e.trim())?;
+            if e.contains("${{") {
+                log::warn!("Skipping `{}`, expressions are not supported", e.trim());
+                return None;
+            }
+            Some(invocation)
+        })
+        .collect::<Vec<_>>();
+
+    // Invocations only differing in the job become a single regulation.
+    let regulations = invocations
+        .into_iter()
+        .into_group_map_by(|e| {
+            (
+                e.toolchain.clone(),
+                e.platform_target.clone(),
+                e.build_targets.clone(),
+                e.features.clone(),
+                e.no_default_features,
+            )
+        })
+        .into_iter()
+        .sorted_by_key(|(key, _)| key.clone())
+        .map(|(_, invocations)| regulation(invocations))
+        .collect();
+
+    Ok(regulations)
+}
+
+fn parse_invocation(command: &str) -> Option<Invocation> {
+    let mut tokens = command
+        .split_whitespace()
+        .map(|e| e.trim_matches(['"', '\'']).to_string())
+        .peekable();
+
+    if tokens.next()? != "cargo" {
+        return None;
+    }
+
+    let mut invocation = Invocation::default();
+    if let Some(toolchain) = tokens.next_if(|e| e.starts_with('+')) {
+        invocation.toolchain = Some(toolchain[1..].to_string());
+    }
+
+    invocation.job = tokens.next()?;
+    if invocation.job.starts_with('-') || IGNORED_SUBCOMMANDS.contains(&invocation.job.as_str()) {
+        return None;
+    }
+
+    while let Some(token) = tokens.next() {
+        let (flag, inline_value) = match token.split_once('=') {
+            Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+            None => (token, None),
+        };
+        let mut value = || inline_value.clone().or_else(|| tokens.next());
+
+        match flag.as_str() {
+            "--" => invocation.args.extend(tokens.by_ref()),
+            "--target" => invocation.platform_target = value(),
+            "--all-targets" => invocation.build_targets.push("all".into()),
+            "--lib" | "--bins" | "--tests" | "--examples" | "--benches" => {
+                invocation.build_targets.push(flag[2..].to_string())
+            }
+            "--bin" | "--example" | "--test" | "--bench" => {
+                let name = value()?;
+                invocation
+                    .build_targets
+                    .push(format!("{}:{name}", &flag[2..]));
+            }
+            "--features" | "-F" => invocation
+                .features
+                .extend(value()?.split([',', ' ']).map(str::to_string)),
+            "--no-default-features" => invocation.no_default_features = true,
+            // rustfmt takes it after `--` just as well
+            "--check" if invocation.job == "fmt" => invocation.args.push(flag),
+            // Package selection and output options do not change what is checked
+            _ => {}
+        }
+    }
+
+    if invocation.build_targets.is_empty() {
+        // The targets cargo selects by default
+        invocation.build_targets = match invocation.job.as_str() {
+            "fmt" | "test" | "bench" => vec!["all".into()],
+            _ => vec!["lib".into(), "bins".into()],
+        };
+    }
+
+    Some(invocation)
+}
+
+fn regulation(invocations: Vec<Invocation>) -> Table {
+    let first = &invocations[0];
+    let mut table = Table::new();
+
+    if invocations.iter().all(|e| e.args.is_empty()) {
+        let jobs = invocations.iter().map(|e| e.job.as_str()).unique();
+        table.insert("jobs", value(jobs.collect::<Array>()));
+    } else {
+        let mut jobs = Table::new();
+        for invocation in invocations.iter() {
+            let mut parameters = InlineTable::new();
+            if !invocation.args.is_empty() {
+                parameters.insert("args", invocation.args.iter().collect::<Array>().into());
+            }
+            jobs.insert(invocation.job.as_str(), value(parameters));
+        }
+        table.insert("jobs", toml_edit::Item::Table(jobs));
+    }
+
+    let platform_target = first.platform_target.as_deref().unwrap_or("host");
+    table.insert(
+        "platform-targets",
+        value(Array::from_iter([platform_target])),
+    );
+    table.insert(
+        "build-targets",
+        value(first.build_targets.iter().collect::<Array>()),
+    );
+
+    if !first.features.is_empty() {
+        let feature_sets = Array::from_iter([first.features.iter().collect::<Array>()]);
+        table.insert("feature-sets", value(feature_sets));
+    }
+    if first.no_default_features {
+        table.insert("default-features", value(false));
+    }
+    if let Some(toolchain) = first.toolchain.as_ref() {
+        table.insert("toolchain", value(toolchain.as_str()));
+    }
+
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_invocation_of_cargo_commands_only() {
+        let invocation = parse_invocation(
+            "cargo +nightly clippy --all-targets --features a,b -p foo -- -D warnings",
+        )
+        .expect("a cargo invocation");
+        assert_eq!(invocation.toolchain.as_deref(), Some("nightly"));
+        assert_eq!(invocation.job, "clippy");
+        assert_eq!(invocation.build_targets, ["all"]);
+        assert_eq!(invocation.features, ["a", "b"]);
+        assert_eq!(invocation.args, ["-D", "warnings"]);
+
+        let invocation = parse_invocation("cargo build --bin=tool --target thumbv7em-none-eabihf")
+            .expect("a cargo invocation");
+        assert_eq!(invocation.build_targets, ["bin:tool"]);
+        assert_eq!(
+            invocation.platform_target.as_deref(),
+            Some("thumbv7em-none-eabihf")
+        );
+
+        assert!(parse_invocation("cargo fetch --locked").is_none());
+        assert!(parse_invocation("cargo --version").is_none());
+        assert!(parse_invocation("echo cargo test").is_none());
+    }
+}
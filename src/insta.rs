@@ -0,0 +1,67 @@
+use std::process::Command;
+
+use cargo_metadata::camino::{Utf8Path, Utf8PathBuf};
+use itertools::Itertools;
+
+use crate::plan::PlannedCheck;
+use crate::{Error, Result};
+
+/// Package directories with pending insta snapshots of the last run.
+pub const PENDING_FILE_NAME: &str = "insta-pending";
+
+/// Whether the output of a failed check comes from mismatching insta snapshots.
+pub fn detect(output: &str) -> bool {
+    output.contains("Snapshot Summary") || output.contains(".snap.new")
+}
+
+pub fn reset(output_directory: &Utf8Path) -> Result<()> {
+    let path = output_directory.join(PENDING_FILE_NAME);
+    if std::fs::exists(path.as_std_path())? {
+        std::fs::remove_file(path.as_std_path())?;
+    }
+    Ok(())
+}
+
+pub fn record(output_directory: &Utf8Path, check: &PlannedCheck) -> Result<()> {
+    let mut pending = pending(output_directory)?;
+    pending.push(check.command.current_dir.display().to_string().into());
+
+    std::fs::create_dir_all(output_directory.as_std_path())?;
+    std::fs::write(
+        output_directory.join(PENDING_FILE_NAME).as_std_path(),
+        pending.iter().unique().join("\n"),
+    )?;
+    Ok(())
+}
+
+fn pending(output_directory: &Utf8Path) -> Result<Vec<Utf8PathBuf>> {
+    let path = output_directory.join(PENDING_FILE_NAME);
+    if !std::fs::exists(path.as_std_path())? {
+        return Ok(Vec::new());
+    }
+
+    let data = std::fs::read_to_string(path.as_std_path())?;
+    Ok(data.lines().map(Utf8PathBuf::from).collect())
+}
+
+/// Runs `cargo insta review` for each package with pending snapshots.
+pub fn review(output_directory: &Utf8Path) -> Result<()> {
+    let pending = pending(output_directory)?;
+    if pending.is_empty() {
+        log::info!("No pending snapshots from the last run");
+    }
+
+    for directory in pending {
+        let status = Command::new("cargo")
+            .args(["insta", "review"])
+            .current_dir(directory.as_std_path())
+            .status()?;
+        if !status.success() {
+            return Err(Error::Cargo(format!(
+                "insta review in {directory} failed ({status})"
+            )));
+        }
+    }
+
+    reset(output_directory)
+}
@@ -0,0 +1,495 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Job;
+
+/// The hidden subcommand running the canary job.
+pub const CANARY_COMMAND: &str = "canary";
+
+/// The hidden subcommand running the hygiene job.
+pub const HYGIENE_COMMAND: &str = "hygiene";
+
+/// The hidden subcommand running the manifest-policy job.
+pub const MANIFEST_POLICY_COMMAND: &str = "manifest-policy";
+
+/// The hidden subcommand running the reproducible job.
+pub const REPRODUCIBLE_COMMAND: &str = "reproducible";
+
+/// The hidden subcommand running the workspace-deps job.
+pub const WORKSPACE_DEPS_COMMAND: &str = "workspace-deps";
+
+/// Jobs customs knows more about than how to invoke `cargo <job>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobKind {
+    Fmt,
+    /// Builds the documentation, denying broken intra-doc links.
+    Doc,
+    /// Measures the documentation coverage with rustdoc, requires nightly.
+    DocCoverage,
+    /// Builds the documentation like docs.rs, with its `[package.metadata.docs.rs]` settings.
+    DocsRs,
+    /// Compares the public API against a baseline with cargo-semver-checks.
+    SemverChecks,
+    /// Compares the public API against a baseline with cargo-public-api.
+    PublicApi,
+    /// Finds misspellings in source and documentation with the `typos` binary,
+    /// which picks up a `_typos.toml` of the package.
+    Typos,
+    /// Built-in whitespace and line ending checks, run by customs itself.
+    Hygiene,
+    /// Built-in check of the version requirements between workspace members, run by customs itself.
+    WorkspaceDeps,
+    /// Built-in check of the manifest against a policy given as job arguments, run by customs itself.
+    ManifestPolicy,
+    /// Test coverage with cargo-llvm-cov, merged over all test binaries.
+    Coverage,
+    /// Mutation testing with cargo-mutants, of the package in the current directory.
+    Mutants,
+    /// Builds twice from scratch and compares the artifacts, run by customs itself.
+    Reproducible,
+    /// Checks against the upcoming release of a dependency, run by customs itself.
+    Canary,
+    /// Removes the artifacts of the package only, e.g. to verify cold builds in the following jobs.
+    Clean,
+    /// Any other cargo subcommand, taking the usual target selection arguments.
+    Cargo,
+}
+
+impl JobKind {
+    pub fn of(job: &Job) -> JobKind {
+        match job.name.as_str() {
+            "fmt" => JobKind::Fmt,
+            "doc" => JobKind::Doc,
+            "doc-coverage" => JobKind::DocCoverage,
+            "docs-rs" => JobKind::DocsRs,
+            "semver-checks" => JobKind::SemverChecks,
+            "public-api" => JobKind::PublicApi,
+            "typos" => JobKind::Typos,
+            "hygiene" => JobKind::Hygiene,
+            "workspace-deps" => JobKind::WorkspaceDeps,
+            "manifest-policy" => JobKind::ManifestPolicy,
+            "mutants" => JobKind::Mutants,
+            "coverage" => JobKind::Coverage,
+            "clean" => JobKind::Clean,
+            "reproducible" => JobKind::Reproducible,
+            "canary" => JobKind::Canary,
+            _ => JobKind::Cargo,
+        }
+    }
+
+    /// The program to run, all jobs but standalone tools are cargo subcommands.
+    pub fn program(self) -> String {
+        match self {
+            JobKind::Typos => "typos".into(),
+            JobKind::Hygiene
+            | JobKind::WorkspaceDeps
+            | JobKind::ManifestPolicy
+            | JobKind::Reproducible
+            | JobKind::Canary => std::env::current_exe()
+                .map(|e| e.display().to_string())
+                .unwrap_or("cargo-customs".into()),
+            _ => "cargo".into(),
+        }
+    }
+
+    /// The cargo subcommand and its leading arguments.
+    pub fn command_args(self, job: &Job, package_directory: &Path) -> Vec<String> {
+        match self {
+            JobKind::Typos => Vec::new(),
+            JobKind::Hygiene => vec![HYGIENE_COMMAND.into()],
+            JobKind::WorkspaceDeps => vec![WORKSPACE_DEPS_COMMAND.into()],
+            JobKind::ManifestPolicy => vec![MANIFEST_POLICY_COMMAND.into()],
+            // The arguments of the check follow as cargo arguments
+            JobKind::Reproducible => std::iter::once(REPRODUCIBLE_COMMAND.into())
+                .chain(job.normalize.iter().map(|e| format!("--normalize={e}")))
+                .chain(std::iter::once("--".into()))
+                .collect(),
+            JobKind::Canary => {
+                let options = [
+                    ("dependency", &job.dependency),
+                    ("git", &job.git),
+                    ("rev", &job.rev),
+                    ("version", &job.version),
+                    ("command", &job.command),
+                ];
+                std::iter::once(CANARY_COMMAND.into())
+                    .chain(
+                        options.into_iter().filter_map(|(name, value)| {
+                            Some(format!("--{name}={}", value.as_ref()?))
+                        }),
+                    )
+                    .chain(std::iter::once("--".into()))
+                    .collect()
+            }
+            JobKind::SemverChecks => {
+                let mut args = vec![job.name.clone(), "check-release".into()];
+                match job.baseline.as_deref() {
+                    // default of cargo-semver-checks: the latest release on crates.io
+                    None => {}
+                    Some(baseline) if cargo_metadata::semver::Version::parse(baseline).is_ok() => {
+                        args.extend(["--baseline-version".into(), baseline.into()]);
+                    }
+                    Some(baseline) if package_directory.join(baseline).is_dir() => {
+                        args.push("--baseline-root".into());
+                        args.push(package_directory.join(baseline).display().to_string());
+                    }
+                    Some(baseline) => args.extend(["--baseline-rev".into(), baseline.into()]),
+                }
+                args
+            }
+            JobKind::PublicApi => {
+                // Only additions are compatible changes
+                vec![
+                    job.name.clone(),
+                    "diff".into(),
+                    job.baseline.clone().unwrap_or("latest".into()),
+                    "--deny=changed".into(),
+                    "--deny=removed".into(),
+                ]
+            }
+            JobKind::DocCoverage => vec!["rustdoc".into(), "--lib".into()],
+            JobKind::Coverage => vec!["llvm-cov".into(), "--json".into(), "--summary-only".into()],
+            JobKind::DocsRs => vec!["doc".into(), "--lib".into(), "--no-deps".into()],
+            JobKind::Mutants => {
+                let mut args = vec![job.name.clone()];
+                if let Some(timeout) = job.timeout {
+                    args.push(format!("--timeout={timeout}"));
+                }
+                args
+            }
+            // Artifacts are only reported in the JSON messages, diagnostics are still rendered.
+            JobKind::Cargo if job.max_artifact_size.is_some() => vec![
+                job.name.clone(),
+                "--message-format=json-render-diagnostics".into(),
+            ],
+            JobKind::Cargo if job.name == "nextest" => {
+                let mut args = vec![job.name.clone(), "run".into()];
+                if let Some(retries) = job.retry_failed_tests {
+                    args.push(format!("--retries={retries}"));
+                }
+                if let Some(threads) = job.test_threads {
+                    args.push(format!("--test-threads={threads}"));
+                }
+                args
+            }
+            JobKind::Fmt | JobKind::Doc | JobKind::Clean | JobKind::Cargo => vec![job.name.clone()],
+        }
+    }
+
+    /// Arguments for the underlying tool, i.e. after `--`.
+    pub fn tool_args(self, job: &Job) -> Vec<String> {
+        match self {
+            // libtest takes the number of threads, nextest does as a cargo subcommand
+            JobKind::Cargo if job.name == "test" => job
+                .test_threads
+                .map(|e| format!("--test-threads={e}"))
+                .into_iter()
+                .collect(),
+            JobKind::DocCoverage => [
+                "-Zunstable-options",
+                "--show-coverage",
+                "--output-format",
+                "json",
+            ]
+            .map(String::from)
+            .to_vec(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Flags passed to rustdoc via `RUSTDOCFLAGS`.
+    pub fn rustdoc_flags(self) -> Vec<String> {
+        match self {
+            JobKind::Doc => vec!["-D".into(), "rustdoc::broken_intra_doc_links".into()],
+            JobKind::DocsRs => vec!["--cfg".into(), "docsrs".into()],
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn evaluation(self, job: &Job) -> Option<Evaluation> {
+        match self {
+            JobKind::DocCoverage => Some(Evaluation::DocCoverage {
+                min_percent: job.min_doc_coverage.unwrap_or(0),
+            }),
+            JobKind::Coverage => Some(Evaluation::Coverage {
+                min_percent: job.min_coverage.unwrap_or(0),
+                ratchet: job.coverage_ratchet.unwrap_or(false),
+            }),
+            JobKind::Mutants => Some(Evaluation::Mutants),
+            JobKind::Cargo => job
+                .max_artifact_size
+                .map(|max_bytes| Evaluation::ArtifactSize { max_bytes }),
+            _ => None,
+        }
+    }
+
+    /// Whether cargo builds into the target directory, locking it meanwhile.
+    pub fn uses_target_directory(self) -> bool {
+        !matches!(
+            self,
+            JobKind::Fmt
+                | JobKind::Typos
+                | JobKind::Hygiene
+                | JobKind::WorkspaceDeps
+                | JobKind::ManifestPolicy
+        )
+    }
+
+    pub fn takes_build_target(self) -> bool {
+        matches!(
+            self,
+            JobKind::Cargo
+                | JobKind::Doc
+                | JobKind::Coverage
+                | JobKind::Reproducible
+                | JobKind::Canary
+        )
+    }
+
+    pub fn takes_platform_target(self) -> bool {
+        matches!(
+            self,
+            JobKind::Cargo
+                | JobKind::Doc
+                | JobKind::DocCoverage
+                | JobKind::DocsRs
+                | JobKind::PublicApi
+                | JobKind::Coverage
+                | JobKind::Clean
+                | JobKind::Reproducible
+                | JobKind::Canary
+        )
+    }
+
+    /// Whether `--locked`, `--offline` and `--ignore-rust-version` are understood.
+    pub fn takes_lock_flags(self) -> bool {
+        matches!(
+            self,
+            JobKind::Cargo
+                | JobKind::Doc
+                | JobKind::DocCoverage
+                | JobKind::DocsRs
+                | JobKind::Coverage
+                | JobKind::Reproducible
+        )
+    }
+
+    pub fn takes_features(self) -> bool {
+        !matches!(
+            self,
+            JobKind::Fmt
+                | JobKind::Typos
+                | JobKind::Hygiene
+                | JobKind::WorkspaceDeps
+                | JobKind::ManifestPolicy
+                | JobKind::Clean
+        )
+    }
+}
+
+/// Judges the captured output of a check, beyond its exit status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Evaluation {
+    DocCoverage {
+        min_percent: u8,
+    },
+    /// Size limit of cdylib and staticlib outputs, e.g. for firmware blobs or plugins.
+    ArtifactSize {
+        max_bytes: u64,
+    },
+    /// Line coverage of `cargo llvm-cov`, with `ratchet` it must not drop below the last run either.
+    Coverage {
+        min_percent: u8,
+        ratchet: bool,
+    },
+    /// Reports the summary of cargo-mutants, also when surviving mutants fail the check.
+    Mutants,
+}
+
+/// A `compiler-artifact` message of `cargo build --message-format=json`.
+#[derive(Debug, Deserialize)]
+struct Artifact {
+    reason: String,
+    #[serde(default)]
+    target: Option<ArtifactTarget>,
+    #[serde(default)]
+    filenames: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ArtifactTarget {
+    crate_types: Vec<String>,
+}
+
+/// Coverage of a single file as reported by `rustdoc --show-coverage --output-format json`.
+#[derive(Debug, Deserialize)]
+struct FileCoverage {
+    total: u64,
+    with_docs: u64,
+}
+
+/// The summary of `cargo llvm-cov --json`, an LLVM coverage export.
+#[derive(Debug, Deserialize)]
+struct CoverageExport {
+    data: Vec<CoverageData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CoverageData {
+    totals: CoverageTotals,
+}
+
+#[derive(Debug, Deserialize)]
+struct CoverageTotals {
+    lines: CoverageCount,
+}
+
+#[derive(Debug, Deserialize)]
+struct CoverageCount {
+    count: u64,
+    covered: u64,
+}
+
+/// Percentage of covered lines reported by `cargo llvm-cov --json`.
+pub fn line_coverage(stdout: &str) -> std::result::Result<f64, String> {
+    let export: CoverageExport = stdout
+        .lines()
+        .rev()
+        .find_map(|e| serde_json::from_str(e).ok())
+        .ok_or("no coverage report in the cargo-llvm-cov output")?;
+
+    let count = export
+        .data
+        .iter()
+        .map(|e| e.totals.lines.count)
+        .sum::<u64>();
+    let covered = export
+        .data
+        .iter()
+        .map(|e| e.totals.lines.covered)
+        .sum::<u64>();
+    Ok(match count {
+        0 => 100.0,
+        _ => covered as f64 * 100.0 / count as f64,
+    })
+}
+
+impl Evaluation {
+    /// Returns a summary of the result, or why the check failed.
+    pub fn evaluate(self, stdout: &str) -> std::result::Result<String, String> {
+        match self {
+            Evaluation::DocCoverage { min_percent } => {
+                let files: BTreeMap<String, FileCoverage> = stdout
+                    .lines()
+                    .rev()
+                    .find_map(|e| serde_json::from_str(e).ok())
+                    .ok_or("no coverage report in the rustdoc output")?;
+
+                let total = files.values().map(|e| e.total).sum::<u64>();
+                let with_docs = files.values().map(|e| e.with_docs).sum::<u64>();
+                let percent = match total {
+                    0 => 100.0,
+                    _ => with_docs as f64 * 100.0 / total as f64,
+                };
+
+                let summary = format!("documentation coverage {percent:.1}% ({with_docs}/{total})");
+                if percent < f64::from(min_percent) {
+                    return Err(format!("{summary} is below {min_percent}%"));
+                }
+                Ok(summary)
+            }
+            Evaluation::Coverage { min_percent, .. } => {
+                let percent = line_coverage(stdout)?;
+                let summary = format!("line coverage {percent:.1}%");
+                if percent < f64::from(min_percent) {
+                    return Err(format!("{summary} is below {min_percent}%"));
+                }
+                Ok(summary)
+            }
+            Evaluation::Mutants => Ok(stdout
+                .lines()
+                .rev()
+                .find(|e| e.contains("mutants tested"))
+                .unwrap_or("no mutants generated")
+                .trim()
+                .to_string()),
+            Evaluation::ArtifactSize { max_bytes } => {
+                let native = ["cdylib", "staticlib"];
+                let files = stdout
+                    .lines()
+                    .filter_map(|e| serde_json::from_str::<Artifact>(e).ok())
+                    .filter(|e| e.reason == "compiler-artifact")
+                    .filter(|e| {
+                        e.target.as_ref().is_some_and(|e| {
+                            e.crate_types.iter().any(|e| native.contains(&e.as_str()))
+                        })
+                    })
+                    .flat_map(|e| e.filenames)
+                    // Import libraries and debug info next to the library are not the artifact
+                    .filter(|e| {
+                        !e.ends_with(".pdb") && !e.ends_with(".dll.lib") && !e.ends_with(".dll.a")
+                    })
+                    .collect::<Vec<_>>();
+
+                let mut sizes = Vec::new();
+                for file in files {
+                    let size = std::fs::metadata(file.as_str())
+                        .map_err(|e| format!("cannot read size of {file}: {e}"))?
+                        .len();
+                    sizes.push((file, size));
+                }
+
+                let Some((file, size)) = sizes.into_iter().max_by_key(|(_, size)| *size) else {
+                    return Ok("no cdylib or staticlib artifacts".into());
+                };
+
+                let name = Path::new(file.as_str())
+                    .file_name()
+                    .map(|e| e.to_string_lossy().into_owned())
+                    .unwrap_or(file);
+                let summary = format!("largest artifact {name} has {size} bytes");
+                if size > max_bytes {
+                    return Err(format!("{summary}, more than {max_bytes}"));
+                }
+                Ok(summary)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn doc_coverage_fails_below_minimum() {
+        let stdout = "Documenting foo\n\
+            {\"src/lib.rs\":{\"total\":4,\"with_docs\":3,\"total_examples\":4,\"with_examples\":0},\
+            \"src/a.rs\":{\"total\":4,\"with_docs\":1,\"total_examples\":4,\"with_examples\":0}}\n";
+
+        assert_eq!(
+            Evaluation::DocCoverage { min_percent: 50 }.evaluate(stdout),
+            Ok("documentation coverage 50.0% (4/8)".into())
+        );
+        assert_eq!(
+            Evaluation::DocCoverage { min_percent: 80 }.evaluate(stdout),
+            Err("documentation coverage 50.0% (4/8) is below 80%".into())
+        );
+        assert!(
+            Evaluation::DocCoverage { min_percent: 0 }
+                .evaluate("error")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn doc_denies_broken_intra_doc_links() {
+        assert_eq!(
+            JobKind::Doc.rustdoc_flags(),
+            ["-D", "rustdoc::broken_intra_doc_links"]
+        );
+    }
+}
@@ -0,0 +1,936 @@
+//! The customs binary is the main product of this package. The library holds its implementation,
+//! exposes [`Options`] to plan and run checks from other programs with a [`Cancellation`],
+//! the [`Executor`] running single checks, e.g. to run them elsewhere or replace them in tests,
+//! and support for workspaces testing their own customs files, behind the `test-support` feature.
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+use serde::Serialize;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use cargo_metadata::Metadata;
+use clap::{ArgAction, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+
+mod add;
+mod background;
+mod badge;
+mod boards;
+mod budget;
+mod canary;
+mod cancellation;
+mod ci;
+mod compare;
+mod config;
+mod console;
+mod discovery;
+mod docs_rs;
+mod embed;
+mod exec;
+mod executor;
+mod fmt_config;
+mod git;
+mod heartbeat;
+mod history;
+mod hygiene;
+mod import;
+mod insta;
+mod job;
+mod lint;
+mod lock_wait;
+mod manifest_policy;
+mod metadata_cache;
+mod metrics;
+mod network;
+mod otlp;
+mod overrides;
+mod plan;
+mod plan_diff;
+mod platform;
+mod preset;
+mod problem_matcher;
+mod replay;
+mod reproducible;
+mod result_cache;
+mod retention;
+mod run;
+mod shard;
+mod targets;
+mod telemetry;
+mod test_results;
+mod toolchain;
+mod tools;
+mod transcript;
+mod wizard;
+mod workspace_deps;
+
+use ci::Ci;
+use config::Job;
+use console::{ColorChoice, Verbosity};
+use job::JobKind;
+use plan::{Invocation, Schedule, SkipReason};
+use preset::Preset;
+use result_cache::ResultCache;
+use transcript::Transcript;
+
+pub use cancellation::Cancellation;
+pub use console::Console;
+pub use embed::{Options, RunOutcome};
+pub use executor::{CheckOutcome, Executor, Subprocess};
+pub use plan::{Plan, PlannedCheck};
+pub use run::Outcome;
+pub use test_results::TestOutcome;
+
+#[cfg(feature = "test-support")]
+pub mod test_support;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("No 'Customs.toml' found.")]
+    CustomsMissing,
+
+    #[error("Cannot add regulations to '{0}', only a Customs.toml is edited.")]
+    NotAppendable(String),
+
+    #[error("No regulations defined in '{0}'.")]
+    NoRegulations(String),
+
+    #[error("Invalid Customs file '{path}': {source}{}", .hint.map(|e| format!("hint: {e}")).unwrap_or_default())]
+    InvalidToml {
+        path: String,
+        source: Box<toml::de::Error>,
+        hint: Option<&'static str>,
+    },
+
+    #[cfg(feature = "json")]
+    #[error("Invalid Customs file '{0}': {1}")]
+    InvalidJson(String, serde_json::Error),
+
+    #[cfg(feature = "yaml")]
+    #[error("Invalid Customs file '{0}': {1}")]
+    InvalidYaml(String, serde_yaml::Error),
+
+    #[error("Invalid run report '{0}': {1}")]
+    InvalidRunReport(String, serde_json::Error),
+
+    #[error("Generator of '{0}' failed: {1}")]
+    GeneratorFailed(String, String),
+
+    #[error("Unknown board '{board}', {}", .file.as_ref().map(|e| format!("not listed in {e}")).unwrap_or("no boards.toml found".into()))]
+    UnknownBoard { board: String, file: Option<String> },
+
+    #[error("Invalid [package.metadata.docs.rs] in '{0}': {1}")]
+    InvalidDocsRsMetadata(String, serde_json::Error),
+
+    #[error("Invalid parallelism '{0}', expected a number, `num-cpus` or `num-cpus/<divisor>`")]
+    InvalidParallelism(String),
+
+    #[error("Invalid max-log-age: {0}")]
+    InvalidRetention(String),
+
+    #[error("No suite '{0}' in the customs file of the workspace root, defined are: {1}")]
+    UnknownSuite(String, String),
+
+    #[error("No regulation named '{0}' in the selected packages.")]
+    UnknownRegulation(String),
+
+    #[error("Invalid override of '{0}': {1}")]
+    InvalidOverride(String, String),
+
+    #[error("Invalid toolchain file '{0}': {1}")]
+    InvalidToolchainFile(String, toml::de::Error),
+
+    #[error("Error from cargo: {0}")]
+    Cargo(String),
+
+    #[error("Error from rustup: {0}")]
+    Rustup(String),
+
+    #[error("Error from git: {0}")]
+    Git(String),
+
+    #[error("The run was cancelled.")]
+    Cancelled,
+
+    #[error("Check '{0}' failed.")]
+    CheckFailed(String),
+
+    #[error("{0} checks failed.")]
+    ChecksFailed(usize),
+
+    #[error("{0} customs files are not formatted, run `cargo customs fmt-config`.")]
+    UnformattedCustoms(usize),
+
+    #[error("{0} checks were skipped, but skipping is forbidden.")]
+    ForbiddenSkips(usize),
+
+    #[error("Tool '{0}' not found in PATH.")]
+    ToolNotFound(String),
+
+    #[error("Exporting the trace to '{0}' failed ({1}).")]
+    TraceExport(String, std::process::ExitStatus),
+
+    #[error("Refusing to look up '{0}' in PATH in hermetic mode.")]
+    UnresolvedTool(String),
+
+    #[error(
+        "Cannot pass --cargo-config to check '{0}', its subcommand is run without cargo in hermetic mode."
+    )]
+    CargoConfigUnforwardable(String),
+
+    #[error("Invalid arguments: {0}")]
+    InvalidArguments(clap::Error),
+
+    #[error("Unexpected I/O Error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Unexpected error: {0}")]
+    Unexpected(#[from] anyhow::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Parser)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[clap(flatten)]
+    manifest: clap_cargo::Manifest,
+    #[clap(flatten)]
+    workspace: clap_cargo::Workspace,
+    #[clap(flatten)]
+    features: clap_cargo::Features,
+    #[clap(flatten)]
+    overrides: overrides::Overrides,
+
+    /// Resolve all tools to absolute paths when planning and never look them up in PATH afterwards,
+    /// e.g. for reproducible runs in Nix.
+    #[arg(long)]
+    hermetic: bool,
+
+    /// Fail if any check was skipped.
+    #[arg(long)]
+    forbid_skips: bool,
+
+    /// Fail if any check was skipped for the given reason.
+    #[arg(long, value_enum, value_name = "REASON")]
+    forbid_skip_reason: Vec<SkipReason>,
+
+    /// Skip checks that already succeeded for the current commit and record new successes,
+    /// in a directory or an S3-compatible bucket (`s3://bucket/prefix`).
+    #[arg(long, value_name = "LOCATION")]
+    result_cache: Option<String>,
+
+    /// Behave as in CI, even if no CI was detected.
+    #[arg(long, conflicts_with = "no_ci")]
+    ci: bool,
+
+    /// Behave as locally, even if a CI was detected.
+    #[arg(long)]
+    no_ci: bool,
+
+    /// Require an up-to-date lock file for all checks, in addition to regulations setting `locked`.
+    #[arg(long)]
+    locked: bool,
+
+    /// Run all checks without network access, in addition to regulations setting `offline`.
+    #[arg(long)]
+    offline: bool,
+
+    /// Ignore the `rust-version` of packages for all checks,
+    /// in addition to regulations setting `ignore-rust-version`.
+    #[arg(long)]
+    ignore_rust_version: bool,
+
+    /// Run all checks, even those a regulation only runs in CI.
+    #[arg(long)]
+    full: bool,
+
+    /// Only run the checks of regulations with this `name`, may be given multiple times.
+    #[arg(long, value_name = "NAME")]
+    regulation: Vec<String>,
+
+    /// Pass `--config KEY=VALUE` to every cargo invocation of the checks, e.g. `profile.dev.debug=0`,
+    /// may be given multiple times.
+    #[arg(long, value_name = "KEY=VALUE")]
+    cargo_config: Vec<String>,
+
+    /// Print the command line of each check that would run instead of running it.
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Check packages without a customs file with implicit regulations:
+    /// check, test and clippy on the host for all build targets.
+    #[arg(long)]
+    implicit: bool,
+
+    /// Only run the checks fitting into a time budget like `5m`, based on their recorded durations.
+    /// Checks that failed last time and checks of changed packages are preferred.
+    #[arg(long, value_name = "DURATION", value_parser = history::parse_duration)]
+    budget: Option<f64>,
+
+    /// Only run the part of the checks of one of several CI runners, e.g. `2/4`.
+    /// The tests of `test` and `nextest` jobs are split across all shards.
+    #[arg(long, value_name = "INDEX/COUNT", value_parser = shard::parse)]
+    shard: Option<shard::Shard>,
+
+    /// Overwrite the expected output of UI tests (`ui-test:` build targets) with the actual output.
+    #[arg(long)]
+    bless: bool,
+
+    /// Run the remaining checks after a check failed and print a summary of all checks at the end,
+    /// always the case in CI.
+    #[arg(long)]
+    keep_going: bool,
+
+    /// Run up to this many checks at the same time.
+    /// Checks building in the same target directory still run one after another.
+    #[arg(short, long, value_name = "N", default_value = "1")]
+    jobs: NonZeroUsize,
+
+    /// With `--jobs`, also run checks of the same package at the same time,
+    /// even if they may skew each other's durations.
+    #[arg(long)]
+    no_package_fence: bool,
+
+    /// Show the output of all checks, not only of failed ones.
+    /// Given twice, also print the command line and environment of each check.
+    #[arg(short, long, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Print a line at this interval while a check with captured output runs, `0` to disable.
+    #[arg(long, value_name = "DURATION", value_parser = history::parse_duration, default_value = "5m")]
+    heartbeat: f64,
+
+    /// Warn when a check with captured output printed nothing for this long, e.g. `10m`.
+    #[arg(long, value_name = "DURATION", value_parser = history::parse_duration)]
+    stall_warning: Option<f64>,
+
+    /// Sample CPU, memory and disk usage of the host while each check runs, recorded in `run.json`.
+    ///
+    /// The whole host is sampled, so with `--jobs` each check is attributed the usage of all checks running alongside it.
+    #[arg(long)]
+    telemetry: bool,
+
+    /// Run the checks at reduced CPU and I/O priority, keeping the machine usable meanwhile.
+    #[arg(long)]
+    background: bool,
+
+    /// Write a timestamped transcript of the whole run to this file,
+    /// with the output of all checks in the order they ran.
+    #[arg(long, value_name = "PATH")]
+    log_file: Option<PathBuf>,
+
+    /// Write the metrics of the run in the Prometheus text format to this file
+    /// instead of `target/customs/metrics.prom`.
+    #[arg(long, value_name = "PATH")]
+    metrics_file: Option<PathBuf>,
+
+    /// Write the status of the run as a shields.io endpoint badge to this file, e.g. to publish from CI.
+    #[arg(long, value_name = "PATH")]
+    badge_file: Option<PathBuf>,
+
+    /// Export the run as an OpenTelemetry trace with one span per check to this OTLP/HTTP endpoint,
+    /// e.g. `http://localhost:4318`.
+    #[arg(long, value_name = "URL")]
+    otlp_endpoint: Option<String>,
+
+    /// Coloring of the output, also applies to the checks.
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t)]
+    color: ColorChoice,
+}
+
+impl Cli {
+    /// Parses the arguments of `cargo-customs`, the first being the executable.
+    fn try_parse_args<I, T>(args: I) -> std::result::Result<Cli, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        // Package selection applies to all subcommands,
+        // so it may be given before or after the subcommand.
+        let matches = Cli::command()
+            .mut_args(|arg| arg.global(true))
+            .try_get_matches_from(args)?;
+        Cli::from_arg_matches(&matches)
+    }
+
+    /// Where the checks of a run print to.
+    fn console(&self) -> Result<Console> {
+        let mut console = Console::new(Verbosity::from_count(self.verbose), self.color, self.ci());
+        console.heartbeat = (self.heartbeat > 0.0).then(|| Duration::from_secs_f64(self.heartbeat));
+        console.stall_warning = self.stall_warning.map(Duration::from_secs_f64);
+        console.telemetry = self.telemetry;
+        console.concurrent = self.jobs.get() > 1;
+        if let Some(path) = self.log_file.as_ref() {
+            console.transcript = Some(Transcript::create(path)?);
+        }
+        Ok(console)
+    }
+
+    fn schedule(&self) -> Schedule {
+        Schedule {
+            // In CI, all failures are reported at once instead of one per pipeline run.
+            keep_going: self.keep_going || self.ci().is_some(),
+            jobs: self.jobs.get(),
+            package_fence: !self.no_package_fence,
+        }
+    }
+
+    fn ci(&self) -> Option<Ci> {
+        let force = match (self.ci, self.no_ci) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        };
+        Ci::detect(force)
+    }
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Print the expanded checks without executing them.
+    Plan {
+        #[arg(long, value_enum, default_value_t = PlanFormat::Json)]
+        format: PlanFormat,
+        /// Print each check with the environment variables customs sets, its working directory and
+        /// its command line instead.
+        #[arg(long)]
+        show_env: bool,
+    },
+    /// Run the checks of a suite of the customs file of the workspace root, e.g. `nightly`.
+    Suite { name: String },
+    /// Print the checks added (+), removed (-) or changed (~) compared to another git revision.
+    PlanDiff {
+        /// The revision to compare against, e.g. `main`.
+        revision: String,
+    },
+    /// Run a command once per expanded check instead of the check itself.
+    ///
+    /// The placeholders `{package}`, `{target}`, `{build-target}`, `{job}`, `{features}` and `{dir}`
+    /// are replaced with the values of the check. Each distinct command runs only once.
+    Exec {
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+    /// Print the checks that failed, got fixed, or changed their duration between two runs.
+    ///
+    /// Runs are compared by their `target/customs/run.json`, checks only run in one of them
+    /// are listed as added (+) or removed (-).
+    Compare { a: PathBuf, b: PathBuf },
+    /// Append a regulation to the nearest customs file.
+    Add {
+        #[command(flatten)]
+        regulation: add::NewRegulation,
+    },
+    /// Append regulations equivalent to the cargo commands of a CI workflow to the nearest customs file.
+    Import {
+        /// The workflow file, e.g. `.github/workflows/ci.yml`.
+        #[arg(long, value_name = "PATH")]
+        from: PathBuf,
+    },
+    /// Format all customs files of the workspace canonically.
+    FmtConfig {
+        /// Only report unformatted files and fail if there are any.
+        #[arg(long)]
+        check: bool,
+    },
+    /// Remove logs, reports, caches and history in `target/customs` beyond `max-log-age` and
+    /// `max-artifacts-size` of the customs file of the workspace root.
+    CleanState {
+        /// Remove all of them instead.
+        #[arg(long)]
+        all: bool,
+    },
+    /// Run the built-in hygiene job in the current directory.
+    #[command(name = job::HYGIENE_COMMAND, hide = true)]
+    Hygiene(hygiene::Options),
+    /// Run the built-in workspace-deps job for the package in the current directory.
+    #[command(name = job::WORKSPACE_DEPS_COMMAND, hide = true)]
+    WorkspaceDeps,
+    /// Run the built-in manifest-policy job for the package in the current directory.
+    #[command(name = job::MANIFEST_POLICY_COMMAND, hide = true)]
+    ManifestPolicy(manifest_policy::Options),
+    /// Run the built-in canary job for the package in the current directory.
+    #[command(name = job::CANARY_COMMAND, hide = true)]
+    Canary(canary::Options),
+    /// Run the built-in reproducible job for the package in the current directory.
+    #[command(name = job::REPRODUCIBLE_COMMAND, hide = true)]
+    Reproducible(reproducible::Options),
+    /// Review the insta snapshots of the checks that failed in the last run.
+    Review,
+    /// Print the GitHub Actions problem matcher for customs failures.
+    ProblemMatcher {
+        /// Write the problem matcher to this path and register it with GitHub Actions instead.
+        #[arg(long, value_name = "PATH")]
+        install: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum PlanFormat {
+    Json,
+}
+
+fn parse_cli() -> Cli {
+    const CARGO_COMMAND_NAME: &str = "customs";
+
+    let mut args = std::env::args().peekable();
+    let executable = args
+        .next()
+        .expect("exec must be invoked with at least the executable");
+    if let Some(first_arg) = args.peek()
+        && first_arg == CARGO_COMMAND_NAME
+    {
+        // cargo may invoke with the subcommand in the first place,
+        // in this case it is simply discarded.
+        let _customs = args.next();
+    }
+
+    let args = std::iter::once(executable).chain(args);
+    Cli::try_parse_args(args).unwrap_or_else(|e| e.exit())
+}
+
+/// Runs customs with the arguments of the process, as the `cargo-customs` binary does.
+pub fn main() -> ExitCode {
+    env_logger::init();
+    let result = run();
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            log::error!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<()> {
+    let args = parse_cli();
+
+    match args.command.as_ref() {
+        Some(Command::ProblemMatcher { install }) => {
+            match install {
+                Some(path) => problem_matcher::install(path)?,
+                None => print!("{}", problem_matcher::PROBLEM_MATCHER),
+            }
+            Ok(())
+        }
+        Some(Command::Hygiene(options)) => {
+            hygiene::check(std::env::current_dir()?.as_path(), options)
+        }
+        Some(Command::Add { regulation }) => {
+            let path = add::add(std::env::current_dir()?.as_path(), regulation)?;
+            eprintln!("Added regulation to {}", path.display());
+            Ok(())
+        }
+        Some(Command::Compare { a, b }) => compare::compare(a, b),
+        Some(Command::Import { from }) => {
+            let regulations = import::import(from)?;
+            let count = regulations.len();
+            let path = add::append(std::env::current_dir()?.as_path(), regulations)?;
+            eprintln!("Added {count} regulations to {}", path.display());
+            Ok(())
+        }
+        Some(Command::Review) => {
+            let metadata = metadata_cache::metadata()?;
+            insta::review(&metadata.target_directory.join("customs"))
+        }
+        Some(Command::WorkspaceDeps) => workspace_deps::check(
+            &metadata_cache::metadata()?,
+            std::env::current_dir()?.as_path(),
+        ),
+        Some(Command::ManifestPolicy(options)) => manifest_policy::check(
+            &metadata_cache::metadata()?,
+            std::env::current_dir()?.as_path(),
+            options,
+        ),
+        Some(Command::Reproducible(options)) => {
+            reproducible::check(&metadata_cache::metadata()?, options, &args.cargo_config)
+        }
+        Some(Command::Canary(options)) => {
+            canary::check(&metadata_cache::metadata()?, options, &args.cargo_config)
+        }
+        Some(Command::FmtConfig { check }) => {
+            fmt_config::fmt_config(&metadata_cache::metadata()?, *check)
+        }
+        Some(Command::CleanState { all }) => {
+            retention::clean_state(&metadata_cache::metadata()?, *all)
+        }
+        Some(Command::Plan { format, show_env }) => run_plan(
+            &args,
+            Planned::Show {
+                format: *format,
+                show_env: *show_env,
+            },
+        ),
+        Some(Command::PlanDiff { revision }) => run_plan(&args, Planned::Diff(revision)),
+        Some(Command::Exec { command }) => run_plan(&args, Planned::Exec(command)),
+        Some(Command::Suite { .. }) | None => run_plan(&args, Planned::Run),
+    }
+}
+
+/// What is done with the checks once planned.
+enum Planned<'a> {
+    /// Printed by `cargo customs plan`.
+    Show { format: PlanFormat, show_env: bool },
+    /// Compared to the plan at a git revision.
+    Diff(&'a str),
+    /// A command run once per distinct rendering.
+    Exec(&'a [String]),
+    /// Executed, or only printed with `--dry-run`.
+    Run,
+}
+
+/// Opens the result cache given on the command line and skips the checks it holds a result for.
+fn apply_result_cache(
+    args: &Cli,
+    metadata: &Metadata,
+    plan: &mut Plan,
+) -> Result<Option<ResultCache>> {
+    let result_cache = match args.result_cache.as_ref() {
+        Some(location) => ResultCache::open(location, &metadata.workspace_root)?,
+        None => None,
+    };
+    if let Some(result_cache) = result_cache.as_ref() {
+        result_cache.apply(plan)?;
+    }
+    Ok(result_cache)
+}
+
+fn run_plan(args: &Cli, planned: Planned) -> Result<()> {
+    let metadata = metadata_cache::metadata()?;
+
+    // Offer to set up customs on the first run, the run continues with the new regulations
+    if args.command.is_none()
+        && args.ci().is_none()
+        && !args.implicit
+        && !args.dry_run
+        && wizard::is_interactive()
+        && !wizard::is_declined(&metadata)
+        && !config::workspace_has_customs(&metadata)?
+    {
+        wizard::setup(&metadata)?;
+    }
+
+    let customs_files = config::CustomsFiles::default();
+    let mut plan = plan::build_plan(args, &metadata, &customs_files)?;
+
+    let ci = args.ci();
+    match planned {
+        Planned::Show { show_env: true, .. } => {
+            for check in plan.checks.iter() {
+                match check.skipped {
+                    Some(reason) => println!("{} (skipped: {reason})", check.id),
+                    None => println!("{}", check.id),
+                }
+                print!("{}", check.command.describe());
+            }
+        }
+        Planned::Show {
+            format: PlanFormat::Json,
+            ..
+        } => {
+            let json = serde_json::to_string_pretty(&plan).map_err(anyhow::Error::from)?;
+            println!("{json}");
+            if let Some(prediction) = plan.describe_prediction() {
+                eprintln!("Estimated duration: {prediction}");
+            }
+        }
+        Planned::Diff(revision) => {
+            plan_diff::plan_diff(args, &plan, &metadata.workspace_root, revision)?
+        }
+        Planned::Exec(command) => {
+            // The output of the command is what the user is after
+            let verbosity = Verbosity::from_count(args.verbose).max(Verbosity::Verbose);
+            let console = Console::new(verbosity, args.color, ci);
+            exec::exec_plan(plan, command).execute(
+                &Subprocess,
+                &Cancellation::default(),
+                None,
+                &console,
+                Schedule::default(),
+                &mut BTreeMap::new(),
+            )?
+        }
+        // Exactly what a run would execute, results in the cache are skipped as well
+        Planned::Run if args.dry_run => {
+            apply_result_cache(args, &metadata, &mut plan)?;
+            for check in plan.checks.iter() {
+                match check.skipped {
+                    Some(reason) => println!("{} (skipped: {reason})", check.id),
+                    None => println!("{}: {}", check.id, check.command.env_command_line()),
+                }
+            }
+        }
+        Planned::Run => {
+            let result_cache = apply_result_cache(args, &metadata, &mut plan)?;
+
+            if args.background {
+                background::lower_priority();
+            }
+
+            let console = args.console()?;
+            let mut run_info = run::RunInfo::collect(&plan)?;
+            console.transcript(
+                format!(
+                    "customs {} started with arguments: {}",
+                    run_info.customs_version,
+                    run_info.args.join(" ")
+                )
+                .as_str(),
+            );
+            run_info.print_header(&console);
+            run_info.write()?;
+
+            let schedule = args.schedule();
+            let result = plan.execute(
+                &Subprocess,
+                &Cancellation::default(),
+                result_cache.as_ref(),
+                &console,
+                schedule,
+                &mut run_info.outcomes,
+            );
+            run_info.summarize_groups();
+            run_info.print_groups(&console);
+            if schedule.keep_going {
+                run_info.print_summary(&console);
+            }
+            run_info.write()?;
+            let finished = run::unix_seconds();
+            let metrics_file = match args.metrics_file.as_ref() {
+                Some(path) => path.clone(),
+                None => plan.output_directory.join("metrics.prom").into(),
+            };
+            if let Err(e) = metrics::write(&metrics_file, &run_info, finished) {
+                // Like the export below, the failure of a check must not be replaced
+                eprintln!(
+                    "warning: writing metrics to {} failed: {e}",
+                    metrics_file.display()
+                );
+            }
+            if let Some(path) = args.badge_file.as_ref() {
+                let suite = match args.command.as_ref() {
+                    Some(Command::Suite { name }) => Some(name.as_str()),
+                    _ => None,
+                };
+                badge::write(path, suite, &run_info, &result)?;
+            }
+            if let Some(endpoint) = args.otlp_endpoint.as_deref()
+                && let Err(e) = otlp::export(endpoint, &run_info, &result, finished)
+            {
+                // Observability must not fail the run
+                eprintln!("warning: {e}");
+            }
+            let retention = config::workspace_retention(&metadata, &customs_files)?;
+            if retention.is_limited() {
+                retention.prune(&plan.output_directory)?;
+            }
+            match result.as_ref() {
+                Ok(()) => console.transcript("customs finished"),
+                Err(e) => console.transcript(format!("customs finished: {e}").as_str()),
+            }
+            result?;
+            plan.enforce_skips(args.forbid_skips, &args.forbid_skip_reason)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct RegulationCheck {
+    pub platform_target: String,
+    pub build_target: String,
+    pub job: Job,
+    pub features: Vec<String>,
+    pub default_features: bool,
+    pub toolchain: Option<String>,
+    pub config_file: Option<String>,
+    pub rustc_wrapper: Option<String>,
+    pub rustc: Option<String>,
+    pub cfgs: Vec<String>,
+    pub rustflags: Vec<String>,
+    /// Flags passed to rustdoc via `RUSTDOCFLAGS`.
+    pub rustdocflags: Vec<String>,
+    pub preset: Option<Preset>,
+    pub failure_log_lines: Option<usize>,
+    pub isolated_tmp: bool,
+    pub isolated_cargo_home: bool,
+    pub locked: bool,
+    pub offline: bool,
+    pub ignore_rust_version: bool,
+    pub board: Option<String>,
+    pub runner: Option<String>,
+    /// Name of the regulation the check stems from, for reports.
+    pub regulation: Option<String>,
+    /// Tags of the regulation the check stems from, for reports.
+    pub tags: Vec<String>,
+}
+
+/// Build target kind of compile-fail test suites, e.g. with trybuild.
+const UI_TEST_PREFIX: &str = "ui-test:";
+
+fn convert_build_target_specifier_to_cargo_argument(input: &str) -> String {
+    // lib is technically not a group of targets, but it is invoked without argument and hence handled here
+    let groups = ["lib", "bins", "examples", "tests", "benches", "all-targets"];
+
+    if groups.contains(&input) {
+        return format!("--{input}");
+    }
+
+    if input == "all" {
+        return "--all-targets".into();
+    }
+
+    // UI tests are integration tests, just with special treatment by customs
+    if let Some(name) = input.strip_prefix(UI_TEST_PREFIX) {
+        return format!("--test={name}");
+    }
+
+    let prefixes = ["bin:", "example:", "test:", "bench:"];
+    for prefix in prefixes {
+        if input.strip_prefix(prefix).is_some() {
+            return format!("--{}", input.to_string().replace(":", "="));
+        }
+    }
+
+    panic!("invalid build target {}", input)
+}
+
+impl RegulationCheck {
+    /// The environment variable cargo reads the runner for the platform target from.
+    pub fn runner_variable(&self) -> String {
+        let triple = self.platform_target.to_uppercase().replace(['-', '.'], "_");
+        format!("CARGO_TARGET_{triple}_RUNNER")
+    }
+
+    pub fn invocation(&self, path: &Path) -> Invocation {
+        let build_target: String =
+            convert_build_target_specifier_to_cargo_argument(self.build_target.as_str());
+
+        let mut platform_target = Some(self.platform_target.as_str());
+        const HOST_PLATFORM_DESIGNATOR: &str = "host";
+        if platform_target.is_some_and(|e| e == HOST_PLATFORM_DESIGNATOR) {
+            platform_target = None;
+        }
+
+        let kind = JobKind::of(&self.job);
+        let mut args = kind.command_args(&self.job, path);
+
+        if kind.takes_build_target() {
+            args.push(build_target);
+
+            if let Some(config_file) = self.config_file.as_ref() {
+                args.push("--config".into());
+                args.push(path.join(config_file).to_string_lossy().into_owned());
+            }
+        }
+
+        if self.locked && kind.takes_lock_flags() {
+            args.push("--locked".into());
+        }
+
+        if self.offline && kind.takes_lock_flags() {
+            args.push("--offline".into());
+        }
+
+        if self.ignore_rust_version && kind.takes_lock_flags() {
+            args.push("--ignore-rust-version".into());
+        }
+
+        if let Some(platform_target) = platform_target
+            && kind.takes_platform_target()
+        {
+            args.push(format!("--target={platform_target}"));
+        }
+
+        if !self.default_features && kind.takes_features() {
+            args.push("--no-default-features".into());
+        }
+
+        if !self.features.is_empty() && kind.takes_features() {
+            args.push("--features".into());
+            args.push(self.features.join(","));
+        }
+
+        let tool_args = kind.tool_args(&self.job);
+        let program = kind.program();
+        if program == "cargo" {
+            args.extend(
+                self.job
+                    .cargo_verbosity
+                    .and_then(|e| e.flag())
+                    .map(String::from),
+            );
+            args.extend(self.job.cargo_args.iter().cloned());
+            if self.job.compiles_tests_only() {
+                args.push("--no-run".into());
+            }
+        }
+
+        // Standalone tools take the job arguments directly
+        if program == "cargo" && (!tool_args.is_empty() || !self.job.args.is_empty()) {
+            args.push("--".into());
+        }
+        args.extend(tool_args);
+        args.extend(self.job.args.iter().cloned());
+
+        let mut env = BTreeMap::new();
+        if let Some(toolchain) = self.toolchain.as_ref() {
+            env.insert("RUSTUP_TOOLCHAIN".into(), toolchain.clone());
+        }
+
+        if let Some(rustc_wrapper) = self.rustc_wrapper.as_ref() {
+            env.insert("RUSTC_WRAPPER".into(), rustc_wrapper.clone());
+        }
+
+        if let Some(runner) = self.runner.as_ref() {
+            env.insert(self.runner_variable(), runner.clone());
+        }
+
+        if let Some(rustc) = self.rustc.as_ref() {
+            env.insert(
+                "RUSTC".into(),
+                path.join(rustc).to_string_lossy().into_owned(),
+            );
+        }
+
+        if !self.cfgs.is_empty() || !self.rustflags.is_empty() {
+            // Setting RUSTFLAGS replaces the flags from the environment, so those are kept.
+            let rustflags = std::env::var("RUSTFLAGS")
+                .into_iter()
+                .chain(self.rustflags.iter().cloned())
+                .chain(self.cfgs.iter().map(|e| format!("--cfg {e}")))
+                .join(" ");
+            env.insert("RUSTFLAGS".into(), rustflags);
+        }
+
+        let mut rustdoc_flags = kind.rustdoc_flags();
+        rustdoc_flags.extend(self.rustdocflags.iter().cloned());
+        if !rustdoc_flags.is_empty() {
+            let rustdoc_flags = std::env::var("RUSTDOCFLAGS")
+                .into_iter()
+                .chain(rustdoc_flags)
+                .join(" ");
+            env.insert("RUSTDOCFLAGS".into(), rustdoc_flags);
+        }
+
+        // Build scripts detect docs.rs by this
+        if kind == JobKind::DocsRs {
+            env.insert("DOCS_RS".into(), "1".into());
+        }
+
+        Invocation {
+            program,
+            args,
+            env,
+            current_dir: path.to_path_buf(),
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::{BTreeSet, HashSet};
+use std::ops::Range;
+
+use cargo_metadata::camino::{Utf8Path, Utf8PathBuf};
+use itertools::Itertools;
+use serde::Deserialize;
+use toml::Spanned;
+
+use crate::Result;
+use crate::config::{Jobs, PackageCustoms, parse_customs_toml};
+
+/// Only the locations of the sections of a customs file, the content is validated when loading.
+#[derive(Debug, Deserialize)]
+struct CustomsFileSpans {
+    default: Option<Spanned<toml::Table>>,
+    #[serde(default)]
+    regulation: Vec<Spanned<toml::Table>>,
+}
+
+struct SourceFile {
+    path: Utf8PathBuf,
+    text: String,
+    spans: CustomsFileSpans,
+}
+
+impl SourceFile {
+    fn read(path: &Utf8Path) -> Result<SourceFile> {
+        let text = std::fs::read_to_string(path.as_std_path())?;
+        let spans = parse_customs_toml(path.as_std_path(), text.as_str())?;
+        Ok(SourceFile {
+            path: path.to_path_buf(),
+            text,
+            spans,
+        })
+    }
+
+    fn line(&self, span: &Range<usize>) -> usize {
+        self.text[..span.start].matches('\n').count() + 1
+    }
+
+    fn regulation_line(&self, index: usize) -> usize {
+        self.line(&self.spans.regulation[index].span())
+    }
+
+    fn warn(&self, span: Range<usize>, message: &str) {
+        let line = self.line(&span);
+        let column = span.start - self.text[..span.start].rfind('\n').map_or(0, |e| e + 1) + 1;
+        log::warn!("{}:{line}:{column}: {message}", self.path);
+    }
+}
+
+/// Warns about configuration that is valid, but most likely not what was intended.
+#[derive(Debug, Default)]
+pub struct Linter {
+    defaults: BTreeSet<Utf8PathBuf>,
+    applied_defaults: BTreeSet<Utf8PathBuf>,
+}
+
+impl Linter {
+    pub fn lint_package(&mut self, package: &PackageCustoms) -> Result<()> {
+        self.defaults.extend(package.defaults.iter().cloned());
+        self.applied_defaults
+            .extend(package.applied_default.iter().cloned());
+
+        // Locations are only known for TOML written by hand
+        if package.path.extension() != Some("toml") || package.customs.generator.is_some() {
+            return Ok(());
+        }
+
+        let source = SourceFile::read(&package.path)?;
+        // Regulations of preset sets follow those written in the file
+        let written = source.spans.regulation.len();
+        let regulations = &package.customs.regulation[..written];
+
+        for (index, regulation) in regulations.iter().enumerate() {
+            if let Jobs::Short(jobs) = &regulation.jobs
+                && let Some(duplicate) = jobs.iter().duplicates().next()
+            {
+                source.warn(
+                    source.spans.regulation[index].span(),
+                    format!("job '{duplicate}' is listed more than once").as_str(),
+                );
+            }
+        }
+
+        let expansions = regulations
+            .iter()
+            .map(|e| e.clone().expand().into_iter().collect::<HashSet<_>>())
+            .collect::<Vec<_>>();
+
+        for (index, expansion) in expansions.iter().enumerate() {
+            let span = source.spans.regulation[index].span();
+
+            if expansion.is_empty() {
+                source.warn(span, "regulation expands to no checks");
+                continue;
+            }
+
+            // Identical regulations shadow each other, only the later one is reported.
+            let shadowing = expansions
+                .iter()
+                .enumerate()
+                .find(|(other, other_expansion)| {
+                    *other != index
+                        && regulations[*other].host_os == regulations[index].host_os
+                        && expansion.is_subset(other_expansion)
+                        && (expansion != *other_expansion || *other < index)
+                });
+
+            if let Some((other, _)) = shadowing {
+                source.warn(
+                    span,
+                    format!(
+                        "regulation is shadowed by the regulation at line {}",
+                        source.regulation_line(other)
+                    )
+                    .as_str(),
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reports lints that are only known after all packages were seen.
+    pub fn finish(self) -> Result<()> {
+        for path in self
+            .defaults
+            .difference(&self.applied_defaults)
+            .filter(|e| e.extension() == Some("toml"))
+        {
+            let source = SourceFile::read(path)?;
+            if let Some(default) = source.spans.default.as_ref() {
+                source.warn(
+                    default.span(),
+                    "default never applies to any of the selected packages",
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,152 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+/// What cargo prints when another process holds one of its locks, followed by what is locked,
+/// e.g. `package cache` or `build directory`.
+const BLOCKING: &str = "Blocking waiting for file lock on ";
+
+/// How long the lock request may take to show up after cargo announced it.
+const REQUEST_ATTEMPTS: usize = 10;
+const REQUEST_DELAY: Duration = Duration::from_millis(50);
+
+/// The lock cargo started to wait for according to its output, if any.
+///
+/// Colored output is matched as well, e.g. with `CARGO_TERM_COLOR=always`.
+pub fn detect(output: &str) -> Option<String> {
+    let output = strip_escapes(output);
+    let (_, rest) = output.split_once(BLOCKING)?;
+    Some(rest.lines().next().unwrap_or_default().trim().to_string())
+}
+
+/// Removes the ANSI escape sequences of terminal colors and styles.
+fn strip_escapes(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(e) = chars.next() {
+        match e {
+            // Up to the final byte of the control sequence, e.g. `\x1b[1m`
+            '\x1b' => {
+                if chars.next() == Some('[') {
+                    for e in chars.by_ref() {
+                        if ('@'..='~').contains(&e) {
+                            break;
+                        }
+                    }
+                }
+            }
+            e => stripped.push(e),
+        }
+    }
+    stripped
+}
+
+/// Tells which process the check waits for, e.g. another cargo of a parallel run.
+pub fn describe(id: &str, lock: &str, pid: u32) -> String {
+    match (0..REQUEST_ATTEMPTS).find_map(|attempt| {
+        if attempt > 0 {
+            std::thread::sleep(REQUEST_DELAY);
+        }
+        holder(pid)
+    }) {
+        Some(holder) => {
+            let name = std::fs::read_to_string(format!("/proc/{holder}/comm")).unwrap_or_default();
+            format!(
+                "{id}: waiting on the {lock} lock held by PID {holder} ({})",
+                name.trim()
+            )
+        }
+        None => format!("{id}: waiting on the {lock} lock"),
+    }
+}
+
+/// The process holding the lock that `pid` or one of its descendants waits for, only known on Linux.
+///
+/// In `/proc/locks`, a blocked request is marked with `->` and shares the device and inode with its holder.
+fn holder(pid: u32) -> Option<u32> {
+    let locks = std::fs::read_to_string("/proc/locks").ok()?;
+    holder_in(locks.as_str(), &descendants(pid))
+}
+
+/// The process holding the lock that one of the `processes` waits for, according to `/proc/locks`.
+fn holder_in(locks: &str, processes: &HashSet<u32>) -> Option<u32> {
+    // (waiting, pid, device:inode)
+    let entries = locks
+        .lines()
+        .filter_map(|line| {
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            let waiting = fields.get(1) == Some(&"->");
+            let offset = usize::from(waiting);
+            let pid = fields.get(4 + offset)?.parse::<u32>().ok()?;
+            let file = *fields.get(5 + offset)?;
+            Some((waiting, pid, file))
+        })
+        .collect::<Vec<_>>();
+
+    let (_, _, file) = entries
+        .iter()
+        .find(|(waiting, pid, _)| *waiting && processes.contains(pid))?;
+    entries
+        .iter()
+        .find(|(waiting, _, other)| !waiting && other == file)
+        .map(|(_, pid, _)| *pid)
+}
+
+/// The process and all processes it spawned, e.g. cargo behind the rustup proxy.
+fn descendants(pid: u32) -> HashSet<u32> {
+    let parents = std::fs::read_dir("/proc")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|e| {
+            let child = e.file_name().to_str()?.parse::<u32>().ok()?;
+            let stat = std::fs::read_to_string(e.path().join("stat")).ok()?;
+            // The command name in parentheses may contain spaces
+            let (_, rest) = stat.rsplit_once(')')?;
+            let parent = rest.split_whitespace().nth(1)?.parse::<u32>().ok()?;
+            Some((child, parent))
+        })
+        .collect::<Vec<_>>();
+
+    let mut processes = HashSet::from([pid]);
+    loop {
+        let count = processes.len();
+        for (child, parent) in parents.iter() {
+            if processes.contains(parent) {
+                processes.insert(*child);
+            }
+        }
+        if processes.len() == count {
+            return processes;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_finds_lock_in_colored_output() {
+        let output = "\x1b[1m\x1b[32m   Compiling\x1b[0m foo v0.1.0\n\
+                      \x1b[1m\x1b[36m    Blocking\x1b[0m waiting for file lock on build directory\n";
+        assert_eq!(detect(output).as_deref(), Some("build directory"));
+    }
+
+    #[test]
+    fn detect_finds_lock_in_plain_output() {
+        let output = "    Blocking waiting for file lock on package cache\n";
+        assert_eq!(detect(output).as_deref(), Some("package cache"));
+        assert_eq!(detect("   Compiling foo v0.1.0\n"), None);
+    }
+
+    #[test]
+    fn holder_in_matches_waiting_request_by_inode() {
+        let locks = "\
+1: FLOCK  ADVISORY  WRITE 100 00:2a:1234 0 EOF
+1: -> FLOCK  ADVISORY  WRITE 200 00:2a:1234 0 EOF
+2: FLOCK  ADVISORY  WRITE 300 00:2a:5678 0 EOF
+";
+        assert_eq!(holder_in(locks, &HashSet::from([200])), Some(100));
+        assert_eq!(holder_in(locks, &HashSet::from([300])), None);
+    }
+}
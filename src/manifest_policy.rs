@@ -0,0 +1,108 @@
+use cargo_metadata::{Metadata, Package};
+
+use crate::{Error, Result};
+
+/// Options of the built-in `manifest-policy` job, given as job arguments.
+#[derive(Debug, Clone, clap::Args)]
+pub struct Options {
+    /// Fields the manifest must set, e.g. `license,repository,rust-version`.
+    #[arg(long, value_delimiter = ',')]
+    require: Vec<String>,
+
+    /// Fields the manifest must not set, e.g. `authors`.
+    #[arg(long, value_delimiter = ',')]
+    ban: Vec<String>,
+
+    /// Require `[lints] workspace = true`.
+    #[arg(long)]
+    workspace_lints: bool,
+
+    /// The edition the package must use.
+    #[arg(long)]
+    edition: Option<String>,
+}
+
+/// Checks the manifest of the package in `directory` against the policy.
+pub fn check(metadata: &Metadata, directory: &std::path::Path, options: &Options) -> Result<()> {
+    let Some(package) = metadata
+        .workspace_packages()
+        .into_iter()
+        .find(|e| e.manifest_path.parent().is_some_and(|e| e == directory))
+    else {
+        return Err(Error::CheckFailed(format!(
+            "manifest-policy (no workspace member in {})",
+            directory.display()
+        )));
+    };
+
+    let mut problems = Vec::new();
+    for field in options.require.iter() {
+        match is_set(package, field) {
+            Some(true) => {}
+            Some(false) => problems.push(format!("`{field}` is required")),
+            None => problems.push(format!("`{field}` is not a known field")),
+        }
+    }
+    for field in options.ban.iter() {
+        match is_set(package, field) {
+            Some(false) => {}
+            Some(true) => problems.push(format!("`{field}` must not be set")),
+            None => problems.push(format!("`{field}` is not a known field")),
+        }
+    }
+    if let Some(edition) = options.edition.as_ref()
+        && package.edition.as_str() != edition
+    {
+        problems.push(format!(
+            "edition {} instead of {edition}",
+            package.edition.as_str()
+        ));
+    }
+    // The metadata resolves the lints, whether they are inherited is only in the manifest
+    if options.workspace_lints && !inherits_lints(package)? {
+        problems.push("`[lints] workspace = true` is required".into());
+    }
+
+    for problem in problems.iter() {
+        println!("{}: {problem}", package.manifest_path);
+    }
+
+    if !problems.is_empty() {
+        return Err(Error::CheckFailed(format!(
+            "manifest-policy ({} problems)",
+            problems.len()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Whether a field of the `[package]` table is set, `None` for unknown fields.
+fn is_set(package: &Package, field: &str) -> Option<bool> {
+    Some(match field {
+        "description" => package.description.is_some(),
+        "license" => package.license.is_some() || package.license_file.is_some(),
+        "license-file" => package.license_file.is_some(),
+        "repository" => package.repository.is_some(),
+        "homepage" => package.homepage.is_some(),
+        "documentation" => package.documentation.is_some(),
+        "readme" => package.readme.is_some(),
+        "rust-version" => package.rust_version.is_some(),
+        "authors" => !package.authors.is_empty(),
+        "keywords" => !package.keywords.is_empty(),
+        "categories" => !package.categories.is_empty(),
+        "links" => package.links.is_some(),
+        "default-run" => package.default_run.is_some(),
+        _ => return None,
+    })
+}
+
+fn inherits_lints(package: &Package) -> Result<bool> {
+    let data = std::fs::read_to_string(package.manifest_path.as_std_path())?;
+    let manifest: toml::Table = toml::from_str(data.as_str()).map_err(anyhow::Error::from)?;
+    Ok(manifest
+        .get("lints")
+        .and_then(|e| e.get("workspace"))
+        .and_then(|e| e.as_bool())
+        .unwrap_or(false))
+}
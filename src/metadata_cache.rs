@@ -0,0 +1,183 @@
+use std::collections::BTreeMap;
+use std::process::Command;
+use std::time::UNIX_EPOCH;
+
+use cargo_metadata::camino::{Utf8Path, Utf8PathBuf};
+use cargo_metadata::{Metadata, MetadataCommand};
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result};
+
+pub const FILE_NAME: &str = "metadata.json";
+
+/// `cargo metadata` of each directory customs was invoked in,
+/// valid as long as no manifest, lock file or cargo configuration changed.
+type Cache = BTreeMap<String, CachedMetadata>;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedMetadata {
+    /// Modification times of the files the metadata depends on, in nanoseconds, `None` if missing.
+    modified: BTreeMap<Utf8PathBuf, Option<u128>>,
+    metadata: Metadata,
+}
+
+/// Runs `cargo metadata`, or reuses its result from `target/customs/metadata.json`
+/// if the manifests of the workspace, its lock file and the cargo configuration did not change since.
+///
+/// Results are kept per directory, as the built-in jobs run in the directories of the packages.
+pub fn metadata() -> Result<Metadata> {
+    let current_dir = Utf8PathBuf::from_path_buf(std::env::current_dir()?)
+        .map_err(|e| Error::Unexpected(anyhow::anyhow!("{} is not valid UTF-8", e.display())))?;
+    let cache_file = cache_file(&current_dir);
+    let mut cache = cache_file
+        .as_ref()
+        .and_then(|e| std::fs::read_to_string(e.as_std_path()).ok())
+        .and_then(|e| serde_json::from_str::<Cache>(e.as_str()).ok())
+        .unwrap_or_default();
+
+    if let Some(cached) = cache.remove(current_dir.as_str())
+        // What the metadata depends on may have changed since the cache was written, e.g. by an update of customs
+        && dependencies(&cached.metadata, &current_dir)
+            .iter()
+            .all(|path| cached.modified.get(path) == Some(&modified_nanos(path)))
+    {
+        log::debug!("Using cached metadata of {current_dir}");
+        return Ok(cached.metadata);
+    }
+
+    let metadata = MetadataCommand::new().exec().map_err(|e| match e {
+        cargo_metadata::Error::CargoMetadata { stderr } => Error::Cargo(stderr),
+        _ => Error::Unexpected(e.into()),
+    })?;
+
+    if let Some(cache_file) = cache_file {
+        // Creating the target directory in a package directory changes its modification time
+        let _ = std::fs::create_dir_all(cache_file.parent().expect("cache file is in a directory"));
+        let modified = dependencies(&metadata, &current_dir)
+            .into_iter()
+            .map(|path| {
+                let modified = modified_nanos(&path);
+                (path, modified)
+            })
+            .collect();
+        let cached = CachedMetadata { modified, metadata };
+        cache.insert(current_dir.to_string(), cached);
+        // The cache is an optimization only
+        if let Err(e) = write(&cache_file, &cache) {
+            log::debug!("Cannot write metadata cache {cache_file}: {e}");
+        }
+        return Ok(cache
+            .remove(current_dir.as_str())
+            .expect("just inserted")
+            .metadata);
+    }
+
+    Ok(metadata)
+}
+
+/// Where the cache is, without running `cargo metadata`.
+fn cache_file(current_dir: &Utf8Path) -> Option<Utf8PathBuf> {
+    let output = Command::new("cargo")
+        .args(["locate-project", "--workspace", "--message-format", "plain"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let manifest = Utf8PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+    let target_directory = match configured_target_directory(current_dir) {
+        Some(e) => e,
+        None => manifest.parent()?.join("target"),
+    };
+    Some(target_directory.join("customs").join(FILE_NAME))
+}
+
+/// The target directory set in the environment or with `build.target-dir` in the cargo configuration.
+fn configured_target_directory(current_dir: &Utf8Path) -> Option<Utf8PathBuf> {
+    for variable in ["CARGO_TARGET_DIR", "CARGO_BUILD_TARGET_DIR"] {
+        if let Ok(directory) = std::env::var(variable) {
+            return Some(current_dir.join(directory));
+        }
+    }
+
+    // The closest configuration wins, relative paths are relative to the directory containing `.cargo`
+    config_files(current_dir).into_iter().find_map(|file| {
+        let data = std::fs::read_to_string(file.as_std_path()).ok()?;
+        let config = toml::from_str::<toml::Table>(data.as_str()).ok()?;
+        let directory = config.get("build")?.get("target-dir")?.as_str()?;
+        let base = file.parent()?.parent()?;
+        Some(base.join(directory))
+    })
+}
+
+/// The cargo configuration files that apply in `current_dir`, closest first, whether they exist or not.
+fn config_files(current_dir: &Utf8Path) -> Vec<Utf8PathBuf> {
+    let cargo_home = std::env::var("CARGO_HOME")
+        .map(Utf8PathBuf::from)
+        .ok()
+        .or_else(|| {
+            let home = Utf8PathBuf::from_path_buf(std::env::home_dir()?).ok()?;
+            Some(home.join(".cargo"))
+        });
+
+    current_dir
+        .ancestors()
+        .map(|e| e.join(".cargo"))
+        .chain(cargo_home)
+        .flat_map(|e| [e.join("config.toml"), e.join("config")])
+        .collect()
+}
+
+/// Files a change of which changes the metadata.
+///
+/// Besides the manifests, these are the directories cargo discovers targets in,
+/// as adding e.g. `src/bin/tool.rs` changes the modification time of `src/bin`,
+/// and the cargo configuration, e.g. with a `[patch]`.
+fn dependencies(metadata: &Metadata, current_dir: &Utf8Path) -> Vec<Utf8PathBuf> {
+    const TARGET_DIRECTORIES: &[&str] = &["src", "src/bin", "examples", "tests", "benches"];
+
+    let root = &metadata.workspace_root;
+    let mut files = vec![root.join("Cargo.toml"), root.join("Cargo.lock")];
+    files.extend(config_files(current_dir));
+    for package in metadata.workspace_packages() {
+        files.push(package.manifest_path.clone());
+        let directory = package
+            .manifest_path
+            .parent()
+            .expect("manifest is in a directory");
+        // Also a build script appearing
+        files.push(directory.to_path_buf());
+        for target_directory in TARGET_DIRECTORIES {
+            let target_directory = directory.join(target_directory);
+            // Targets of several files, e.g. `src/bin/tool/main.rs`
+            if let Ok(entries) = target_directory.read_dir_utf8() {
+                files.extend(
+                    entries
+                        .flatten()
+                        .filter(|e| e.path().is_dir())
+                        .map(|e| e.path().to_path_buf()),
+                );
+            }
+            files.push(target_directory);
+        }
+    }
+    files
+}
+
+fn modified_nanos(path: &Utf8Path) -> Option<u128> {
+    let modified = std::fs::metadata(path.as_std_path())
+        .ok()?
+        .modified()
+        .ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_nanos())
+}
+
+/// Replaces the cache at once, so concurrent invocations never read a partially written one.
+fn write(path: &Utf8Path, cache: &Cache) -> Result<()> {
+    let json = serde_json::to_string(cache).map_err(anyhow::Error::from)?;
+    let partial = path.with_extension(format!("json.{}", std::process::id()));
+    std::fs::write(partial.as_std_path(), json)?;
+    std::fs::rename(partial.as_std_path(), path.as_std_path())?;
+    Ok(())
+}
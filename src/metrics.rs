@@ -0,0 +1,93 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::Result;
+use crate::run::RunInfo;
+
+/// Writes the run in the Prometheus text format, e.g. for the textfile collector of node-exporter.
+///
+/// The file is replaced at once, so a collector never reads a partial file.
+pub fn write(path: &Path, run_info: &RunInfo, finished: f64) -> Result<()> {
+    let mut text = String::new();
+    let mut metric = |name: &str, help: &str, samples: Vec<(String, f64)>| {
+        let _ = writeln!(text, "# HELP {name} {help}");
+        let _ = writeln!(text, "# TYPE {name} gauge");
+        for (labels, value) in samples {
+            let _ = writeln!(text, "{name}{labels} {value}");
+        }
+    };
+
+    metric(
+        "customs_run_start_timestamp_seconds",
+        "When the run started, in seconds since the Unix epoch.",
+        vec![(String::new(), run_info.started)],
+    );
+    metric(
+        "customs_run_duration_seconds",
+        "Wall time of the whole run.",
+        vec![(String::new(), finished - run_info.started)],
+    );
+
+    let mut counts = [("passed", 0), ("failed", 0), ("skipped", 0), ("not_run", 0)];
+    for check in run_info.plan.checks.iter() {
+        let status = match run_info.outcomes.get(&check.id) {
+            Some(outcome) if outcome.passed => 0,
+            Some(_) => 1,
+            None if check.skipped.is_some() => 2,
+            None => 3,
+        };
+        counts[status].1 += 1;
+    }
+    metric(
+        "customs_checks",
+        "Checks of the run by status.",
+        counts
+            .iter()
+            .map(|(status, count)| (labels(&[("status", status)]), f64::from(*count)))
+            .collect(),
+    );
+
+    metric(
+        "customs_check_duration_seconds",
+        "Wall time of each executed check.",
+        run_info
+            .plan
+            .checks
+            .iter()
+            .filter_map(|check| {
+                let outcome = run_info.outcomes.get(&check.id)?;
+                let labels = labels(&[
+                    ("id", check.id.as_str()),
+                    ("package", check.package.as_str()),
+                    ("target", check.check.platform_target.as_str()),
+                    ("build_target", check.check.build_target.as_str()),
+                    ("job", check.check.job.name.as_str()),
+                    ("status", if outcome.passed { "passed" } else { "failed" }),
+                ]);
+                Some((labels, outcome.seconds))
+            })
+            .collect(),
+    );
+
+    let partial = path.with_extension("prom.partial");
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&partial, text)?;
+    std::fs::rename(&partial, path)?;
+    Ok(())
+}
+
+fn labels(pairs: &[(&str, &str)]) -> String {
+    let pairs = pairs
+        .iter()
+        .map(|(key, value)| {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{key}=\"{value}\"")
+        })
+        .collect::<Vec<_>>();
+    format!("{{{}}}", pairs.join(","))
+}
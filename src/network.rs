@@ -0,0 +1,57 @@
+use std::time::Duration;
+
+/// How often a check of a `network` job is retried after a network error.
+pub const RETRIES: u32 = 3;
+
+/// The delay before the first retry, doubled for each further one.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(2);
+
+/// Output of cargo, curl and git on failures of the network or a registry rather than of the check.
+const PATTERNS: &[&str] = &[
+    "spurious network error",
+    "failed to download",
+    "failed to fetch",
+    "failed to query replaced source registry",
+    "Couldn't resolve host",
+    "Could not resolve host",
+    "Connection reset by peer",
+    "Connection refused",
+    "Connection timed out",
+    "Operation timed out",
+    "SSL connect error",
+    "error sending request",
+    "502 Bad Gateway",
+    "503 Service Unavailable",
+    "504 Gateway Timeout",
+];
+
+/// Whether the output of a failed check hints at a network error.
+pub fn detect(output: &str) -> bool {
+    PATTERNS.iter().any(|e| output.contains(e))
+}
+
+/// How long to wait before the retry after `attempt` failed attempts.
+pub fn backoff(attempt: u32) -> Duration {
+    INITIAL_BACKOFF * 2u32.pow(attempt.saturating_sub(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_network_errors_of_cargo_and_curl() {
+        assert!(detect(
+            "warning: spurious network error (2 tries remaining): [6] Couldn't resolve host name"
+        ));
+        assert!(detect("curl: (6) Could not resolve host: index.crates.io"));
+        assert!(!detect("error[E0308]: mismatched types"));
+    }
+
+    #[test]
+    fn backoff_doubles_per_attempt() {
+        assert_eq!(backoff(1), Duration::from_secs(2));
+        assert_eq!(backoff(2), Duration::from_secs(4));
+        assert_eq!(backoff(3), Duration::from_secs(8));
+    }
+}
@@ -0,0 +1,138 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use serde_json::{Value, json};
+
+use crate::plan::Fnv1a;
+use crate::run::RunInfo;
+use crate::{Error, Result};
+
+/// OTLP span kind `INTERNAL`.
+const SPAN_KIND_INTERNAL: u8 = 1;
+/// OTLP status codes.
+const STATUS_OK: u8 = 1;
+const STATUS_ERROR: u8 = 2;
+
+/// Sends the run as a trace with one span per executed check to an OTLP/HTTP endpoint.
+///
+/// The JSON encoding of OTLP is posted with `curl`, so customs needs no HTTP client of its own.
+/// The root span fails if any check failed or `result` is an error.
+pub fn export(
+    endpoint: &str,
+    run_info: &RunInfo,
+    result: &Result<()>,
+    finished: f64,
+) -> Result<()> {
+    let trace_id = trace_id(run_info);
+    let root_id = span_id(trace_id.as_str(), "");
+    let passed = result.is_ok() && run_info.outcomes.values().all(|e| e.passed);
+
+    let mut spans = vec![json!({
+        "traceId": trace_id,
+        "spanId": root_id,
+        "name": "customs",
+        "kind": SPAN_KIND_INTERNAL,
+        "startTimeUnixNano": nanos(run_info.started),
+        "endTimeUnixNano": nanos(finished),
+        "attributes": attributes(&[
+            ("customs.version", run_info.customs_version),
+            ("customs.args", run_info.args.join(" ").as_str()),
+            ("customs.host", run_info.host.as_str()),
+        ]),
+        "status": status(passed),
+    })];
+
+    for check in run_info.plan.checks.iter() {
+        let Some(outcome) = run_info.outcomes.get(&check.id) else {
+            continue;
+        };
+        spans.push(json!({
+            "traceId": trace_id,
+            "spanId": span_id(trace_id.as_str(), check.id.as_str()),
+            "parentSpanId": root_id,
+            "name": check.id,
+            "kind": SPAN_KIND_INTERNAL,
+            "startTimeUnixNano": nanos(outcome.started),
+            "endTimeUnixNano": nanos(outcome.started + outcome.seconds),
+            "attributes": attributes(&[
+                ("customs.package", check.package.as_str()),
+                ("customs.target", check.check.platform_target.as_str()),
+                ("customs.build_target", check.check.build_target.as_str()),
+                ("customs.job", check.check.job.name.as_str()),
+                ("customs.status", if outcome.passed { "passed" } else { "failed" }),
+            ]),
+            "status": status(outcome.passed),
+        }));
+    }
+
+    let request = json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": attributes(&[("service.name", "cargo-customs")]),
+            },
+            "scopeSpans": [{
+                "scope": { "name": "cargo-customs", "version": run_info.customs_version },
+                "spans": spans,
+            }],
+        }],
+    });
+
+    post(
+        format!("{}/v1/traces", endpoint.trim_end_matches('/')).as_str(),
+        &request,
+    )
+}
+
+fn post(url: &str, request: &Value) -> Result<()> {
+    let mut curl = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--request", "POST"])
+        .args(["--header", "Content-Type: application/json"])
+        .args(["--data-binary", "@-", url])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .map_err(|_| Error::ToolNotFound("curl".into()))?;
+    curl.stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(request.to_string().as_bytes())?;
+    let status = curl.wait()?;
+    if !status.success() {
+        return Err(Error::TraceExport(url.into(), status));
+    }
+    Ok(())
+}
+
+/// Unique per run, spans are derived from it to be unique per check.
+fn trace_id(run_info: &RunInfo) -> String {
+    let seed = format!("{} {}", run_info.started, std::process::id());
+    let mut high = Fnv1a::default();
+    high.write(seed.as_str());
+    let mut low = Fnv1a::default();
+    low.write(seed.as_str());
+    low.write(run_info.current_dir.as_str());
+    format!("{:016x}{:016x}", high.0, low.0)
+}
+
+fn span_id(trace_id: &str, check_id: &str) -> String {
+    let mut hasher = Fnv1a::default();
+    hasher.write(trace_id);
+    hasher.write(check_id);
+    format!("{:016x}", hasher.0)
+}
+
+/// Nanoseconds since the Unix epoch, as a string since JSON numbers lose precision beyond 2^53.
+fn nanos(seconds: f64) -> String {
+    ((seconds * 1e9) as u128).to_string()
+}
+
+fn attributes(pairs: &[(&str, &str)]) -> Value {
+    pairs
+        .iter()
+        .map(|(key, value)| json!({ "key": key, "value": { "stringValue": value } }))
+        .collect()
+}
+
+fn status(passed: bool) -> Value {
+    json!({ "code": if passed { STATUS_OK } else { STATUS_ERROR } })
+}
@@ -0,0 +1,65 @@
+use crate::config::{JobParameters, Jobs, Regulation};
+use crate::{Error, Result};
+
+/// Changes of all regulations for a single run, applied after defaults and presets.
+#[derive(Debug, Clone, Default, clap::Args)]
+pub struct Overrides {
+    /// Add a job to every regulation, may be given multiple times.
+    #[arg(long = "add-job", value_name = "JOB")]
+    add_jobs: Vec<String>,
+
+    /// Add a platform target to every regulation, may be given multiple times.
+    #[arg(long = "add-target", value_name = "TRIPLE")]
+    add_targets: Vec<String>,
+
+    /// Set a field of every regulation, e.g. `toolchain=nightly` or `locked=true`.
+    /// The value is read as TOML, or as a string if it is not valid TOML.
+    #[arg(long = "set", value_name = "KEY=VALUE", value_parser = parse_assignment)]
+    set: Vec<(String, String)>,
+}
+
+fn parse_assignment(text: &str) -> std::result::Result<(String, String), String> {
+    let (key, value) = text
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got '{text}'"))?;
+    Ok((key.trim().to_string(), value.trim().to_string()))
+}
+
+impl Overrides {
+    pub fn apply(&self, regulation: &mut Regulation) -> Result<()> {
+        for job in self.add_jobs.iter() {
+            match &mut regulation.jobs {
+                Jobs::Short(jobs) if !jobs.contains(job) => jobs.push(job.clone()),
+                Jobs::Detailed(jobs) => {
+                    jobs.entry(job.clone())
+                        .or_insert_with(JobParameters::default);
+                }
+                Jobs::Short(_) => {}
+            }
+        }
+
+        for target in self.add_targets.iter() {
+            if !regulation.platform_targets.contains(target) {
+                regulation.platform_targets.push(target.clone());
+            }
+        }
+
+        if self.set.is_empty() {
+            return Ok(());
+        }
+
+        // Through TOML, so every field is set like in a customs file
+        let mut fields = toml::Table::try_from(&*regulation).map_err(anyhow::Error::from)?;
+        for (key, value) in self.set.iter() {
+            let value = toml::from_str::<toml::Table>(format!("value = {value}").as_str())
+                .ok()
+                .and_then(|mut e| e.remove("value"))
+                .unwrap_or_else(|| toml::Value::String(value.clone()));
+            fields.insert(key.clone(), value);
+            *regulation = fields.clone().try_into().map_err(|e: toml::de::Error| {
+                Error::InvalidOverride(key.clone(), e.message().to_string())
+            })?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,1526 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Display;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Instant;
+
+use cargo_metadata::camino::{Utf8Path, Utf8PathBuf};
+use cargo_metadata::{Metadata, Package};
+use clap::ValueEnum;
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::boards;
+use crate::budget;
+use crate::cancellation::Cancellation;
+use crate::config::{self, CustomsFiles, load_customs};
+use crate::console::{Console, Verbosity};
+use crate::discovery::Exclusions;
+use crate::docs_rs;
+use crate::executor::{CheckOutcome, Executor};
+use crate::heartbeat::{self, Tee};
+use crate::history::{CoverageHistory, Durations, format_duration};
+use crate::insta;
+use crate::job::{self, Evaluation, JobKind};
+use crate::lint::Linter;
+use crate::network;
+use crate::platform::PlatformTargets;
+use crate::problem_matcher;
+use crate::replay;
+use crate::result_cache::ResultCache;
+use crate::run::{Outcome, unix_seconds};
+use crate::shard::{self, Shard};
+use crate::targets;
+use crate::telemetry::Sampler;
+use crate::test_results::{self, TestOutcome};
+use crate::toolchain::{ToolchainFile, find_toolchain_file, launch_toolchain};
+use crate::tools::Tools;
+use crate::{Cli, Error, RegulationCheck, Result};
+
+/// The fully expanded set of checks for all selected packages,
+/// in the order they are executed.
+#[derive(Debug, Clone, Serialize)]
+pub struct Plan {
+    /// Resolved tools, only recorded in hermetic mode.
+    pub tools: Option<Tools>,
+    pub checks: Vec<PlannedCheck>,
+    /// Expected wall time of all checks that are not skipped, in seconds,
+    /// `None` if there is no prediction for any of them.
+    pub predicted_duration: Option<f64>,
+    /// Where logs and other artifacts of a run are written, `target/customs`.
+    #[serde(skip)]
+    pub output_directory: Utf8PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedCheck {
+    pub id: String,
+    pub package: String,
+    pub manifest_path: Utf8PathBuf,
+    pub customs_file: Utf8PathBuf,
+    #[serde(flatten)]
+    pub check: RegulationCheck,
+    pub command: Invocation,
+    /// The toolchain the check actually uses, `None` for the rustup default.
+    pub effective_toolchain: Option<String>,
+    /// Judges the output of the command in addition to its exit status.
+    pub evaluation: Option<Evaluation>,
+    /// Set if the check is not executed.
+    pub skipped: Option<SkipReason>,
+    /// Expected wall time in seconds, `None` if there is nothing to base a prediction on.
+    pub predicted_duration: Option<f64>,
+    /// The shard whose part of the tests a `test` check runs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shard: Option<Shard>,
+}
+
+/// How [`Plan::execute`] runs the checks.
+#[derive(Debug, Clone, Copy)]
+pub struct Schedule {
+    /// Whether the remaining checks still run after a check failed.
+    pub keep_going: bool,
+    /// How many checks run at the same time.
+    pub jobs: usize,
+    /// Whether checks of the same package run one after another, unless both have their own target directory,
+    /// so they do not skew each other's durations.
+    pub package_fence: bool,
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Schedule {
+            keep_going: false,
+            jobs: 1,
+            package_fence: true,
+        }
+    }
+}
+
+/// Why a check was not executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum SkipReason {
+    /// The regulation is restricted to other host operating systems.
+    HostOs,
+    /// The check already succeeded for the same commit.
+    #[serde(rename = "cache")]
+    #[value(name = "cache")]
+    CacheHit,
+    /// The regulation reduces its checks outside of CI.
+    #[serde(rename = "local")]
+    #[value(name = "local")]
+    LocalRun,
+    /// The package has no target matching the build target.
+    Pruned,
+    /// The check does not fit into the time budget of the run.
+    #[serde(rename = "budget")]
+    #[value(name = "budget")]
+    OverBudget,
+    /// Another shard of the run executes the check.
+    #[serde(rename = "shard")]
+    #[value(name = "shard")]
+    OtherShard,
+}
+
+impl Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SkipReason::HostOs => write!(f, "not applicable to host OS"),
+            SkipReason::CacheHit => write!(f, "already succeeded for this commit"),
+            SkipReason::LocalRun => write!(f, "reduced for local runs, run with --full"),
+            SkipReason::Pruned => write!(f, "package has no such build target"),
+            SkipReason::OverBudget => write!(f, "does not fit into the time budget"),
+            SkipReason::OtherShard => write!(f, "runs on another shard"),
+        }
+    }
+}
+
+/// Marks the `stdin` of a job as a path instead of the input itself.
+const STDIN_FILE_PREFIX: &str = "file:";
+
+/// The output streams of a check, each logged to its own file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogStream {
+    /// Both, interleaved as they were printed.
+    Both,
+    Stdout,
+    Stderr,
+}
+
+/// A fully resolved process invocation.
+#[derive(Debug, Clone, Serialize)]
+pub struct Invocation {
+    pub program: String,
+    pub args: Vec<String>,
+    pub env: BTreeMap<String, String>,
+    pub current_dir: PathBuf,
+}
+
+impl Invocation {
+    pub fn to_command(&self) -> Command {
+        let mut command = Command::new(self.program.as_str());
+        command
+            .args(self.args.iter())
+            .envs(self.env.iter())
+            .current_dir(self.current_dir.as_path())
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit());
+        command
+    }
+
+    /// Prints what is run, the environment only lists what differs from the inherited one.
+    fn trace(&self) {
+        eprint!("{}", self.describe());
+    }
+
+    /// The environment customs adds or overrides, the working directory and the command line,
+    /// one indented line each.
+    pub fn describe(&self) -> String {
+        let mut lines = String::new();
+        for (key, value) in self.env.iter() {
+            lines.push_str(format!("  {key}={value}\n").as_str());
+        }
+        lines.push_str(format!("  cd {}\n", self.current_dir.display()).as_str());
+        lines.push_str(format!("  {}\n", self.command_line()).as_str());
+        lines
+    }
+
+    /// The command line prefixed with the environment customs adds or overrides, as a shell takes it.
+    ///
+    /// The `CUSTOMS_*` description of the check is left out, it does not change what runs.
+    pub fn env_command_line(&self) -> String {
+        let quote = |e: &str| match e
+            .chars()
+            .all(|e| e.is_ascii_alphanumeric() || "-_.,/:=+@%".contains(e))
+        {
+            true => e.to_string(),
+            false => format!("'{}'", e.replace('\'', r"'\''")),
+        };
+        self.env
+            .iter()
+            .filter(|(key, _)| !key.starts_with("CUSTOMS_"))
+            .map(|(key, value)| format!("{key}={}", quote(value)))
+            .chain(std::iter::once(self.command_line()))
+            .join(" ")
+    }
+
+    /// The program and its arguments, separated by spaces.
+    pub fn command_line(&self) -> String {
+        std::iter::once(&self.program)
+            .chain(self.args.iter())
+            .join(" ")
+    }
+}
+
+impl Plan {
+    /// Executes all checks that are not skipped, as many at a time as the schedule allows.
+    ///
+    /// Checks building in the same target directory never run concurrently,
+    /// they would only wait for cargo's lock on it.
+    /// The outcome of every executed check is recorded in `outcomes`.
+    /// Once `cancellation` is cancelled, no further checks start and the run fails.
+    pub fn execute(
+        &self,
+        executor: &dyn Executor,
+        cancellation: &Cancellation,
+        result_cache: Option<&ResultCache>,
+        console: &Console,
+        schedule: Schedule,
+        outcomes: &mut BTreeMap<String, Outcome>,
+    ) -> Result<()> {
+        insta::reset(&self.output_directory)?;
+        let mut durations = Durations::load(&self.output_directory)?;
+        let mut failed = Vec::new();
+        let mut error = None;
+
+        let mut pending = Vec::new();
+        for check in self.checks.iter() {
+            match check.skipped {
+                Some(reason) => {
+                    console.transcript(format!("skipped {}: {reason}", check.id).as_str())
+                }
+                None => pending.push(check),
+            }
+        }
+
+        std::thread::scope(|scope| -> Result<()> {
+            let (sender, receiver) = std::sync::mpsc::channel();
+            let mut busy = HashSet::new();
+            let mut busy_packages = HashSet::new();
+            let fenced = |check: &PlannedCheck| {
+                (schedule.package_fence && !check.command.env.contains_key("CARGO_TARGET_DIR"))
+                    .then(|| check.package.clone())
+            };
+            let mut running = 0;
+
+            loop {
+                // The first pending check in plan order that may start, with a single job the plan order itself
+                if cancellation.is_cancelled() {
+                    error.get_or_insert(Error::Cancelled);
+                }
+                while error.is_none() && running < schedule.jobs {
+                    let Some(index) = pending.iter().position(|e| {
+                        self.target_directory(e)
+                            .is_none_or(|directory| !busy.contains(&directory))
+                            && fenced(e).is_none_or(|package| !busy_packages.contains(&package))
+                    }) else {
+                        break;
+                    };
+                    let check = pending.remove(index);
+
+                    // In hermetic mode, nothing may be looked up in PATH.
+                    if self.tools.is_some()
+                        && !Path::new(check.command.program.as_str()).is_absolute()
+                    {
+                        error = Some(Error::UnresolvedTool(check.command.program.clone()));
+                        break;
+                    }
+
+                    busy.extend(self.target_directory(check));
+                    busy_packages.extend(fenced(check));
+                    running += 1;
+                    let sender = sender.clone();
+                    scope.spawn(move || {
+                        let finished = self.execute_check(executor, check, console, cancellation);
+                        // Only gone if recording an earlier check failed
+                        let _ = sender.send((check, finished));
+                    });
+                }
+                if running == 0 {
+                    break;
+                }
+
+                let (check, (result, seconds, outcome)) =
+                    receiver.recv().expect("running checks report back");
+                running -= 1;
+                if let Some(directory) = self.target_directory(check) {
+                    busy.remove(&directory);
+                }
+                if let Some(package) = fenced(check) {
+                    busy_packages.remove(&package);
+                }
+                if let Some(outcome) = outcome {
+                    outcomes.insert(check.id.clone(), outcome);
+                }
+
+                match result {
+                    Err(Error::CheckFailed(_)) if schedule.keep_going => {
+                        failed.push(check);
+                        continue;
+                    }
+                    // Checks already running are still awaited and recorded
+                    Err(Error::Cancelled) => {
+                        error.get_or_insert(Error::Cancelled);
+                        continue;
+                    }
+                    Err(e) => {
+                        replay::write(&self.output_directory, &[check])?;
+                        error.get_or_insert(e);
+                        continue;
+                    }
+                    Ok(()) => durations.record(check.id.as_str(), seconds)?,
+                }
+
+                if let Some(result_cache) = result_cache {
+                    result_cache.record(check)?;
+                }
+            }
+
+            Ok(())
+        })?;
+
+        if let Some(e) = error {
+            return Err(e);
+        }
+
+        replay::write(&self.output_directory, &failed)?;
+        self.print_skipped(console);
+
+        match failed.as_slice() {
+            [] => {}
+            [check] => return Err(Error::CheckFailed(check.id.clone())),
+            _ => return Err(Error::ChecksFailed(failed.len())),
+        }
+
+        Ok(())
+    }
+
+    /// Runs a single check, returning its result, its duration in seconds
+    /// and its outcome unless customs itself failed to run it.
+    fn execute_check(
+        &self,
+        executor: &dyn Executor,
+        check: &PlannedCheck,
+        console: &Console,
+        cancellation: &Cancellation,
+    ) -> (Result<()>, f64, Option<Outcome>) {
+        console.begin_check(check.id.as_str());
+        console.transcript(format!("begin {}", check.id).as_str());
+        for line in check.command.describe().lines() {
+            console.transcript(line);
+        }
+        let start = Instant::now();
+        let started = unix_seconds();
+        let sampler = console.telemetry.then(Sampler::start);
+        let CheckOutcome { result, tests } =
+            executor.execute(check, console, &self.output_directory, cancellation);
+        let usage = sampler.and_then(Sampler::finish);
+        console.end_check(check.id.as_str());
+
+        let seconds = start.elapsed().as_secs_f64();
+        let status = match result.as_ref() {
+            Ok(()) => "passed".to_string(),
+            Err(Error::CheckFailed(_)) => "failed".to_string(),
+            Err(Error::Cancelled) => "cancelled".to_string(),
+            Err(e) => format!("error: {e}"),
+        };
+        console.transcript(
+            format!(
+                "end {} {status} after {}",
+                check.id,
+                format_duration(seconds)
+            )
+            .as_str(),
+        );
+
+        let recorded = matches!(
+            result,
+            Ok(()) | Err(Error::CheckFailed(_)) | Err(Error::Cancelled)
+        );
+        let outcome = recorded.then(|| {
+            let log = |stream| {
+                Some(check.log_file(&self.output_directory, stream)).filter(|e| e.is_file())
+            };
+            Outcome {
+                passed: result.is_ok(),
+                cancelled: matches!(result, Err(Error::Cancelled)),
+                started,
+                seconds,
+                tests,
+                usage,
+                stdout_log: log(LogStream::Stdout),
+                stderr_log: log(LogStream::Stderr),
+            }
+        });
+        (result, seconds, outcome)
+    }
+
+    /// The target directory a check builds in, `None` if its job builds nothing.
+    fn target_directory(&self, check: &PlannedCheck) -> Option<PathBuf> {
+        if !JobKind::of(&check.check.job).uses_target_directory() {
+            return None;
+        }
+        Some(match check.command.env.get("CARGO_TARGET_DIR") {
+            Some(directory) => check.command.current_dir.join(directory),
+            None => self
+                .output_directory
+                .parent()
+                .expect("the output directory is in the target directory")
+                .into(),
+        })
+    }
+
+    /// Predicts the duration of each check from the last successful runs.
+    pub fn predict_durations(&mut self) -> Result<()> {
+        let durations = Durations::load(&self.output_directory)?;
+        for check in self.checks.iter_mut() {
+            check.predicted_duration = durations.get(check.id.as_str());
+        }
+        self.update_predicted_duration();
+        Ok(())
+    }
+
+    /// Sums up the predictions of all checks that are not skipped.
+    pub fn update_predicted_duration(&mut self) {
+        let predictions = self
+            .checks
+            .iter()
+            .filter(|e| e.skipped.is_none())
+            .filter_map(|e| e.predicted_duration)
+            .collect::<Vec<_>>();
+
+        self.predicted_duration = match predictions.is_empty() {
+            true => None,
+            false => Some(predictions.iter().sum()),
+        };
+    }
+
+    /// Estimated duration of the plan, for the user before starting.
+    pub fn describe_prediction(&self) -> Option<String> {
+        let total = self.predicted_duration?;
+        let unknown = self
+            .checks
+            .iter()
+            .filter(|e| e.skipped.is_none() && e.predicted_duration.is_none())
+            .count();
+
+        Some(match unknown {
+            0 => format!("about {}", format_duration(total)),
+            _ => format!(
+                "about {} and {unknown} checks without history",
+                format_duration(total)
+            ),
+        })
+    }
+
+    fn skipped(&self) -> impl Iterator<Item = (&PlannedCheck, SkipReason)> {
+        self.checks
+            .iter()
+            .filter_map(|e| e.skipped.map(|reason| (e, reason)))
+    }
+
+    fn print_skipped(&self, console: &Console) {
+        let skipped = self
+            .skipped()
+            .map(|(check, reason)| (check.id.clone(), reason.to_string()))
+            .collect::<Vec<_>>();
+
+        if !skipped.is_empty() {
+            console.table("Skipped:", &skipped);
+        }
+    }
+
+    pub fn enforce_skips(&self, forbid_all: bool, forbidden: &[SkipReason]) -> Result<()> {
+        let violations = self
+            .skipped()
+            // Pruned checks have nothing to run and sharded checks run elsewhere,
+            // they only violate if named explicitly.
+            .filter(|(_, reason)| {
+                let deliberate = matches!(reason, SkipReason::Pruned | SkipReason::OtherShard);
+                (forbid_all && !deliberate) || forbidden.contains(reason)
+            })
+            .count();
+
+        if violations > 0 {
+            return Err(Error::ForbiddenSkips(violations));
+        }
+
+        Ok(())
+    }
+}
+
+impl PlannedCheck {
+    /// Identifies what the check does independent of where the workspace is located.
+    pub fn fingerprint(&self, workspace_root: &Utf8Path) -> String {
+        let relative = |e: &str| e.replace(workspace_root.as_str(), "");
+
+        let program = Path::new(self.command.program.as_str())
+            .file_name()
+            .map(|e| e.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let mut hasher = Fnv1a::default();
+        hasher.write(self.id.as_str());
+        hasher.write(program.as_str());
+        for arg in self.command.args.iter() {
+            hasher.write(relative(arg).as_str());
+        }
+        for (key, value) in self.command.env.iter() {
+            hasher.write(key);
+            hasher.write(relative(value).as_str());
+        }
+        hasher.write(relative(self.command.current_dir.to_string_lossy().as_ref()).as_str());
+        hasher.write(self.effective_toolchain.as_deref().unwrap_or_default());
+
+        format!("{:016x}", hasher.0)
+    }
+
+    /// Runs the check, captured output is written to a log file in the output directory of the plan.
+    ///
+    /// The results of individual tests are added to `tests`, if the job reports any.
+    pub fn execute(
+        &self,
+        console: &Console,
+        output_directory: &Utf8Path,
+        cancellation: &Cancellation,
+        tests: &mut BTreeMap<String, TestOutcome>,
+    ) -> Result<()> {
+        let verbosity = console.verbosity;
+        if verbosity >= Verbosity::Trace {
+            self.command.trace();
+        }
+
+        // Without streaming, the output is only shown if the check fails.
+        let stream = verbosity >= Verbosity::Verbose;
+        let mut command = self.command.to_command();
+        if std::env::var_os("CARGO_TERM_COLOR").is_none() {
+            command.env("CARGO_TERM_COLOR", console.child_color());
+        }
+        // The toolchain was decided when planning, what rustup resolved for customs itself must not leak
+        if !self.command.env.contains_key("RUSTUP_TOOLCHAIN") {
+            command.env_remove("RUSTUP_TOOLCHAIN");
+            command.env_remove("RUSTUP_TOOLCHAIN_SOURCE");
+        }
+        if console.ci.is_some() && std::env::var_os("CARGO_TERM_PROGRESS_WHEN").is_none() {
+            // Progress bars are only noise in CI logs
+            command.env("CARGO_TERM_PROGRESS_WHEN", "never");
+        }
+        // The transcript and the detection of network errors and failed snapshots need all output,
+        // streamed output is still shown while it arrives, prefixed if other checks stream at the same time
+        command.stdout(Stdio::piped()).stderr(Stdio::piped());
+        let reports_tests = test_results::reports_tests(&self.check.job);
+        let capture_stdout = !stream || self.evaluation.is_some() || reports_tests;
+        let tee = Tee {
+            stdout: stream && !capture_stdout,
+            stderr: stream,
+            prefixed: stream && console.concurrent,
+        };
+
+        let mut input = None;
+        if let Some(stdin) = self.check.job.stdin.as_deref() {
+            match stdin.strip_prefix(STDIN_FILE_PREFIX) {
+                Some(path) => {
+                    let path = self.command.current_dir.join(path);
+                    // A failure of this check only, the others still run with --keep-going
+                    let file = match std::fs::File::open(&path) {
+                        Ok(e) => e,
+                        Err(e) => {
+                            let message = format!(
+                                "check {} failed: cannot read stdin from '{}' ({e})",
+                                self.id,
+                                path.display()
+                            );
+                            eprintln!("{}", problem_matcher::failure_line(self, message.as_str()));
+                            return Err(Error::CheckFailed(self.id.clone()));
+                        }
+                    };
+                    command.stdin(file);
+                }
+                None => {
+                    input = Some(stdin.as_bytes().to_vec());
+                    command.stdin(Stdio::piped());
+                }
+            }
+        }
+
+        // Scratch files of one check must not interfere with other checks.
+        let tmp = self.check.isolated_tmp.then(|| {
+            output_directory
+                .join("tmp")
+                .join(file_name(self.id.as_str()))
+        });
+        if let Some(tmp) = tmp.as_ref() {
+            std::fs::create_dir_all(tmp.as_std_path())?;
+            for key in ["TMPDIR", "TMP", "TEMP"] {
+                command.env(key, tmp.as_str());
+            }
+        }
+
+        // Neither the global registry nor its cache are visible to the check, e.g. to verify an offline build.
+        // Outside of the target directory, which is often cached or uploaded by CI
+        let cargo_home = self.check.isolated_cargo_home.then(|| {
+            let name = format!(
+                "customs-{}-{}",
+                std::process::id(),
+                file_name(self.id.as_str())
+            );
+            Utf8PathBuf::from_path_buf(std::env::temp_dir().join(name))
+                .expect("the temporary directory is valid UTF-8")
+        });
+        let remove_scratch = || -> Result<()> {
+            for directory in tmp.iter().chain(cargo_home.iter()) {
+                std::fs::remove_dir_all(directory.as_std_path())?;
+            }
+            Ok(())
+        };
+        if let Some(cargo_home) = cargo_home.as_ref() {
+            command.env("CARGO_HOME", cargo_home.as_str());
+            match self.seed_cargo_home(cargo_home) {
+                Ok(tokens) => {
+                    command.envs(tokens);
+                }
+                Err(e) => {
+                    remove_scratch()?;
+                    return Err(e);
+                }
+            }
+        }
+
+        if let Some(shard) = self.shard
+            && let Some(names) = self.shard_tests(&command, shard)?
+        {
+            if names.is_empty() {
+                eprintln!("{}: no tests in shard {shard}", self.id);
+                remove_scratch()?;
+                return Ok(());
+            }
+            if !self.command.args.iter().any(|e| e == "--") {
+                command.arg("--");
+            }
+            command.args(names).arg("--exact");
+        }
+
+        let mut attempt = 0;
+        let captured = loop {
+            let captured = heartbeat::output(
+                &mut command,
+                self.id.as_str(),
+                console,
+                cancellation,
+                tee,
+                input.clone(),
+            );
+            let captured = match captured {
+                Ok(_) if cancellation.is_cancelled() => {
+                    remove_scratch()?;
+                    return Err(Error::Cancelled);
+                }
+                Ok(e) => e,
+                Err(e) => {
+                    remove_scratch()?;
+                    return Err(e.into());
+                }
+            };
+            // Registry and network hiccups are no failures of the check, unlike flaky tests
+            let output = &captured.output;
+            let retry = self.check.job.network
+                && !output.status.success()
+                && attempt < network::RETRIES
+                && network::detect(String::from_utf8_lossy(&output.stderr).as_ref());
+            if !retry {
+                break captured;
+            }
+            attempt += 1;
+            let backoff = network::backoff(attempt);
+            eprintln!(
+                "{}: network error, retrying in {} ({attempt}/{})",
+                self.id,
+                format_duration(backoff.as_secs_f64()),
+                network::RETRIES
+            );
+            std::thread::sleep(backoff);
+        };
+        let output = &captured.output;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if reports_tests {
+            tests.extend(test_results::parse(
+                stdout.as_ref(),
+                String::from_utf8_lossy(&output.stderr).as_ref(),
+            ));
+        }
+
+        // Non-zero, but declared a success by the job, e.g. a tool reporting findings
+        let notes = !output.status.success()
+            && output
+                .status
+                .code()
+                .is_some_and(|e| self.check.job.success_exit_codes.contains(&e));
+        let failure = if notes {
+            eprintln!("{}: passed with notes ({})", self.id, output.status);
+            None
+        } else if !output.status.success() {
+            // cargo-mutants fails on surviving mutants, the summary names how many
+            if self.evaluation == Some(Evaluation::Mutants) {
+                let summary = Evaluation::Mutants.evaluate(stdout.as_ref());
+                eprintln!("{}: {}", self.id, summary.unwrap_or_else(|e| e));
+            }
+            let retries = self.check.job.retry_failed_tests.unwrap_or(0);
+            if retries > 0 && self.retry_failed_tests(&command, retries, tests)? {
+                let flaky = tests
+                    .iter()
+                    .filter(|(_, outcome)| **outcome == TestOutcome::Flaky)
+                    .map(|(name, _)| name.as_str())
+                    .join(", ");
+                eprintln!("{}: flaky tests passed on retry: {flaky}", self.id);
+                None
+            } else {
+                Some(format!("check {} failed ({})", self.id, output.status))
+            }
+        } else if let Some(evaluation) = self.evaluation {
+            match evaluation.evaluate(stdout.as_ref()) {
+                Ok(summary) => {
+                    eprintln!("{}: {summary}", self.id);
+                    match evaluation {
+                        Evaluation::Coverage { ratchet: true, .. } => {
+                            self.ratchet_coverage(output_directory, stdout.as_ref())?
+                        }
+                        _ => None,
+                    }
+                }
+                Err(reason) => Some(format!("check {} failed: {reason}", self.id)),
+            }
+        } else {
+            None
+        };
+
+        remove_scratch()?;
+
+        // Separately for tools printing machine-readable data to stdout
+        for (stream, data) in [
+            (LogStream::Stdout, &output.stdout),
+            (LogStream::Stderr, &output.stderr),
+        ] {
+            let path = self.log_file(output_directory, stream);
+            match data.is_empty() {
+                false => {
+                    std::fs::create_dir_all(
+                        path.parent().expect("log file is in the log directory"),
+                    )?;
+                    std::fs::write(path, data)?
+                }
+                // Left over from an earlier run
+                true => std::fs::remove_file(path).or_else(|e| match e.kind() {
+                    std::io::ErrorKind::NotFound => Ok(()),
+                    _ => Err(e),
+                })?,
+            }
+        }
+
+        if !output.stdout.is_empty() || !output.stderr.is_empty() {
+            let log_file = self.log_file(output_directory, LogStream::Both);
+            std::fs::write(&log_file, captured.interleaved())?;
+
+            if (failure.is_some() || notes) && !stream {
+                let limit = self.check.failure_log_lines.unwrap_or(usize::MAX);
+                let stdout_lines = stdout.lines().count();
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                let stderr_lines = stderr.lines().count();
+
+                // The end of the output is where the error usually is.
+                let stderr_shown = stderr_lines.min(limit);
+                let stdout_shown = stdout_lines.min(limit - stderr_shown);
+                let omitted = stdout_lines + stderr_lines - stdout_shown - stderr_shown;
+                if omitted > 0 {
+                    eprintln!("... {omitted} lines omitted, see {log_file}");
+                }
+                for line in stdout.lines().skip(stdout_lines - stdout_shown) {
+                    println!("{line}");
+                }
+                for line in stderr.lines().skip(stderr_lines - stderr_shown) {
+                    eprintln!("{line}");
+                }
+            }
+        }
+
+        if stream && !tee.stdout {
+            // Only the output of evaluated and test checks was captured
+            std::io::stdout().write_all(&output.stdout)?;
+        }
+
+        if failure.is_some()
+            && (insta::detect(stdout.as_ref())
+                || insta::detect(&String::from_utf8_lossy(&output.stderr)))
+        {
+            insta::record(output_directory, self)?;
+        }
+
+        if let Some(message) = failure {
+            eprintln!("{}", problem_matcher::failure_line(self, message.as_str()));
+            return Err(Error::CheckFailed(self.id.clone()));
+        }
+
+        Ok(())
+    }
+
+    /// Where the captured output of the check is logged, one file per check and stream directly in `logs`.
+    pub fn log_file(&self, output_directory: &Utf8Path, stream: LogStream) -> Utf8PathBuf {
+        let extension = match stream {
+            LogStream::Both => "log",
+            LogStream::Stdout => "stdout.log",
+            LogStream::Stderr => "stderr.log",
+        };
+        output_directory
+            .join("logs")
+            .join(format!("{}.{extension}", file_name(self.id.as_str())))
+    }
+
+    /// Lists the tests of a `test` check and returns those of the shard,
+    /// `None` if they cannot be listed, e.g. because the build fails, which the check itself then reports.
+    fn shard_tests(&self, command: &Command, shard: Shard) -> Result<Option<Vec<String>>> {
+        let mut args = self.command.args.clone();
+        if !args.iter().any(|e| e == "--") {
+            args.push("--".into());
+        }
+        args.extend(["--list", "--format", "terse"].map(String::from));
+
+        let invocation = Invocation {
+            args,
+            ..self.command.clone()
+        };
+        let mut list = invocation.to_command();
+        // The same environment as the check, so the build is reused
+        for (key, value) in command.get_envs() {
+            if let Some(value) = value {
+                list.env(key, value);
+            }
+        }
+        let output = list
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output()?;
+        if !output.status.success() {
+            return Ok(None);
+        }
+
+        let names = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|e| e.strip_suffix(": test"))
+            .filter(|e| shard.contains(e))
+            .map(String::from)
+            .unique()
+            .collect();
+        Ok(Some(names))
+    }
+
+    /// The `--config` arguments of `--cargo-config` leading the cargo command line of the check.
+    fn cargo_config(&self) -> &[String] {
+        let pairs = self
+            .command
+            .args
+            .chunks(2)
+            .take_while(|e| e.len() == 2 && e[0] == "--config")
+            .count();
+        &self.command.args[..pairs * 2]
+    }
+
+    /// Creates the `CARGO_HOME` of the check with the configuration of the global one and downloads
+    /// the dependencies of the package into it.
+    ///
+    /// Credentials are not copied, the registry tokens to pass in the environment are returned instead.
+    fn seed_cargo_home(&self, cargo_home: &Utf8Path) -> Result<Vec<(String, String)>> {
+        std::fs::create_dir_all(cargo_home.as_std_path())?;
+
+        // Registries and mirrors still apply
+        let global = std::env::var_os("CARGO_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::home_dir().map(|e| e.join(".cargo")));
+        let mut tokens = Vec::new();
+        if let Some(global) = global {
+            for file in ["config.toml", "config"] {
+                let source = global.join(file);
+                if source.is_file() {
+                    std::fs::copy(&source, cargo_home.join(file))?;
+                }
+            }
+            for file in ["credentials.toml", "credentials"] {
+                if let Ok(credentials) = std::fs::read_to_string(global.join(file)) {
+                    tokens.extend(registry_tokens(credentials.as_str()));
+                }
+            }
+        }
+        // Set by the user, they already reach the check
+        tokens.retain(|(key, _)| std::env::var_os(key).is_none());
+
+        let mut fetch = Command::new("cargo");
+        fetch
+            .args(self.cargo_config())
+            .arg("fetch")
+            .envs(self.command.env.iter())
+            .env("CARGO_HOME", cargo_home.as_str())
+            .envs(tokens.iter().map(|(key, value)| (key, value)))
+            .current_dir(self.command.current_dir.as_path());
+        if self.check.locked {
+            fetch.arg("--locked");
+        }
+        let output = fetch.output()?;
+        if !output.status.success() {
+            eprintln!("{}", String::from_utf8_lossy(&output.stderr));
+            let message = format!(
+                "check {} failed: cargo fetch failed ({})",
+                self.id, output.status
+            );
+            eprintln!("{}", problem_matcher::failure_line(self, message.as_str()));
+            return Err(Error::CheckFailed(self.id.clone()));
+        }
+        Ok(tokens)
+    }
+
+    /// Fails if the line coverage dropped since the last run, otherwise raises the ratchet.
+    fn ratchet_coverage(
+        &self,
+        output_directory: &Utf8Path,
+        stdout: &str,
+    ) -> Result<Option<String>> {
+        let Ok(percent) = job::line_coverage(stdout) else {
+            return Ok(None);
+        };
+        let mut history = CoverageHistory::load(output_directory)?;
+        match history.get(self.id.as_str()) {
+            Some(previous) if percent < previous => Ok(Some(format!(
+                "check {} failed: line coverage dropped from {previous:.1}% to {percent:.1}%",
+                self.id
+            ))),
+            _ => {
+                history.record(self.id.as_str(), percent)?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Reruns only the failed libtest tests, marking those that pass as flaky.
+    ///
+    /// Returns whether all failed tests passed eventually, nextest retries by itself.
+    fn retry_failed_tests(
+        &self,
+        command: &Command,
+        retries: u8,
+        tests: &mut BTreeMap<String, TestOutcome>,
+    ) -> Result<bool> {
+        let failed = |tests: &BTreeMap<String, TestOutcome>| {
+            tests
+                .iter()
+                .filter(|(_, outcome)| **outcome == TestOutcome::Failed)
+                .map(|(name, _)| name.clone())
+                .collect::<Vec<_>>()
+        };
+
+        // Without failed tests, something else failed, e.g. the build.
+        if self.check.job.name != "test" || failed(tests).is_empty() {
+            return Ok(false);
+        }
+
+        for attempt in 1..=retries {
+            let names = failed(tests);
+            if names.is_empty() {
+                break;
+            }
+            log::info!(
+                "Retrying {} failed tests of {}, attempt {attempt}",
+                names.len(),
+                self.id
+            );
+
+            let mut args = self.command.args.clone();
+            if !args.iter().any(|e| e == "--") {
+                args.push("--".into());
+            }
+            args.extend(names.iter().cloned());
+            args.push("--exact".into());
+
+            let invocation = Invocation {
+                args,
+                ..self.command.clone()
+            };
+            let mut retry = invocation.to_command();
+            // The same environment as the first run, e.g. colors and the isolated temporary directory
+            for (key, value) in command.get_envs() {
+                if let Some(value) = value {
+                    retry.env(key, value);
+                }
+            }
+            let output = retry
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .output()?;
+            let retried = test_results::parse(
+                String::from_utf8_lossy(&output.stdout).as_ref(),
+                String::from_utf8_lossy(&output.stderr).as_ref(),
+            );
+            for name in names {
+                if retried.get(&name) == Some(&TestOutcome::Passed) {
+                    tests.insert(name, TestOutcome::Flaky);
+                }
+            }
+        }
+
+        Ok(failed(tests).is_empty())
+    }
+}
+
+pub fn build_plan(args: &Cli, metadata: &Metadata, files: &CustomsFiles) -> Result<Plan> {
+    let workspace = &args.workspace;
+    let (packages_to_check, _) = workspace.partition_packages(metadata);
+    let packages_to_check = order_by_dependencies(packages_to_check);
+
+    // The whole matrix runs in CI, local runs may be reduced.
+    let local = !args.full && args.ci().is_none();
+
+    let mut checks = Vec::new();
+    let mut seen = HashSet::new();
+    let mut linter = Linter::default();
+    let mut platform_targets = PlatformTargets::default();
+    let mut tools = if args.hermetic {
+        Some(Tools::resolve_toolchain()?)
+    } else {
+        None
+    };
+
+    let exclusions = Exclusions::new(metadata, files)?;
+    for package in packages_to_check.iter() {
+        let info = load_customs(package, metadata, &exclusions, files)?;
+
+        let (customs_file, mut info) = match info {
+            Some(e) => {
+                linter.lint_package(&e)?;
+                (e.path, e.customs)
+            }
+            None if args.implicit => (
+                Utf8PathBuf::from(config::IMPLICIT_CUSTOMS_PATH),
+                config::implicit_customs()?,
+            ),
+            None => {
+                // If customs was invoked to target a single package,
+                // then the user intent is to run a non-empty set of regulations.
+                // Hence, not finding a customs definition is probably user error.
+                if packages_to_check.len() == 1 && !workspace.workspace {
+                    return Err(Error::CustomsMissing);
+                } else {
+                    // If there are multiple packages, it is plausible
+                    // that not all have customs definitions, so a warning is sufficient.
+                    log::warn!("No customs file for {}", package.manifest_path);
+                    continue;
+                }
+            }
+        };
+
+        for regulation in info.regulation.iter_mut() {
+            args.overrides.apply(regulation)?;
+        }
+
+        let directory = package
+            .manifest_path
+            .parent()
+            .expect("Manifest must be in some directory");
+
+        if info.regulation.is_empty() {
+            return Err(Error::NoRegulations(
+                package.manifest_path.as_str().to_string(),
+            ));
+        }
+
+        let toolchain_file = find_toolchain_file(directory)?;
+        let boards = boards::find_boards_file(directory)?;
+        let launch_toolchain = launch_toolchain();
+        let mut reported_conflicts = HashSet::new();
+        let mut reported_left_out = HashSet::new();
+        let mut reported_host_only = HashSet::new();
+        let mut reported_runners = HashSet::new();
+
+        let regulations = info
+            .regulation
+            .into_iter()
+            .map(|e| boards::expand(e, boards.as_ref()))
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .map(|mut e| {
+                let toolchain = effective_toolchain(e.toolchain.as_ref(), toolchain_file.as_ref());
+                e.platform_targets =
+                    platform_targets.expand(&e.platform_targets, toolchain.as_deref())?;
+                if let Some(preset) = e.preset {
+                    e.platform_targets =
+                        preset.restrict_targets(e.platform_targets, &mut platform_targets)?;
+                }
+                platform_targets.warn_missing(&e.platform_targets, toolchain.as_deref());
+                Ok(e)
+            })
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .flat_map(|e| e.expand_with_skip_reasons(local))
+            .flat_map(|(check, skipped)| {
+                targets::split_runnable(package, check)
+                    .into_iter()
+                    .map(move |e| (e, skipped))
+            })
+            .collect::<Vec<_>>();
+
+        for (mut regulation, skipped) in regulations {
+            regulation.locked |= args.locked;
+            regulation.offline |= args.offline;
+            regulation.ignore_rust_version |= args.ignore_rust_version;
+
+            if targets::is_proc_macro(package) && regulation.platform_target != "host" {
+                if reported_host_only.insert(regulation.platform_target.clone()) {
+                    log::info!(
+                        "Checking proc-macro {} for host instead of {}",
+                        package.name,
+                        regulation.platform_target
+                    );
+                }
+                regulation.platform_target = "host".into();
+            }
+
+            let skipped = skipped.or_else(|| {
+                let build_target = regulation.build_target.as_str();
+                let pruned = targets::selected(package, build_target).is_empty()
+                    || (regulation.job.name == "run" && !targets::is_single(build_target));
+                (JobKind::of(&regulation.job).takes_build_target() && pruned)
+                    .then_some(SkipReason::Pruned)
+            });
+
+            if regulation.job.name == "run"
+                && skipped.is_none()
+                && regulation.platform_target != "host"
+                && !has_runner(&regulation)
+                && reported_runners.insert(regulation.platform_target.clone())
+            {
+                log::warn!(
+                    "No runner for {} known to customs, running binaries of {} may fail",
+                    regulation.platform_target,
+                    package.name
+                );
+            }
+
+            let left_out = targets::apply_required_features(package, &mut regulation);
+            if !left_out.is_empty()
+                && reported_left_out
+                    .insert((regulation.build_target.clone(), regulation.features.clone()))
+            {
+                log::warn!(
+                    "{} of {} are not checked for {} without the features they require",
+                    left_out.join(", "),
+                    package.name,
+                    regulation.build_target
+                );
+            }
+
+            // `cargo clean -p` removes the artifacts of all build targets and features at once
+            if JobKind::of(&regulation.job) == JobKind::Clean {
+                regulation.build_target = "all".into();
+                regulation.features.clear();
+                regulation.default_features = true;
+            }
+
+            let explicit_toolchain = regulation.toolchain.is_some();
+            if JobKind::of(&regulation.job) == JobKind::DocsRs {
+                docs_rs::apply(package, &mut regulation)?;
+            }
+
+            // The checks run with the toolchain of `cargo +toolchain customs` unless a regulation or its job says otherwise.
+            if regulation.toolchain.is_none() {
+                regulation.toolchain = launch_toolchain.clone();
+            }
+
+            // Overlapping regulations must not run the same check twice, whatever they are called.
+            let unlabeled = RegulationCheck {
+                regulation: None,
+                tags: Vec::new(),
+                ..regulation.clone()
+            };
+            if skipped.is_none() && !seen.insert((package.id.clone(), unlabeled)) {
+                continue;
+            }
+
+            let mut command = regulation.invocation(directory.as_std_path());
+            // Without a package, cargo clean removes the whole target directory
+            if JobKind::of(&regulation.job) == JobKind::Clean {
+                command
+                    .args
+                    .splice(1..1, ["-p".into(), package.name.to_string()]);
+            }
+            if args.bless && regulation.build_target.starts_with(crate::UI_TEST_PREFIX) {
+                command.env.insert("TRYBUILD".into(), "overwrite".into());
+            }
+            if let Some(tools) = tools.as_mut() {
+                command = tools.pin(command)?;
+            }
+
+            if let (Some(toolchain), Some(file)) = (&regulation.toolchain, &toolchain_file)
+                && explicit_toolchain
+                && *toolchain != file.channel
+                && reported_conflicts.insert(toolchain.clone())
+            {
+                log::warn!(
+                    "Regulation toolchain '{toolchain}' for {} overrides '{}' pinned by {}",
+                    package.name,
+                    file.channel,
+                    file.path
+                );
+            }
+
+            let evaluation = JobKind::of(&regulation.job).evaluation(&regulation.job);
+            let effective_toolchain =
+                effective_toolchain(regulation.toolchain.as_ref(), toolchain_file.as_ref());
+
+            checks.push(PlannedCheck {
+                id: check_id(package, &regulation),
+                package: package.name.to_string(),
+                manifest_path: package.manifest_path.clone(),
+                customs_file: customs_file.clone(),
+                check: regulation,
+                command,
+                effective_toolchain,
+                evaluation,
+                skipped,
+                predicted_duration: None,
+                shard: None,
+            });
+        }
+    }
+
+    linter.finish()?;
+    disambiguate_ids(&mut checks);
+    export_check_env(&mut checks);
+
+    // After disambiguating, so checks keep the IDs they have in a full run
+    if let Some(name) = args.regulation.iter().find(|name| {
+        !checks
+            .iter()
+            .any(|e| e.check.regulation.as_ref() == Some(*name))
+    }) {
+        return Err(Error::UnknownRegulation(name.clone()));
+    }
+    if !args.regulation.is_empty() {
+        checks.retain(|e| {
+            e.check
+                .regulation
+                .as_ref()
+                .is_some_and(|name| args.regulation.contains(name))
+        });
+    }
+    if let Some(crate::Command::Suite { name }) = args.command.as_ref() {
+        let suite = config::workspace_suite(metadata, files, name.as_str())?;
+        checks.retain(|e| suite.contains(&e.check));
+    }
+
+    let is_cargo = |e: &PlannedCheck| {
+        Path::new(e.command.program.as_str()).file_stem() == Some("cargo".as_ref())
+    };
+
+    // An explicit CARGO_BUILD_JOBS is passed on as is
+    if let Some(jobs) = config::workspace_parallelism(metadata, files)?
+        && std::env::var_os("CARGO_BUILD_JOBS").is_none()
+    {
+        for check in checks.iter_mut().filter(|e| is_cargo(e)) {
+            check
+                .command
+                .env
+                .insert("CARGO_BUILD_JOBS".into(), jobs.to_string());
+        }
+    }
+
+    if let Some(shard) = args.shard {
+        shard::apply(&mut checks, shard);
+    }
+
+    // Before the subcommand, where external subcommands like fmt cannot mistake it for an option of their own
+    if !args.cargo_config.is_empty() {
+        for check in &mut checks {
+            let kind = JobKind::of(&check.check.job);
+            let flag = match kind {
+                _ if is_cargo(check) => "--config",
+                // Run by customs itself, which passes it on to the cargo invocations
+                JobKind::Canary | JobKind::Reproducible => "--cargo-config",
+                _ if kind.program() == "cargo" => {
+                    return Err(Error::CargoConfigUnforwardable(check.id.clone()));
+                }
+                _ => continue,
+            };
+            let config = args
+                .cargo_config
+                .iter()
+                .flat_map(|e| [flag.to_string(), e.clone()]);
+            check.command.args.splice(0..0, config);
+        }
+    }
+
+    let mut plan = Plan {
+        tools,
+        checks,
+        predicted_duration: None,
+        output_directory: metadata.target_directory.join("customs"),
+    };
+    plan.predict_durations()?;
+    if let Some(budget) = args.budget {
+        budget::apply(&mut plan, budget, &metadata.workspace_root, &exclusions)?;
+    }
+    Ok(plan)
+}
+
+/// Whether a runner for the platform target is set by the regulation, a board or the environment.
+///
+/// Runners in cargo config files are not known, hence this is only a hint.
+fn has_runner(check: &RegulationCheck) -> bool {
+    check.runner.is_some()
+        || check.config_file.is_some()
+        || std::env::var_os(check.runner_variable()).is_some()
+}
+
+/// A check ID usable as a single file or directory name.
+pub fn file_name(id: &str) -> String {
+    id.chars()
+        .map(
+            |e| match e.is_ascii_alphanumeric() || matches!(e, '-' | '_' | '.') {
+                true => e,
+                false => '_',
+            },
+        )
+        .collect()
+}
+
+/// The registry tokens of cargo credentials, as the environment variables cargo reads them from.
+fn registry_tokens(credentials: &str) -> Vec<(String, String)> {
+    let Ok(credentials) = toml::from_str::<toml::Table>(credentials) else {
+        return Vec::new();
+    };
+    let token = |e: &toml::Value| Some(e.get("token")?.as_str()?.to_string());
+
+    let mut tokens = Vec::new();
+    if let Some(token) = credentials.get("registry").and_then(token) {
+        tokens.push(("CARGO_REGISTRY_TOKEN".into(), token));
+    }
+    if let Some(registries) = credentials
+        .get("registries")
+        .and_then(toml::Value::as_table)
+    {
+        for (name, registry) in registries {
+            if let Some(token) = token(registry) {
+                let name = name.to_uppercase().replace('-', "_");
+                tokens.push((format!("CARGO_REGISTRIES_{name}_TOKEN"), token));
+            }
+        }
+    }
+    tokens
+}
+
+/// Same precedence as rustup: explicit override, command line or environment, toolchain file
+fn effective_toolchain(
+    toolchain: Option<&String>,
+    toolchain_file: Option<&ToolchainFile>,
+) -> Option<String> {
+    toolchain
+        .cloned()
+        .or_else(launch_toolchain)
+        .or_else(|| toolchain_file.map(|e| e.channel.clone()))
+}
+
+fn check_id(package: &Package, check: &RegulationCheck) -> String {
+    let mut id = format!(
+        "{}/{}/{}/{}",
+        package.name, check.job.name, check.platform_target, check.build_target
+    );
+    if !check.features.is_empty() {
+        id.push('/');
+        id.push_str(check.features.join("+").as_str());
+    }
+    if let Some(preset) = check.preset {
+        id.push('/');
+        id.push_str(preset.name());
+    }
+    if !check.cfgs.is_empty() {
+        id.push_str("/cfg=");
+        id.push_str(check.cfgs.join("+").as_str());
+    }
+    if let Some(board) = check.board.as_ref() {
+        id.push_str("/board=");
+        id.push_str(board);
+    }
+    id
+}
+
+/// Checks only differing in job arguments share the same readable ID,
+/// those are suffixed with a counter.
+///
+/// Checks only differing in the default features are marked instead, e.g. for
+/// `feature-sets = ["default", "--no-default-features"]`.
+fn disambiguate_ids(checks: &mut [PlannedCheck]) {
+    let with_default_features = checks
+        .iter()
+        .filter(|e| e.check.default_features)
+        .map(|e| e.id.clone())
+        .collect::<HashSet<_>>();
+    for check in checks.iter_mut() {
+        if !check.check.default_features && with_default_features.contains(&check.id) {
+            check.id.push_str("/no-default-features");
+        }
+    }
+
+    let mut occurrences = BTreeMap::<String, usize>::new();
+    for check in checks.iter_mut() {
+        let count = occurrences.entry(check.id.clone()).or_default();
+        *count += 1;
+        if *count > 1 {
+            check.id = format!("{}#{}", check.id, count);
+        }
+    }
+}
+
+/// A hash that is stable across platforms and Rust versions, unlike [`std::hash::DefaultHasher`].
+pub(crate) struct Fnv1a(pub u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Fnv1a {
+    pub fn write(&mut self, value: &str) {
+        // Terminated to tell apart e.g. ["ab", "c"] and ["a", "bc"]
+        for byte in value.bytes().chain(std::iter::once(0)) {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+/// Describes the check to the spawned processes,
+/// so scripts and tests can adapt to the matrix cell they are running in.
+fn export_check_env(checks: &mut [PlannedCheck]) {
+    for check in checks.iter_mut() {
+        let env = [
+            ("CUSTOMS_PACKAGE", check.package.clone()),
+            ("CUSTOMS_TARGET", check.check.platform_target.clone()),
+            ("CUSTOMS_BUILD_TARGET", check.check.build_target.clone()),
+            ("CUSTOMS_JOB", check.check.job.name.clone()),
+            ("CUSTOMS_CHECK_ID", check.id.clone()),
+        ];
+        check
+            .command
+            .env
+            .extend(env.into_iter().map(|(key, value)| (key.to_string(), value)));
+    }
+}
+
+/// Orders packages such that workspace dependencies come before their dependents.
+/// Otherwise, the selection order is preserved.
+fn order_by_dependencies(packages: Vec<&Package>) -> Vec<&Package> {
+    let mut remaining = packages;
+    let mut ordered = Vec::with_capacity(remaining.len());
+
+    while !remaining.is_empty() {
+        let next = remaining
+            .iter()
+            .position(|package| {
+                !package.dependencies.iter().any(|dependency| {
+                    remaining
+                        .iter()
+                        .any(|other| other.id != package.id && *other.name == dependency.name)
+                })
+            })
+            // Cycles are only possible through dev-dependencies,
+            // those are broken up in selection order.
+            .unwrap_or(0);
+        ordered.push(remaining.remove(next));
+    }
+
+    ordered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_name_of_check_id_has_no_separators() {
+        assert_eq!(
+            file_name("foo/test/host/ui-test:compile#2"),
+            "foo_test_host_ui-test_compile_2"
+        );
+    }
+
+    #[test]
+    fn registry_tokens_are_passed_as_environment() {
+        let credentials = r#"
+            [registry]
+            token = "crates-io"
+
+            [registries.my-registry]
+            token = "mine"
+
+            [registries.tokenless]
+            credential-provider = "cargo:token"
+        "#;
+        assert_eq!(
+            registry_tokens(credentials),
+            [
+                ("CARGO_REGISTRY_TOKEN".to_string(), "crates-io".to_string()),
+                (
+                    "CARGO_REGISTRIES_MY_REGISTRY_TOKEN".to_string(),
+                    "mine".to_string()
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use std::collections::BTreeMap;
+
+use cargo_metadata::MetadataCommand;
+use cargo_metadata::camino::Utf8Path;
+
+use crate::config::CustomsFiles;
+use crate::git::git;
+use crate::plan::{Plan, build_plan};
+use crate::{Cli, Error, Result};
+
+/// Prints the checks added, removed or changed compared to the plan at `revision`.
+pub fn plan_diff(args: &Cli, plan: &Plan, workspace_root: &Utf8Path, revision: &str) -> Result<()> {
+    let repository = git(workspace_root, &["rev-parse", "--show-toplevel"])?;
+    let worktree = plan.output_directory.join("plan-diff");
+    if std::fs::exists(worktree.as_std_path())? {
+        git(
+            workspace_root,
+            &["worktree", "remove", "--force", worktree.as_str()],
+        )?;
+    }
+    git(
+        workspace_root,
+        &["worktree", "add", "--detach", worktree.as_str(), revision],
+    )?;
+
+    let old_plan = plan_in_worktree(args, &repository, &worktree);
+    git(
+        workspace_root,
+        &["worktree", "remove", "--force", worktree.as_str()],
+    )?;
+    let old_plan = old_plan?;
+
+    let old = comparable_checks(&old_plan, worktree.as_str());
+    let new = comparable_checks(plan, repository.as_str());
+
+    for (id, check) in new.iter() {
+        match old.get(id) {
+            None => println!("+ {id}"),
+            Some(old_check) if old_check != check => println!("~ {id}"),
+            Some(_) => {}
+        }
+    }
+    for id in old.keys().filter(|e| !new.contains_key(*e)) {
+        println!("- {id}");
+    }
+
+    Ok(())
+}
+
+fn plan_in_worktree(args: &Cli, repository: &str, worktree: &Utf8Path) -> Result<Plan> {
+    // Plan from the same directory as the current plan
+    let current_dir = std::env::current_dir()?;
+    let relative = current_dir.strip_prefix(repository).unwrap_or(&current_dir);
+
+    let metadata = MetadataCommand::new()
+        .current_dir(worktree.as_std_path().join(relative))
+        .exec()
+        .map_err(|e| match e {
+            cargo_metadata::Error::CargoMetadata { stderr } => Error::Cargo(stderr),
+            _ => Error::Unexpected(e.into()),
+        })?;
+
+    // Another checkout, whose customs files may differ
+    build_plan(args, &metadata, &CustomsFiles::default())
+}
+
+/// The checks by ID, serialized with the location of the checkout removed.
+///
+/// Only what the check does is compared, predictions and skips depend on the state of the target directory.
+fn comparable_checks(plan: &Plan, root: &str) -> BTreeMap<String, String> {
+    plan.checks
+        .iter()
+        .map(|e| {
+            let json =
+                serde_json::to_string(&(&e.check, &e.command)).expect("checks are serializable");
+            (e.id.clone(), json.replace(root, ""))
+        })
+        .collect()
+}
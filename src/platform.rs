@@ -0,0 +1,170 @@
+use std::collections::{HashMap, HashSet};
+use std::process::Command;
+
+use itertools::Itertools;
+
+use crate::{Error, Result};
+
+// Platform target groups usable in `platform-targets` next to plain target triples.
+// `host` is not a group, it is kept as is and resolved when invoking cargo.
+const HOST_PLATFORM_DESIGNATOR: &str = "host";
+const TIER1_DESIGNATOR: &str = "tier1";
+const WASM_DESIGNATOR: &str = "wasm";
+const INSTALLED_DESIGNATOR: &str = "installed";
+/// Everything that can be built right now, hence the same as installed.
+const ALL_DESIGNATOR: &str = "all";
+
+/// Tier 1 targets with host tools as of Rust 1.91.
+const TIER1_TARGETS: &[&str] = &[
+    "aarch64-apple-darwin",
+    "aarch64-pc-windows-msvc",
+    "aarch64-unknown-linux-gnu",
+    "i686-pc-windows-msvc",
+    "i686-unknown-linux-gnu",
+    "x86_64-pc-windows-gnu",
+    "x86_64-pc-windows-msvc",
+    "x86_64-unknown-linux-gnu",
+];
+
+const WASM_TARGETS: &[&str] = &["wasm32-unknown-unknown", "wasm32-wasip1", "wasm32-wasip2"];
+
+/// Expands platform target groups and checks targets against rustup.
+///
+/// Rustup is queried once per toolchain, `None` being the toolchain rustup picks by default.
+#[derive(Debug, Default)]
+pub struct PlatformTargets {
+    installed: HashMap<Option<String>, std::result::Result<Vec<String>, String>>,
+    reported_missing: HashSet<(Option<String>, String)>,
+    host: Option<String>,
+}
+
+impl PlatformTargets {
+    pub fn expand(&mut self, targets: &[String], toolchain: Option<&str>) -> Result<Vec<String>> {
+        let mut expanded = Vec::new();
+        for target in targets {
+            match target.as_str() {
+                TIER1_DESIGNATOR => expanded.extend(TIER1_TARGETS.iter().map(|e| e.to_string())),
+                WASM_DESIGNATOR => expanded.extend(WASM_TARGETS.iter().map(|e| e.to_string())),
+                INSTALLED_DESIGNATOR | ALL_DESIGNATOR => {
+                    expanded.extend(self.installed(toolchain).clone().map_err(Error::Rustup)?)
+                }
+                _ => expanded.push(target.clone()),
+            }
+        }
+
+        Ok(expanded.into_iter().unique().collect())
+    }
+
+    /// Warns about targets that are not installed, before any cargo process fails on them.
+    pub fn warn_missing(&mut self, targets: &[String], toolchain: Option<&str>) {
+        // custom target specifications are not managed by rustup
+        let targets = targets
+            .iter()
+            .filter(|e| *e != HOST_PLATFORM_DESIGNATOR && !e.ends_with(".json"))
+            .collect::<Vec<_>>();
+        // Without anything to check, rustup is not even asked
+        if targets.is_empty() {
+            return;
+        }
+
+        let installed = match self.installed(toolchain) {
+            Ok(e) => e.clone(),
+            Err(e) => {
+                // rustup is not required, e.g. with toolchains managed by Nix
+                log::debug!("Cannot check installed targets: {e}");
+                return;
+            }
+        };
+
+        for target in targets {
+            if !installed.contains(target)
+                && self
+                    .reported_missing
+                    .insert((toolchain.map(String::from), target.clone()))
+            {
+                log::warn!(
+                    "Target '{target}' is not installed for toolchain '{}', try `rustup target add {target}`",
+                    toolchain.unwrap_or("default")
+                );
+            }
+        }
+    }
+
+    /// Replaces `host` with the explicit target triple of the host.
+    pub fn resolve_host(&mut self, target: String) -> Result<String> {
+        if target != HOST_PLATFORM_DESIGNATOR {
+            return Ok(target);
+        }
+
+        if self.host.is_none() {
+            let version = rustc_version::version_meta().map_err(anyhow::Error::from)?;
+            self.host = Some(version.host);
+        }
+
+        Ok(self.host.clone().expect("populated above"))
+    }
+
+    fn installed(&mut self, toolchain: Option<&str>) -> &std::result::Result<Vec<String>, String> {
+        self.installed
+            .entry(toolchain.map(String::from))
+            .or_insert_with(|| query_installed_targets(toolchain))
+    }
+}
+
+fn query_installed_targets(toolchain: Option<&str>) -> std::result::Result<Vec<String>, String> {
+    let mut command = Command::new("rustup");
+    command.args(["target", "list", "--installed"]);
+    if let Some(toolchain) = toolchain {
+        command.args(["--toolchain", toolchain]);
+    }
+
+    let output = command.output().map_err(|e| e.to_string())?;
+
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).into_owned());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .filter(|e| !e.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expand_replaces_groups_and_drops_duplicates() -> Result<()> {
+        let mut platform_targets = PlatformTargets::default();
+        // As if rustup was asked already
+        platform_targets.installed.insert(
+            None,
+            Ok(vec![
+                "x86_64-unknown-linux-gnu".into(),
+                "wasm32-wasip1".into(),
+            ]),
+        );
+
+        let targets =
+            ["wasm", "installed", "host", "thumbv7em-none-eabihf", "all"].map(String::from);
+        assert_eq!(
+            platform_targets.expand(&targets, None)?,
+            [
+                "wasm32-unknown-unknown",
+                "wasm32-wasip1",
+                "wasm32-wasip2",
+                "x86_64-unknown-linux-gnu",
+                "host",
+                "thumbv7em-none-eabihf",
+            ]
+        );
+        assert_eq!(
+            platform_targets.expand(&["tier1".into()], None)?,
+            TIER1_TARGETS
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,287 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Result;
+use crate::config::{CustomsFile, Regulation, parse_customs_toml};
+use crate::platform::PlatformTargets;
+
+/// Built-in regulations for checks that are easy to get wrong by hand.
+///
+/// A preset fills in all fields the regulation does not set itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Preset {
+    /// Tests with AddressSanitizer.
+    Asan,
+    /// Tests with ThreadSanitizer.
+    Tsan,
+    /// Concurrency tests with loom, see https://docs.rs/loom.
+    Loom,
+    /// Verifies the library builds without std, on a target that has no std at all.
+    NoStd,
+    /// Builds twice with locked dependencies and compares the artifacts bit for bit.
+    Reproducible,
+}
+
+// Sanitizers require an explicit target, otherwise the flags apply to build scripts and proc-macros too.
+const ASAN: &str = r#"
+toolchain = "nightly"
+platform-targets = ["host"]
+build-targets = ["all"]
+jobs = ["test"]
+rustflags = ["-Zsanitizer=address"]
+"#;
+
+const TSAN: &str = r#"
+toolchain = "nightly"
+platform-targets = ["host"]
+build-targets = ["all"]
+jobs = ["test"]
+rustflags = ["-Zsanitizer=thread"]
+"#;
+
+const LOOM: &str = r#"
+platform-targets = ["host"]
+build-targets = ["all"]
+jobs = ["test"]
+cfgs = ["loom"]
+"#;
+
+const NO_STD: &str = r#"
+platform-targets = ["thumbv6m-none-eabi"]
+build-targets = ["lib"]
+jobs = ["check"]
+default-features = false
+"#;
+
+const REPRODUCIBLE: &str = r#"
+platform-targets = ["host"]
+build-targets = ["lib", "bins"]
+jobs = ["reproducible"]
+locked = true
+"#;
+
+/// See https://doc.rust-lang.org/beta/unstable-book/compiler-flags/sanitizer.html
+const ASAN_TARGETS: &[&str] = &[
+    "aarch64-apple-darwin",
+    "aarch64-unknown-fuchsia",
+    "aarch64-unknown-linux-gnu",
+    "x86_64-apple-darwin",
+    "x86_64-unknown-fuchsia",
+    "x86_64-unknown-freebsd",
+    "x86_64-unknown-linux-gnu",
+];
+
+const TSAN_TARGETS: &[&str] = &[
+    "aarch64-apple-darwin",
+    "aarch64-unknown-linux-gnu",
+    "x86_64-apple-darwin",
+    "x86_64-unknown-freebsd",
+    "x86_64-unknown-linux-gnu",
+];
+
+/// Built-in sets of regulations, selected for a whole customs file with `presets = [..]`.
+///
+/// Each regulation is named `<set>/<part>`, a regulation of the same name in the file replaces it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PresetSet {
+    /// Formatting, warning-free clippy, tests and docs on the host.
+    LibQuality,
+    /// Checks and lints the library without default features on common bare-metal targets,
+    /// tests on the host.
+    EmbeddedMatrix,
+    /// Everything crates.io and docs.rs expect of a release.
+    PublishReady,
+}
+
+const LIB_QUALITY: &str = r#"
+[[regulation]]
+name = "lib-quality/lints"
+platform-targets = ["host"]
+build-targets = ["all"]
+jobs = { fmt = {}, clippy = { args = ["-D", "warnings"] } }
+
+[[regulation]]
+name = "lib-quality/tests"
+platform-targets = ["host"]
+build-targets = ["all"]
+jobs = ["test"]
+
+[[regulation]]
+name = "lib-quality/docs"
+platform-targets = ["host"]
+build-targets = ["lib"]
+jobs = ["doc"]
+"#;
+
+const EMBEDDED_MATRIX: &str = r#"
+[[regulation]]
+name = "embedded-matrix/targets"
+platform-targets = ["thumbv6m-none-eabi", "thumbv7em-none-eabihf", "riscv32imac-unknown-none-elf"]
+build-targets = ["lib"]
+default-features = false
+jobs = { check = {}, clippy = { args = ["-D", "warnings"] } }
+
+[[regulation]]
+name = "embedded-matrix/host"
+platform-targets = ["host"]
+build-targets = ["all"]
+jobs = ["test"]
+"#;
+
+const PUBLISH_READY: &str = r#"
+[[regulation]]
+name = "publish-ready/manifest"
+platform-targets = ["host"]
+build-targets = ["lib"]
+jobs = ["manifest-policy", "hygiene"]
+
+[[regulation]]
+name = "publish-ready/api"
+platform-targets = ["host"]
+build-targets = ["lib"]
+locked = true
+jobs = ["semver-checks", "docs-rs"]
+"#;
+
+impl PresetSet {
+    pub fn name(self) -> &'static str {
+        match self {
+            PresetSet::LibQuality => "lib-quality",
+            PresetSet::EmbeddedMatrix => "embedded-matrix",
+            PresetSet::PublishReady => "publish-ready",
+        }
+    }
+
+    pub(crate) fn regulations(self) -> Result<Vec<Regulation>> {
+        let definition = match self {
+            PresetSet::LibQuality => LIB_QUALITY,
+            PresetSet::EmbeddedMatrix => EMBEDDED_MATRIX,
+            PresetSet::PublishReady => PUBLISH_READY,
+        };
+        let file: CustomsFile =
+            parse_customs_toml(format!("<presets {}>", self.name()).as_ref(), definition)?;
+        Ok(file.regulation)
+    }
+}
+
+impl Preset {
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::Asan => "asan",
+            Preset::Tsan => "tsan",
+            Preset::Loom => "loom",
+            Preset::NoStd => "no-std",
+            Preset::Reproducible => "reproducible",
+        }
+    }
+
+    pub(crate) fn regulation(self) -> Result<Regulation> {
+        let definition = match self {
+            Preset::Asan => ASAN,
+            Preset::Tsan => TSAN,
+            Preset::Loom => LOOM,
+            Preset::NoStd => NO_STD,
+            Preset::Reproducible => REPRODUCIBLE,
+        };
+        parse_customs_toml(format!("<preset {}>", self.name()).as_ref(), definition)
+    }
+
+    fn supported_targets(self) -> Option<&'static [&'static str]> {
+        match self {
+            Preset::Asan => Some(ASAN_TARGETS),
+            Preset::Tsan => Some(TSAN_TARGETS),
+            Preset::Loom | Preset::NoStd | Preset::Reproducible => None,
+        }
+    }
+
+    /// Resolves `host` to the explicit target and drops targets the preset does not support.
+    pub(crate) fn restrict_targets(
+        self,
+        targets: Vec<String>,
+        platform_targets: &mut PlatformTargets,
+    ) -> Result<Vec<String>> {
+        let Some(supported) = self.supported_targets() else {
+            return Ok(targets);
+        };
+
+        let mut restricted = Vec::new();
+        for target in targets {
+            let target = platform_targets.resolve_host(target)?;
+            if supported.contains(&target.as_str()) {
+                restricted.push(target);
+            } else {
+                log::warn!(
+                    "Preset {} is not supported on target '{target}', skipping it",
+                    self.name()
+                );
+            }
+        }
+
+        Ok(restricted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitizer_presets_run_on_nightly_and_supported_targets_only() -> Result<()> {
+        for (preset, flag) in [
+            (Preset::Asan, "-Zsanitizer=address"),
+            (Preset::Tsan, "-Zsanitizer=thread"),
+        ] {
+            let regulation = preset.regulation()?;
+            assert_eq!(regulation.toolchain.as_deref(), Some("nightly"));
+            assert_eq!(regulation.rustflags, [flag]);
+        }
+
+        let targets = [
+            "x86_64-unknown-linux-gnu",
+            "thumbv7em-none-eabihf",
+            "aarch64-unknown-fuchsia",
+        ]
+        .map(String::from)
+        .to_vec();
+        let mut platform_targets = PlatformTargets::default();
+        assert_eq!(
+            Preset::Asan.restrict_targets(targets.clone(), &mut platform_targets)?,
+            ["x86_64-unknown-linux-gnu", "aarch64-unknown-fuchsia"]
+        );
+        assert_eq!(
+            Preset::Tsan.restrict_targets(targets.clone(), &mut platform_targets)?,
+            ["x86_64-unknown-linux-gnu"]
+        );
+        assert_eq!(
+            Preset::Loom.restrict_targets(targets.clone(), &mut platform_targets)?,
+            targets
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn loom_preset_sets_cfg() -> Result<()> {
+        let regulation = Preset::Loom.regulation()?;
+        assert_eq!(regulation.cfgs, ["loom"]);
+        assert!(regulation.toolchain.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn preset_sets_name_their_regulations_after_the_set() -> Result<()> {
+        for set in [
+            PresetSet::LibQuality,
+            PresetSet::EmbeddedMatrix,
+            PresetSet::PublishReady,
+        ] {
+            let regulations = set.regulations()?;
+            assert!(!regulations.is_empty());
+            for regulation in regulations {
+                let name = regulation.name.expect("preset regulations are named");
+                assert!(name.starts_with(&format!("{}/", set.name())), "{name}");
+            }
+        }
+        Ok(())
+    }
+}
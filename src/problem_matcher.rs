@@ -0,0 +1,30 @@
+use std::path::Path;
+
+use crate::Result;
+use crate::plan::PlannedCheck;
+
+/// Matches the lines of [`failure_line`] for GitHub Actions.
+pub const PROBLEM_MATCHER: &str = include_str!("problem-matcher.json");
+
+/// Formats a failure as a single line, this format is considered stable:
+///
+/// `customs: error: <customs file>: <platform target> <job>: <message>`
+pub fn failure_line(check: &PlannedCheck, message: &str) -> String {
+    format!(
+        "customs: error: {}: {} {}: {message}",
+        check.customs_file, check.check.platform_target, check.check.job.name
+    )
+}
+
+/// Writes the problem matcher to `path` and registers it with GitHub Actions.
+pub fn install(path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, PROBLEM_MATCHER)?;
+
+    // GitHub Actions workflow command, see
+    // https://github.com/actions/toolkit/blob/main/docs/problem-matchers.md
+    println!("::add-matcher::{}", path.display());
+    Ok(())
+}
@@ -0,0 +1,98 @@
+use std::path::Path;
+
+use cargo_metadata::camino::Utf8Path;
+
+use crate::Result;
+use crate::plan::{Invocation, PlannedCheck};
+
+const SHELL_SCRIPT: &str = "replay-failed.sh";
+const POWERSHELL_SCRIPT: &str = "replay-failed.ps1";
+
+/// Writes scripts reproducing the failed checks of a run to `directory`,
+/// without failures, stale scripts of a previous run are removed.
+pub fn write(directory: &Utf8Path, failed: &[&PlannedCheck]) -> Result<()> {
+    let shell = directory.join(SHELL_SCRIPT);
+    let powershell = directory.join(POWERSHELL_SCRIPT);
+
+    if failed.is_empty() {
+        for path in [shell, powershell] {
+            if std::fs::exists(path.as_std_path())? {
+                std::fs::remove_file(path.as_std_path())?;
+            }
+        }
+        return Ok(());
+    }
+
+    std::fs::create_dir_all(directory.as_std_path())?;
+    std::fs::write(shell.as_std_path(), shell_script(failed))?;
+    std::fs::write(powershell.as_std_path(), powershell_script(failed))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(shell.as_std_path(), std::fs::Permissions::from_mode(0o755))?;
+    }
+
+    log::info!("Commands of failed checks written to {shell}");
+    Ok(())
+}
+
+fn shell_script(failed: &[&PlannedCheck]) -> String {
+    let quote = |e: &str| format!("'{}'", e.replace('\'', r"'\''"));
+
+    let mut script = String::from("#!/bin/sh\nset -e\n");
+    for check in failed {
+        let Invocation {
+            program,
+            args,
+            env,
+            current_dir,
+        } = &check.command;
+
+        script.push_str(format!("\n# {}\n", check.id).as_str());
+        script.push_str(format!("(cd {} && env", quote(path(current_dir).as_str())).as_str());
+        for (key, value) in env {
+            script.push_str(format!(" {key}={}", quote(value)).as_str());
+        }
+        for arg in std::iter::once(program).chain(args) {
+            script.push(' ');
+            script.push_str(quote(arg).as_str());
+        }
+        script.push_str(")\n");
+    }
+    script
+}
+
+fn powershell_script(failed: &[&PlannedCheck]) -> String {
+    let quote = |e: &str| format!("'{}'", e.replace('\'', "''"));
+
+    let mut script = String::from("$ErrorActionPreference = 'Stop'\n");
+    for check in failed {
+        let Invocation {
+            program,
+            args,
+            env,
+            current_dir,
+        } = &check.command;
+
+        script.push_str(format!("\n# {}\n", check.id).as_str());
+        script.push_str(format!("Push-Location {}\n", quote(path(current_dir).as_str())).as_str());
+        for (key, value) in env {
+            script.push_str(format!("$env:{key} = {}\n", quote(value)).as_str());
+        }
+        script.push('&');
+        for arg in std::iter::once(program).chain(args) {
+            script.push(' ');
+            script.push_str(quote(arg).as_str());
+        }
+        script.push('\n');
+        for key in env.keys() {
+            script.push_str(format!("Remove-Item Env:{key}\n").as_str());
+        }
+        script.push_str("Pop-Location\n");
+    }
+    script
+}
+
+fn path(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
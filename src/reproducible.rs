@@ -0,0 +1,119 @@
+use std::collections::BTreeSet;
+use std::process::{Command, Stdio};
+
+use cargo_metadata::camino::{Utf8Path, Utf8PathBuf};
+use cargo_metadata::{Message, Metadata};
+
+use crate::{Error, Result, config};
+
+/// Options of the built-in `reproducible` job.
+#[derive(Debug, Clone, clap::Args)]
+pub struct Options {
+    /// Text removed from the artifacts before comparing them, e.g. an embedded build host name.
+    #[arg(long)]
+    normalize: Vec<String>,
+
+    /// Arguments of `cargo build`.
+    #[arg(last = true)]
+    cargo_args: Vec<String>,
+}
+
+/// Builds twice from scratch and compares the artifacts bit for bit.
+///
+/// Both builds use the same target directory one after the other,
+/// so paths embedded in the artifacts do not differ between them.
+/// Each package has its own, so checks of different packages may run at the same time.
+/// `cargo_config` is passed as `--config` to both builds.
+pub fn check(metadata: &Metadata, options: &Options, cargo_config: &[String]) -> Result<()> {
+    let package = config::package_at(metadata, std::env::current_dir()?.as_path())
+        .map_or("workspace", |e| e.name.as_str());
+    let directory = metadata
+        .target_directory
+        .join("customs")
+        .join("reproducible")
+        .join(package);
+    let first = directory.join("first");
+    let second = directory.join("second");
+    remove(&directory)?;
+
+    let artifacts = build(&second, options, cargo_config)?;
+    std::fs::rename(second.as_std_path(), first.as_std_path())?;
+    build(&second, options, cargo_config)?;
+
+    let mut nondeterministic = 0;
+    for artifact in artifacts {
+        let relative = artifact
+            .strip_prefix(&second)
+            .expect("artifacts are in the target directory");
+        let a = normalize(std::fs::read(first.join(relative))?, options);
+        let b = normalize(std::fs::read(second.join(relative))?, options);
+        if a != b {
+            println!("nondeterministic: {relative}");
+            nondeterministic += 1;
+        }
+    }
+    remove(&directory)?;
+
+    if nondeterministic > 0 {
+        return Err(Error::CheckFailed(format!(
+            "reproducible ({nondeterministic} nondeterministic artifacts)"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Builds into `target_directory` and returns the artifacts built.
+fn build(
+    target_directory: &Utf8Path,
+    options: &Options,
+    cargo_config: &[String],
+) -> Result<Vec<Utf8PathBuf>> {
+    let output = Command::new("cargo")
+        .args(cargo_config.iter().flat_map(|e| ["--config", e.as_str()]))
+        .arg("build")
+        .args(options.cargo_args.iter())
+        .arg("--message-format=json-render-diagnostics")
+        .env("CARGO_TARGET_DIR", target_directory)
+        // Incremental compilation embeds session specific data
+        .env("CARGO_INCREMENTAL", "0")
+        .stderr(Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        return Err(Error::CheckFailed("reproducible (build failed)".into()));
+    }
+
+    // The same file may be reported for several targets, e.g. the dependency info
+    let mut artifacts = BTreeSet::new();
+    for message in Message::parse_stream(output.stdout.as_slice()) {
+        if let Message::CompilerArtifact(artifact) = message? {
+            artifacts.extend(artifact.filenames);
+        }
+    }
+    Ok(artifacts.into_iter().collect())
+}
+
+fn normalize(mut data: Vec<u8>, options: &Options) -> Vec<u8> {
+    for text in options.normalize.iter().filter(|e| !e.is_empty()) {
+        let text = text.as_bytes();
+        let mut normalized = Vec::with_capacity(data.len());
+        let mut rest = data.as_slice();
+        while !rest.is_empty() {
+            if rest.starts_with(text) {
+                rest = &rest[text.len()..];
+            } else {
+                normalized.push(rest[0]);
+                rest = &rest[1..];
+            }
+        }
+        data = normalized;
+    }
+    data
+}
+
+fn remove(directory: &Utf8Path) -> Result<()> {
+    match std::fs::remove_dir_all(directory.as_std_path()) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
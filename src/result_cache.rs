@@ -0,0 +1,187 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use cargo_metadata::camino::{Utf8Path, Utf8PathBuf};
+
+use crate::Result;
+use crate::git::git;
+use crate::plan::{Fnv1a, Plan, PlannedCheck, SkipReason};
+
+/// Environment variables changing the result of a check without showing in its command.
+const FINGERPRINTED_ENV: &[&str] = &[
+    "RUSTFLAGS",
+    "RUSTDOCFLAGS",
+    "CARGO_ENCODED_RUSTFLAGS",
+    "CARGO_ENCODED_RUSTDOCFLAGS",
+    "RUSTC",
+    "RUSTC_WRAPPER",
+    "RUSTC_WORKSPACE_WRAPPER",
+    "CARGO_BUILD_TARGET",
+    "CC",
+    "CXX",
+    "AR",
+    "CFLAGS",
+    "CXXFLAGS",
+];
+
+/// Successful check results of a commit, shared between CI pipelines.
+#[derive(Debug)]
+pub struct ResultCache {
+    backend: Backend,
+    commit: String,
+    workspace_root: Utf8PathBuf,
+    /// `rustc -vV` by effective toolchain, `None` for the rustup default.
+    rustc_versions: RefCell<BTreeMap<Option<String>, String>>,
+}
+
+#[derive(Debug)]
+enum Backend {
+    Directory(PathBuf),
+    /// Any S3-compatible storage, accessed via the `aws` CLI,
+    /// which also honors `AWS_ENDPOINT_URL` for non-AWS providers.
+    S3(String),
+}
+
+impl ResultCache {
+    /// Opens the cache at `location`, a directory or an `s3://bucket/prefix` URL.
+    ///
+    /// Results are only attributable to a commit for a clean git checkout,
+    /// otherwise there is no cache.
+    pub fn open(location: &str, workspace_root: &Utf8Path) -> Result<Option<ResultCache>> {
+        let backend = match location.strip_prefix("s3://") {
+            Some(_) => Backend::S3(location.trim_end_matches('/').to_string()),
+            None => Backend::Directory(PathBuf::from(location)),
+        };
+
+        let commit = git(workspace_root, &["rev-parse", "HEAD"])?;
+        let changes = git(workspace_root, &["status", "--porcelain"])?;
+        if !changes.is_empty() {
+            log::warn!("Uncommitted changes, the result cache is not used");
+            return Ok(None);
+        }
+
+        Ok(Some(ResultCache {
+            backend,
+            commit,
+            workspace_root: workspace_root.to_path_buf(),
+            rustc_versions: RefCell::new(BTreeMap::new()),
+        }))
+    }
+
+    /// Marks all checks that already succeeded for this commit as skipped.
+    pub fn apply(&self, plan: &mut Plan) -> Result<()> {
+        for check in plan.checks.iter_mut().filter(|e| e.skipped.is_none()) {
+            if self.contains(check)? {
+                check.skipped = Some(SkipReason::CacheHit);
+            }
+        }
+        plan.update_predicted_duration();
+
+        Ok(())
+    }
+
+    /// The commit and a fingerprint of the check and its environment,
+    /// so switching toolchains or global flags does not reuse results.
+    fn key(&self, check: &PlannedCheck) -> String {
+        let mut hasher = Fnv1a::default();
+        hasher.write(check.fingerprint(self.workspace_root.as_path()).as_str());
+        for key in FINGERPRINTED_ENV {
+            hasher.write(key);
+            hasher.write(std::env::var(key).unwrap_or_default().as_str());
+        }
+        // Where the tool is found, e.g. a cargo of another installation earlier in PATH
+        let program = resolve_program(check.command.program.as_str());
+        hasher.write(program.to_string_lossy().as_ref());
+        hasher.write(self.rustc_version(check).as_str());
+
+        format!("{}/{:016x}", self.commit, hasher.0)
+    }
+
+    /// The version of the rustc the check compiles with, e.g. to tell apart two nightlies.
+    fn rustc_version(&self, check: &PlannedCheck) -> String {
+        let toolchain = check.effective_toolchain.clone();
+        if let Some(version) = self.rustc_versions.borrow().get(&toolchain) {
+            return version.clone();
+        }
+
+        let rustc = check
+            .command
+            .env
+            .get("RUSTC")
+            .map(String::as_str)
+            .unwrap_or("rustc");
+        let mut command = Command::new(rustc);
+        command.arg("-vV").current_dir(&check.command.current_dir);
+        match toolchain.as_ref() {
+            Some(toolchain) => command.env("RUSTUP_TOOLCHAIN", toolchain),
+            None => command.env_remove("RUSTUP_TOOLCHAIN"),
+        };
+        // Without a rustc, there is nothing to tell apart
+        let version = command
+            .output()
+            .map(|e| String::from_utf8_lossy(&e.stdout).into_owned())
+            .unwrap_or_default();
+
+        self.rustc_versions
+            .borrow_mut()
+            .insert(toolchain, version.clone());
+        version
+    }
+
+    fn contains(&self, check: &PlannedCheck) -> Result<bool> {
+        let key = self.key(check);
+        match &self.backend {
+            Backend::Directory(directory) => Ok(directory.join(key).is_file()),
+            Backend::S3(url) => Ok(Command::new("aws")
+                .args(["s3", "ls", format!("{url}/{key}").as_str()])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()?
+                .success()),
+        }
+    }
+
+    pub fn record(&self, check: &PlannedCheck) -> Result<()> {
+        let key = self.key(check);
+        match &self.backend {
+            Backend::Directory(directory) => {
+                let path = directory.join(key);
+                std::fs::create_dir_all(path.parent().expect("key contains the commit"))?;
+                std::fs::write(path, check.id.as_str())?;
+            }
+            Backend::S3(url) => {
+                let mut child = Command::new("aws")
+                    .args(["s3", "cp", "-", format!("{url}/{key}").as_str()])
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::null())
+                    .spawn()?;
+                std::io::Write::write_all(
+                    &mut child.stdin.take().expect("stdin is piped"),
+                    check.id.as_bytes(),
+                )?;
+                if !child.wait()?.success() {
+                    log::warn!("Failed to record result of {} in {url}", check.id);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The path of a program as found in `PATH`, the name itself if it is not found.
+fn resolve_program(program: &str) -> PathBuf {
+    if Path::new(program).is_absolute() {
+        return PathBuf::from(program);
+    }
+
+    let candidates = [program.to_string(), format!("{program}.exe")];
+    std::env::var_os("PATH")
+        .iter()
+        .flat_map(std::env::split_paths)
+        .flat_map(|directory| candidates.iter().map(move |e| directory.join(e)))
+        .find(|e| e.is_file())
+        .unwrap_or_else(|| PathBuf::from(program))
+}
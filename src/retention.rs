@@ -0,0 +1,175 @@
+use std::time::{Duration, SystemTime};
+
+use cargo_metadata::Metadata;
+use cargo_metadata::camino::{Utf8Path, Utf8PathBuf};
+
+use crate::config::{CustomsFiles, workspace_retention};
+use crate::{Result, history, insta, metadata_cache, wizard};
+
+/// Long-lived state rather than output of past runs, pruning it would silently reset
+/// the coverage ratchet, the duration estimates, the metadata cache or bring back the wizard.
+/// Only `clean-state --all` removes these.
+const STATE_FILES: [&str; 5] = [
+    history::FILE_NAME,
+    history::COVERAGE_FILE_NAME,
+    insta::PENDING_FILE_NAME,
+    metadata_cache::FILE_NAME,
+    wizard::DECLINED_FILE_NAME,
+];
+
+/// Limits of the state customs keeps in `target/customs`, from the customs file of the workspace root.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Retention {
+    /// Files older than this are removed.
+    pub max_age: Option<Duration>,
+    /// The oldest files are removed until all fit into this many bytes.
+    pub max_size: Option<u64>,
+}
+
+/// What was removed.
+#[derive(Debug, Default)]
+pub struct Pruned {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+impl Retention {
+    pub fn is_limited(&self) -> bool {
+        self.max_age.is_some() || self.max_size.is_some()
+    }
+
+    /// Removes logs, reports and caches in `directory` exceeding the limits, the state files are kept.
+    pub fn prune(&self, directory: &Utf8Path) -> Result<Pruned> {
+        let mut files = Vec::new();
+        collect(directory, &mut files)?;
+        files.retain(|(path, _, _)| {
+            !(path.parent() == Some(directory)
+                && path.file_name().is_some_and(|e| STATE_FILES.contains(&e)))
+        });
+        // Oldest first
+        files.sort_by_key(|(_, modified, _)| *modified);
+
+        let now = SystemTime::now();
+        let mut total = files.iter().map(|(_, _, size)| size).sum::<u64>();
+        let mut pruned = Pruned::default();
+        for (path, modified, size) in files {
+            let too_old = self
+                .max_age
+                .is_some_and(|e| now.duration_since(modified).unwrap_or_default() > e);
+            let too_large = self.max_size.is_some_and(|e| total > e);
+            if !too_old && !too_large {
+                continue;
+            }
+
+            std::fs::remove_file(path.as_std_path())?;
+            total -= size;
+            pruned.files += 1;
+            pruned.bytes += size;
+        }
+
+        remove_empty_directories(directory)?;
+        Ok(pruned)
+    }
+}
+
+/// Applies the retention limits of the workspace to `target/customs`, or with `all` removes it entirely.
+pub fn clean_state(metadata: &Metadata, all: bool) -> Result<()> {
+    let directory = metadata.target_directory.join("customs");
+    if all {
+        match std::fs::remove_dir_all(directory.as_std_path()) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+        eprintln!("Removed {directory}");
+        return Ok(());
+    }
+
+    let retention = workspace_retention(metadata, &CustomsFiles::default())?;
+    if !retention.is_limited() {
+        eprintln!(
+            "Neither max-log-age nor max-artifacts-size is set in the customs file of the workspace root, \
+             use --all to remove everything"
+        );
+        return Ok(());
+    }
+
+    let pruned = retention.prune(&directory)?;
+    eprintln!("Removed {} files ({} bytes)", pruned.files, pruned.bytes);
+    Ok(())
+}
+
+/// All files below `directory` with their modification time and size.
+fn collect(directory: &Utf8Path, files: &mut Vec<(Utf8PathBuf, SystemTime, u64)>) -> Result<()> {
+    let entries = match std::fs::read_dir(directory.as_std_path()) {
+        Ok(e) => e,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+
+    for entry in entries {
+        let entry = entry?;
+        let Ok(path) = Utf8PathBuf::from_path_buf(entry.path()) else {
+            continue;
+        };
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            collect(&path, files)?;
+        } else {
+            files.push((path, metadata.modified()?, metadata.len()));
+        }
+    }
+    Ok(())
+}
+
+/// Removes the directories below `directory` left empty, returns whether `directory` itself is empty.
+fn remove_empty_directories(directory: &Utf8Path) -> Result<bool> {
+    let Ok(entries) = std::fs::read_dir(directory.as_std_path()) else {
+        return Ok(false);
+    };
+
+    let mut empty = true;
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        match Utf8PathBuf::from_path_buf(path) {
+            Ok(path) if entry.file_type()?.is_dir() && remove_empty_directories(&path)? => {
+                std::fs::remove_dir(path.as_std_path())?;
+            }
+            _ => empty = false,
+        }
+    }
+    Ok(empty)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prune_keeps_state_files() -> Result<()> {
+        let directory = Utf8PathBuf::from_path_buf(
+            std::env::temp_dir().join(format!("customs-retention-{}", std::process::id())),
+        )
+        .expect("temp dir must be UTF-8");
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(directory.join("logs"))?;
+        for file in STATE_FILES {
+            std::fs::write(directory.join(file), "{}")?;
+        }
+        std::fs::write(directory.join("logs").join("foo.log"), "output")?;
+
+        let pruned = Retention {
+            max_age: None,
+            max_size: Some(0),
+        }
+        .prune(&directory)?;
+
+        assert_eq!(pruned.files, 1);
+        assert!(!directory.join("logs").exists());
+        for file in STATE_FILES {
+            assert!(directory.join(file).is_file(), "{file} must be kept");
+        }
+        std::fs::remove_dir_all(&directory)?;
+        Ok(())
+    }
+}
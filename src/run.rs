@@ -0,0 +1,239 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use cargo_metadata::camino::Utf8PathBuf;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use crate::Result;
+use crate::console::Console;
+use crate::history::format_duration;
+use crate::plan::Plan;
+use crate::telemetry::Usage;
+use crate::test_results::TestOutcome;
+
+/// Environment variables changing what the checks do.
+const RELEVANT_ENV: &[&str] = &[
+    "RUSTUP_TOOLCHAIN",
+    "RUSTFLAGS",
+    "RUSTDOCFLAGS",
+    "RUSTC",
+    "RUSTC_WRAPPER",
+    "CARGO",
+    "CARGO_HOME",
+    "CARGO_TARGET_DIR",
+    "CARGO_BUILD_TARGET",
+];
+
+/// Everything needed to reproduce a run, written to `target/customs/run.json`.
+#[derive(Debug, Serialize)]
+pub struct RunInfo {
+    pub customs_version: &'static str,
+    pub rustc_version: String,
+    pub cargo_version: String,
+    pub host: String,
+    pub packages: Vec<String>,
+    /// The arguments customs was invoked with.
+    pub args: Vec<String>,
+    /// When the run started, in seconds since the Unix epoch.
+    pub started: f64,
+    pub current_dir: String,
+    pub env: BTreeMap<String, String>,
+    pub plan: Plan,
+    /// Results of the executed checks by id, filled in after the run.
+    pub outcomes: BTreeMap<String, Outcome>,
+    /// Results by regulation name and tag, e.g. `regulation embedded` or `tag lint`.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub groups: BTreeMap<String, GroupSummary>,
+}
+
+/// Counts of the checks of a regulation or tag.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GroupSummary {
+    pub passed: usize,
+    pub failed: usize,
+    /// Checks skipped, or not executed because the run stopped early.
+    pub not_run: usize,
+}
+
+impl Display for GroupSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if self.failed > 0 {
+            parts.push(format!("{} failed", self.failed));
+        }
+        parts.push(format!("{} passed", self.passed));
+        if self.not_run > 0 {
+            parts.push(format!("{} not run", self.not_run));
+        }
+        match self.failed {
+            0 => write!(f, "all green ({})", parts.join(", ")),
+            _ => write!(f, "{}", parts.join(", ")),
+        }
+    }
+}
+
+/// Result of an executed check.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Outcome {
+    pub passed: bool,
+    /// Whether the check was killed because the run was cancelled.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cancelled: bool,
+    /// When the check started, in seconds since the Unix epoch.
+    #[serde(default)]
+    pub started: f64,
+    /// Wall time in seconds.
+    pub seconds: f64,
+    /// Results of the individual tests of test jobs, by test name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tests: BTreeMap<String, TestOutcome>,
+    /// Resource usage of the host while the check ran, with `--telemetry`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+    /// Where the captured stdout of the check is logged, if it printed to stdout.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stdout_log: Option<Utf8PathBuf>,
+    /// Where the captured stderr of the check is logged, if it printed to stderr.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stderr_log: Option<Utf8PathBuf>,
+}
+
+/// The current time in seconds since the Unix epoch.
+pub fn unix_seconds() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0.0, |e| e.as_secs_f64())
+}
+
+impl RunInfo {
+    pub fn collect(plan: &Plan) -> Result<RunInfo> {
+        // In hermetic mode, the versions of the pinned tools the checks run with
+        let pinned = |name: &str| plan.tools.as_ref().and_then(|e| e.get(name));
+        let rustc = match pinned("rustc") {
+            Some(rustc) => rustc_version::VersionMeta::for_command(Command::new(rustc)),
+            None => rustc_version::version_meta(),
+        }
+        .map_err(anyhow::Error::from)?;
+        let cargo = Command::new(pinned("cargo").unwrap_or(Path::new("cargo")))
+            .arg("--version")
+            .output()?;
+
+        Ok(RunInfo {
+            customs_version: env!("CARGO_PKG_VERSION"),
+            rustc_version: rustc.short_version_string,
+            cargo_version: String::from_utf8_lossy(&cargo.stdout).trim().to_string(),
+            host: rustc.host,
+            packages: plan
+                .checks
+                .iter()
+                .map(|e| e.package.clone())
+                .unique()
+                .collect(),
+            args: std::env::args().skip(1).collect(),
+            started: unix_seconds(),
+            current_dir: std::env::current_dir()?.display().to_string(),
+            env: RELEVANT_ENV
+                .iter()
+                .filter_map(|key| Some((key.to_string(), std::env::var(key).ok()?)))
+                .collect(),
+            plan: plan.clone(),
+            outcomes: BTreeMap::new(),
+            groups: BTreeMap::new(),
+        })
+    }
+
+    pub fn print_header(&self, console: &Console) {
+        console.header_line(
+            "Running",
+            format!(
+                "customs {} with {}, {} on {}",
+                self.customs_version, self.rustc_version, self.cargo_version, self.host
+            )
+            .as_str(),
+        );
+        console.header_line("Packages", self.packages.join(", ").as_str());
+        if let Some(prediction) = self.plan.describe_prediction() {
+            console.header_line("Duration", prediction.as_str());
+        }
+        if !self.args.is_empty() {
+            console.header_line("Arguments", self.args.join(" ").as_str());
+        }
+    }
+
+    /// Tallies the outcomes by the names and tags of the regulations of the checks.
+    pub fn summarize_groups(&mut self) {
+        self.groups.clear();
+        for check in self.plan.checks.iter() {
+            let groups = check
+                .check
+                .regulation
+                .iter()
+                .map(|e| format!("regulation {e}"))
+                .chain(check.check.tags.iter().map(|e| format!("tag {e}")));
+            for group in groups {
+                let summary = self.groups.entry(group).or_default();
+                match self.outcomes.get(check.id.as_str()) {
+                    Some(outcome) if outcome.passed => summary.passed += 1,
+                    Some(_) => summary.failed += 1,
+                    None => summary.not_run += 1,
+                }
+            }
+        }
+    }
+
+    /// Prints the group summaries, if any regulation is named or tagged.
+    pub fn print_groups(&self, console: &Console) {
+        let rows = self
+            .groups
+            .iter()
+            .map(|(group, summary)| (group.clone(), summary.to_string()))
+            .collect::<Vec<_>>();
+        if !rows.is_empty() {
+            console.table("Regulations:", &rows);
+        }
+    }
+
+    /// Prints the status and duration of every check of the plan.
+    pub fn print_summary(&self, console: &Console) {
+        let heading = [
+            "Package",
+            "Platform",
+            "Build target",
+            "Job",
+            "Status",
+            "Duration",
+        ];
+        let mut rows = vec![heading.map(String::from).to_vec()];
+        for check in self.plan.checks.iter() {
+            let outcome = self.outcomes.get(check.id.as_str());
+            let status = match (outcome, check.skipped) {
+                (Some(outcome), _) if outcome.passed => "passed",
+                (Some(outcome), _) if outcome.cancelled => "cancelled",
+                (Some(_), _) => "failed",
+                (None, Some(_)) => "skipped",
+                (None, None) => "not run",
+            };
+            rows.push(vec![
+                check.package.clone(),
+                check.check.platform_target.clone(),
+                check.check.build_target.clone(),
+                check.check.job.name.clone(),
+                status.to_string(),
+                outcome.map_or("-".to_string(), |e| format_duration(e.seconds)),
+            ]);
+        }
+        console.columns("Summary:", &rows);
+    }
+
+    pub fn write(&self) -> Result<()> {
+        let path = self.plan.output_directory.join("run.json");
+        std::fs::create_dir_all(self.plan.output_directory.as_std_path())?;
+        let json = serde_json::to_string_pretty(self).map_err(anyhow::Error::from)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+}
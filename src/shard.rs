@@ -0,0 +1,64 @@
+use std::fmt::Display;
+
+use serde::Serialize;
+
+use crate::plan::{Fnv1a, PlannedCheck, SkipReason};
+
+/// One of several CI runners sharing the checks, e.g. `2/4` for the second of four.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Shard {
+    /// Starting at 1, like nextest partitions.
+    pub index: u32,
+    pub count: u32,
+}
+
+impl Display for Shard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+/// Parses shards like `2/4`.
+pub fn parse(input: &str) -> std::result::Result<Shard, String> {
+    let invalid = || format!("invalid shard '{input}', expected e.g. `2/4`");
+    let (index, count) = input.split_once('/').ok_or_else(invalid)?;
+    let index = index.trim().parse::<u32>().map_err(|_| invalid())?;
+    let count = count.trim().parse::<u32>().map_err(|_| invalid())?;
+    if index == 0 || index > count {
+        return Err(format!("shard {index} is not one of 1 to {count}"));
+    }
+    Ok(Shard { index, count })
+}
+
+impl Shard {
+    /// Whether the shard is responsible for a check or test, decided by a hash stable across runs and hosts.
+    pub fn contains(&self, name: &str) -> bool {
+        let mut hasher = Fnv1a::default();
+        hasher.write(name);
+        hasher.0 % u64::from(self.count) == u64::from(self.index - 1)
+    }
+}
+
+/// Restricts the checks to those of the shard.
+///
+/// Test jobs run on every shard with a part of their tests, so a single large test suite is spread too:
+/// nextest partitions them itself, the tests of `test` jobs are listed and filtered when the check runs.
+/// All other checks run on one of the shards.
+pub fn apply(checks: &mut [PlannedCheck], shard: Shard) {
+    for check in checks.iter_mut().filter(|e| e.skipped.is_none()) {
+        // Without running tests, there is nothing to split
+        let runs_tests = !check.check.job.compiles_tests_only();
+        match check.check.job.name.as_str() {
+            "nextest" if runs_tests => {
+                // After `nextest run`
+                check
+                    .command
+                    .args
+                    .insert(2, format!("--partition=hash:{shard}"));
+            }
+            "test" if runs_tests => check.shard = Some(shard),
+            _ if !shard.contains(check.id.as_str()) => check.skipped = Some(SkipReason::OtherShard),
+            _ => {}
+        }
+    }
+}
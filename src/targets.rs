@@ -0,0 +1,139 @@
+use std::collections::BTreeSet;
+
+use cargo_metadata::{Package, Target, TargetKind};
+
+use crate::RegulationCheck;
+
+/// The targets of a package a build target specifier selects.
+pub fn selected<'a>(package: &'a Package, build_target: &str) -> Vec<&'a Target> {
+    let is_lib = |e: &Target| {
+        e.kind.iter().any(|e| {
+            matches!(
+                e,
+                TargetKind::Lib
+                    | TargetKind::RLib
+                    | TargetKind::DyLib
+                    | TargetKind::CDyLib
+                    | TargetKind::StaticLib
+                    | TargetKind::ProcMacro
+            )
+        })
+    };
+
+    let matches = |target: &Target| match build_target.split_once(':') {
+        Some(("bin", name)) => target.is_bin() && target.name == name,
+        Some(("example", name)) => target.is_example() && target.name == name,
+        Some(("test" | "ui-test", name)) => target.is_test() && target.name == name,
+        Some(("bench", name)) => target.is_bench() && target.name == name,
+        _ => match build_target {
+            "lib" => is_lib(target),
+            "bins" => target.is_bin(),
+            "examples" => target.is_example(),
+            "tests" => target.is_test(),
+            "benches" => target.is_bench(),
+            _ => !target.is_custom_build(),
+        },
+    };
+
+    package.targets.iter().filter(|e| matches(e)).collect()
+}
+
+/// Proc-macro crates are loaded by the compiler, hence always built for the host.
+pub fn is_proc_macro(package: &Package) -> bool {
+    package.targets.iter().any(|e| e.is_proc_macro())
+}
+
+/// Whether the build target names a single target rather than a group.
+pub fn is_single(build_target: &str) -> bool {
+    build_target.contains(':')
+}
+
+/// `cargo run` only takes a single binary or example, groups are split into one check per target.
+pub fn split_runnable(package: &Package, check: RegulationCheck) -> Vec<RegulationCheck> {
+    if check.job.name != "run" || is_single(check.build_target.as_str()) {
+        return vec![check];
+    }
+
+    let runnable = selected(package, check.build_target.as_str())
+        .into_iter()
+        .filter_map(|e| {
+            if e.is_bin() {
+                Some(format!("bin:{}", e.name))
+            } else if e.is_example() {
+                Some(format!("example:{}", e.name))
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // Left as is to be pruned
+    if runnable.is_empty() {
+        return vec![check];
+    }
+
+    runnable
+        .into_iter()
+        .map(|build_target| RegulationCheck {
+            build_target,
+            ..check.clone()
+        })
+        .collect()
+}
+
+/// The features a check enables, following the feature table of the package transitively.
+fn enabled_features(package: &Package, check: &RegulationCheck) -> BTreeSet<String> {
+    let mut pending = check.features.clone();
+    if check.default_features {
+        pending.push("default".into());
+    }
+
+    let mut enabled = BTreeSet::new();
+    while let Some(feature) = pending.pop() {
+        // `dep:name` and `name/feature` enable dependencies, not features of the package
+        if feature.starts_with("dep:") || feature.contains('/') {
+            continue;
+        }
+        if let Some(implied) = package.features.get(&feature) {
+            pending.extend(implied.iter().filter(|e| !enabled.contains(*e)).cloned());
+        }
+        enabled.insert(feature);
+    }
+    enabled
+}
+
+/// Enables the features a single build target requires, since cargo rejects it otherwise.
+///
+/// Cargo silently leaves out targets of a group whose features are not enabled,
+/// those are returned to notify the user.
+pub fn apply_required_features(package: &Package, check: &mut RegulationCheck) -> Vec<String> {
+    let mut left_out = Vec::new();
+    let enabled = enabled_features(package, check);
+
+    for target in selected(package, check.build_target.as_str()) {
+        let missing = target
+            .required_features
+            .iter()
+            .filter(|e| !enabled.contains(*e))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if missing.is_empty() {
+            continue;
+        }
+
+        if is_single(check.build_target.as_str()) {
+            log::info!(
+                "Enabling features {} required by {} of {}",
+                missing.join(", "),
+                target.name,
+                package.name
+            );
+            check.features.extend(missing);
+        } else {
+            left_out.push(target.name.clone());
+        }
+    }
+
+    left_out
+}
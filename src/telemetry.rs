@@ -0,0 +1,187 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// How often the host is sampled while a check runs.
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Bytes per sector in `/proc/diskstats`, independent of the device.
+const SECTOR_SIZE: u64 = 512;
+
+/// Resource usage of the whole host while a check ran, only available on Linux.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+    /// Busy share of all CPUs in percent.
+    pub cpu_percent_average: f64,
+    pub cpu_percent_peak: f64,
+    /// Memory not available to new processes, in bytes.
+    pub memory_bytes_average: u64,
+    pub memory_bytes_peak: u64,
+    pub disk_read_bytes: u64,
+    pub disk_written_bytes: u64,
+}
+
+/// Samples the host in the background until finished.
+pub struct Sampler {
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<Option<Usage>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    /// Busy and total jiffies of all CPUs since boot.
+    cpu: (u64, u64),
+    memory: u64,
+    /// Sectors read and written since boot.
+    disk: (u64, u64),
+}
+
+impl Sampler {
+    pub fn start() -> Sampler {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let thread = std::thread::spawn(move || {
+            let mut samples = vec![sample()?];
+            while !stopped.load(Ordering::Relaxed) {
+                std::thread::sleep(SAMPLE_INTERVAL);
+                samples.push(sample()?);
+            }
+            Some(summarize(&samples))
+        });
+        Sampler { stop, thread }
+    }
+
+    /// The usage since the start, `None` if the host cannot be sampled.
+    pub fn finish(self) -> Option<Usage> {
+        self.stop.store(true, Ordering::Relaxed);
+        self.thread.join().expect("sampler must not panic")
+    }
+}
+
+fn summarize(samples: &[Sample]) -> Usage {
+    let cpu = samples
+        .windows(2)
+        .map(|e| {
+            let busy = e[1].cpu.0.saturating_sub(e[0].cpu.0);
+            let total = e[1].cpu.1.saturating_sub(e[0].cpu.1);
+            match total {
+                0 => 0.0,
+                _ => busy as f64 * 100.0 / total as f64,
+            }
+        })
+        .collect::<Vec<_>>();
+    let (first, last) = (samples[0], samples[samples.len() - 1]);
+
+    Usage {
+        cpu_percent_average: match cpu.is_empty() {
+            true => 0.0,
+            false => cpu.iter().sum::<f64>() / cpu.len() as f64,
+        },
+        cpu_percent_peak: cpu.iter().copied().fold(0.0, f64::max),
+        memory_bytes_average: samples.iter().map(|e| e.memory).sum::<u64>() / samples.len() as u64,
+        memory_bytes_peak: samples.iter().map(|e| e.memory).max().unwrap_or_default(),
+        disk_read_bytes: last.disk.0.saturating_sub(first.disk.0) * SECTOR_SIZE,
+        disk_written_bytes: last.disk.1.saturating_sub(first.disk.1) * SECTOR_SIZE,
+    }
+}
+
+fn sample() -> Option<Sample> {
+    Some(Sample {
+        cpu: cpu()?,
+        memory: memory()?,
+        disk: disk()?,
+    })
+}
+
+/// From the first line of `/proc/stat`: `cpu user nice system idle iowait ...`.
+fn cpu() -> Option<(u64, u64)> {
+    let stat = std::fs::read_to_string("/proc/stat").ok()?;
+    let jiffies = stat
+        .lines()
+        .next()?
+        .strip_prefix("cpu ")?
+        .split_whitespace()
+        .map(|e| e.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    let total = jiffies.iter().sum::<u64>();
+    let idle = jiffies.get(3)? + jiffies.get(4).unwrap_or(&0);
+    Some((total - idle, total))
+}
+
+fn memory() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let field = |name: &str| {
+        meminfo
+            .lines()
+            .find_map(|e| e.strip_prefix(name))?
+            .trim()
+            .strip_suffix("kB")?
+            .trim()
+            .parse::<u64>()
+            .ok()
+    };
+    Some(field("MemTotal:")?.saturating_sub(field("MemAvailable:")?) * 1024)
+}
+
+/// Sectors of whole disks, partitions would count twice.
+fn disk() -> Option<(u64, u64)> {
+    let diskstats = std::fs::read_to_string("/proc/diskstats").ok()?;
+    let mut sectors = (0, 0);
+    for line in diskstats.lines() {
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+        let Some(name) = fields.get(2) else {
+            continue;
+        };
+        let virtual_device = name.starts_with("loop") || name.starts_with("ram");
+        if virtual_device || !std::path::Path::new("/sys/block").join(name).exists() {
+            continue;
+        }
+        let field = |index: usize| fields.get(index).and_then(|e| e.parse::<u64>().ok());
+        sectors.0 += field(5).unwrap_or_default();
+        sectors.1 += field(9).unwrap_or_default();
+    }
+    Some(sectors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarize_averages_and_peaks_between_samples() {
+        let sample = |cpu, memory, disk| Sample { cpu, memory, disk };
+        let usage = summarize(&[
+            sample((0, 0), 100, (10, 20)),
+            sample((50, 100), 300, (11, 20)),
+            sample((50, 200), 200, (12, 24)),
+        ]);
+
+        assert_eq!(
+            usage,
+            Usage {
+                cpu_percent_average: 25.0,
+                cpu_percent_peak: 50.0,
+                memory_bytes_average: 200,
+                memory_bytes_peak: 300,
+                disk_read_bytes: 2 * SECTOR_SIZE,
+                disk_written_bytes: 4 * SECTOR_SIZE,
+            }
+        );
+    }
+
+    #[test]
+    fn summarize_single_sample_has_no_cpu_usage() {
+        let usage = summarize(&[Sample {
+            cpu: (50, 100),
+            memory: 100,
+            disk: (1, 1),
+        }]);
+
+        assert_eq!(usage.cpu_percent_average, 0.0);
+        assert_eq!(usage.memory_bytes_peak, 100);
+        assert_eq!(usage.disk_read_bytes, 0);
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Job;
+
+/// Result of a single test within a check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TestOutcome {
+    Passed,
+    Failed,
+    Ignored,
+    /// Failed at first, but passed when retried.
+    Flaky,
+}
+
+impl TestOutcome {
+    fn severity(self) -> u8 {
+        match self {
+            TestOutcome::Ignored => 0,
+            TestOutcome::Passed => 1,
+            TestOutcome::Flaky => 2,
+            TestOutcome::Failed => 3,
+        }
+    }
+}
+
+/// Whether the job runs tests whose results can be read from its output.
+pub fn reports_tests(job: &Job) -> bool {
+    matches!(job.name.as_str(), "test" | "nextest")
+}
+
+/// Reads the test results from the human readable output of libtest and nextest.
+///
+/// Tests of the same name in different test binaries are merged, the worst outcome taking precedence.
+/// Flaky tests are only reported by nextest itself when it retries.
+pub fn parse(stdout: &str, stderr: &str) -> BTreeMap<String, TestOutcome> {
+    let mut tests = BTreeMap::new();
+    let mut record = |name: &str, outcome: TestOutcome| {
+        let entry = tests.entry(name.to_string()).or_insert(outcome);
+        if outcome.severity() > entry.severity() {
+            *entry = outcome;
+        }
+    };
+
+    // libtest: `test module::name ... ok`
+    for line in stdout.lines() {
+        let Some((name, result)) = line
+            .strip_prefix("test ")
+            .and_then(|e| e.split_once(" ... "))
+        else {
+            continue;
+        };
+        let outcome = match result.split_whitespace().next() {
+            Some("ok") => TestOutcome::Passed,
+            Some("FAILED") => TestOutcome::Failed,
+            Some("ignored" | "ignored,") => TestOutcome::Ignored,
+            _ => continue,
+        };
+        record(name.trim(), outcome);
+    }
+
+    // nextest: `        PASS [   0.004s] crate::binary module::name`
+    for line in stderr.lines() {
+        let line = line.trim_start();
+        let Some((status, rest)) = line.split_once(' ') else {
+            continue;
+        };
+        let outcome = match status {
+            "PASS" => TestOutcome::Passed,
+            "FAIL" | "SIGSEGV" | "SIGABRT" | "TIMEOUT" => TestOutcome::Failed,
+            "SKIP" => TestOutcome::Ignored,
+            "FLAKY" => TestOutcome::Flaky,
+            _ => continue,
+        };
+        let Some((_, name)) = rest.trim_start().split_once("] ") else {
+            // a bare SKIP line has no duration
+            if outcome == TestOutcome::Ignored {
+                record(rest.trim(), outcome);
+            }
+            continue;
+        };
+        record(name.trim(), outcome);
+    }
+
+    tests
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_libtest_output_merging_binaries() {
+        let stdout = "running 3 tests\n\
+            test a::passes ... ok\n\
+            test a::fails ... FAILED\n\
+            test a::slow ... ignored, takes a minute\n\
+            \n\
+            running 1 test\n\
+            test a::passes ... FAILED\n\
+            test result: FAILED. 1 passed; 2 failed; 1 ignored\n";
+
+        assert_eq!(
+            parse(stdout, ""),
+            BTreeMap::from([
+                ("a::fails".into(), TestOutcome::Failed),
+                ("a::passes".into(), TestOutcome::Failed),
+                ("a::slow".into(), TestOutcome::Ignored),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_nextest_output() {
+        let stderr = "    Starting 4 tests across 1 binary\n\
+            \x20       PASS [   0.004s] foo::it passes\n\
+            \x20       FAIL [   0.010s] foo::it fails\n\
+            \x20      FLAKY 2/3 [   0.020s] foo::it retried\n\
+            \x20       SKIP [         ] foo::it skipped\n\
+            \x20    Summary [   0.030s] 4 tests run\n";
+
+        assert_eq!(
+            parse("", stderr),
+            BTreeMap::from([
+                ("foo::it fails".into(), TestOutcome::Failed),
+                ("foo::it passes".into(), TestOutcome::Passed),
+                ("foo::it retried".into(), TestOutcome::Flaky),
+                ("foo::it skipped".into(), TestOutcome::Ignored),
+            ])
+        );
+    }
+}
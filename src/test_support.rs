@@ -0,0 +1,92 @@
+//! Asserts which checks customs files expand to, so a workspace catches regressions of its configuration
+//! in its own tests.
+//!
+//! The plan is taken from `cargo customs plan`, so `cargo-customs` must be installed.
+//!
+//! ```no_run
+//! use cargo_customs::assert_plan;
+//!
+//! assert_plan!("crates/firmware", contains "firmware/clippy/thumbv7em-none-eabihf/lib");
+//! assert_plan!("crates/firmware", lacks "firmware/test/thumbv7em-none-eabihf/lib");
+//! ```
+
+use std::path::Path;
+use std::process::Command;
+
+use serde::Deserialize;
+
+/// The checks planned for a directory.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Plan {
+    pub checks: Vec<Check>,
+}
+
+/// A planned check, with the fields regulations are usually tested for.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Check {
+    pub id: String,
+    pub package: String,
+    pub platform_target: String,
+    pub build_target: String,
+    pub job: Job,
+    pub features: Vec<String>,
+    pub toolchain: Option<String>,
+    /// Why the check would not run, e.g. `pruned`.
+    pub skipped: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Job {
+    pub name: String,
+}
+
+impl Plan {
+    /// IDs of the checks that would run.
+    pub fn ids(&self) -> Vec<&str> {
+        self.checks
+            .iter()
+            .filter(|e| e.skipped.is_none())
+            .map(|e| e.id.as_str())
+            .collect()
+    }
+
+    /// Whether the check of this ID would run.
+    pub fn contains(&self, id: &str) -> bool {
+        self.ids().contains(&id)
+    }
+}
+
+/// Plans the checks like `cargo customs` run in `directory` would, panicking if planning fails.
+pub fn plan(directory: impl AsRef<Path>) -> Plan {
+    let directory = directory.as_ref();
+    let output = Command::new("cargo")
+        .args(["customs", "plan", "--format", "json"])
+        .current_dir(directory)
+        .output()
+        .expect("cargo must be installed");
+    assert!(
+        output.status.success(),
+        "planning in {} failed:\n{}",
+        directory.display(),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    serde_json::from_slice(&output.stdout).expect("the plan of cargo customs must be valid")
+}
+
+/// Asserts that a check `contains` or `lacks` in the plan of a directory, by its ID.
+#[macro_export]
+macro_rules! assert_plan {
+    ($directory:expr, contains $id:expr) => {{
+        let plan = $crate::test_support::plan($directory);
+        assert!(
+            plan.contains($id),
+            "{} is not planned, the plan has:\n{}",
+            $id,
+            plan.ids().join("\n")
+        );
+    }};
+    ($directory:expr, lacks $id:expr) => {{
+        let plan = $crate::test_support::plan($directory);
+        assert!(!plan.contains($id), "{} is planned", $id);
+    }};
+}
@@ -0,0 +1,120 @@
+use cargo_metadata::camino::{Utf8Path, Utf8PathBuf};
+use serde::Deserialize;
+
+use crate::{Error, Result};
+
+/// A `rust-toolchain.toml` (or legacy `rust-toolchain`) file pinning the toolchain for a directory tree.
+#[derive(Debug, Clone)]
+pub struct ToolchainFile {
+    pub path: Utf8PathBuf,
+    pub channel: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ToolchainFileContent {
+    toolchain: ToolchainSection,
+}
+
+#[derive(Debug, Deserialize)]
+struct ToolchainSection {
+    channel: Option<String>,
+    path: Option<String>,
+}
+
+/// Finds the toolchain file rustup would apply in `directory`,
+/// i.e. the closest one in the directory or any of its ancestors.
+pub fn find_toolchain_file(directory: &Utf8Path) -> Result<Option<ToolchainFile>> {
+    // rustup prefers the legacy name if both exist
+    const FILE_NAMES: [&str; 2] = ["rust-toolchain", "rust-toolchain.toml"];
+
+    for directory in directory.ancestors() {
+        for file_name in FILE_NAMES {
+            let path = directory.join(file_name);
+            if !std::fs::exists(path.as_std_path())? {
+                continue;
+            }
+
+            let data = std::fs::read_to_string(path.as_std_path())?;
+            let channel = parse_channel(data.as_str())
+                .map_err(|e| Error::InvalidToolchainFile(path.to_string(), e))?;
+            if let Some(channel) = channel {
+                return Ok(Some(ToolchainFile { path, channel }));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// The toolchain customs was launched with, e.g. by `cargo +nightly customs`.
+///
+/// rustup sets `RUSTUP_TOOLCHAIN` for every proxied call, also for its default toolchain or
+/// a toolchain file. Only an override on the command line or in the environment applies to all packages,
+/// otherwise the toolchain is resolved per package directory.
+pub fn launch_toolchain() -> Option<String> {
+    let toolchain = std::env::var("RUSTUP_TOOLCHAIN").ok()?;
+    match std::env::var("RUSTUP_TOOLCHAIN_SOURCE").as_deref() {
+        Ok("cli" | "env") | Err(_) => Some(toolchain),
+        Ok(_) => None,
+    }
+}
+
+fn parse_channel(data: &str) -> std::result::Result<Option<String>, toml::de::Error> {
+    let trimmed = data.trim();
+
+    // legacy format with only the channel name
+    if !trimmed.contains('\n') && !trimmed.contains('[') {
+        return Ok(Some(trimmed.to_string()));
+    }
+
+    let content: ToolchainFileContent = toml::from_str(data)?;
+    Ok(content.toolchain.channel.or(content.toolchain.path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_channel_of_both_formats() {
+        assert_eq!(
+            parse_channel("nightly-2024-01-01\n").unwrap(),
+            Some("nightly-2024-01-01".into())
+        );
+        assert_eq!(
+            parse_channel("[toolchain]\nchannel = \"1.80\"\ncomponents = [\"clippy\"]\n").unwrap(),
+            Some("1.80".into())
+        );
+        assert_eq!(
+            parse_channel("[toolchain]\ncomponents = [\"clippy\"]\n").unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn find_toolchain_file_prefers_the_closest_one() -> Result<()> {
+        let directory = Utf8PathBuf::from_path_buf(
+            std::env::temp_dir().join(format!("customs-toolchain-{}", std::process::id())),
+        )
+        .expect("temp dir must be UTF-8");
+        let _ = std::fs::remove_dir_all(&directory);
+        let package = directory.join("crates").join("foo");
+        std::fs::create_dir_all(&package)?;
+        std::fs::write(
+            directory.join("rust-toolchain.toml"),
+            "[toolchain]\nchannel = \"stable\"\n",
+        )?;
+
+        let file = find_toolchain_file(&package)?.expect("toolchain file of the root");
+        assert_eq!(file.path, directory.join("rust-toolchain.toml"));
+        assert_eq!(file.channel, "stable");
+
+        std::fs::write(package.join("rust-toolchain"), "nightly\n")?;
+        let file = find_toolchain_file(&package)?.expect("toolchain file of the package");
+        assert_eq!(file.path, package.join("rust-toolchain"));
+        assert_eq!(file.channel, "nightly");
+
+        std::fs::remove_dir_all(&directory)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::plan::Invocation;
+use crate::{Error, Result};
+
+/// Subcommands implemented by cargo itself,
+/// every other subcommand is an external `cargo-<name>` executable.
+const BUILTIN_CARGO_COMMANDS: &[&str] = &[
+    "add",
+    "bench",
+    "build",
+    "check",
+    "clean",
+    "doc",
+    "fetch",
+    "fix",
+    "generate-lockfile",
+    "info",
+    "init",
+    "install",
+    "locate-project",
+    "login",
+    "logout",
+    "metadata",
+    "new",
+    "owner",
+    "package",
+    "pkgid",
+    "publish",
+    "remove",
+    "report",
+    "run",
+    "rustc",
+    "rustdoc",
+    "search",
+    "test",
+    "tree",
+    "uninstall",
+    "update",
+    "vendor",
+    "verify-project",
+    "version",
+    "yank",
+];
+
+/// Absolute paths of all tools used by a plan.
+///
+/// In hermetic environments (e.g. Nix) tools must not be looked up in `PATH` at execution time,
+/// hence they are resolved once when planning and recorded here.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(transparent)]
+pub struct Tools(BTreeMap<String, PathBuf>);
+
+impl Tools {
+    /// Resolves the toolchain customs runs with.
+    /// Explicit `CARGO`/`RUSTC` overrides take precedence over `PATH`, just like cargo does.
+    pub fn resolve_toolchain() -> Result<Tools> {
+        let mut tools = Tools::default();
+        tools.resolve("cargo", Some("CARGO"))?;
+        tools.resolve("rustc", Some("RUSTC"))?;
+        Ok(tools)
+    }
+
+    /// The path a tool was resolved to, if it was.
+    pub fn get(&self, name: &str) -> Option<&Path> {
+        self.0.get(name).map(PathBuf::as_path)
+    }
+
+    fn resolve(&mut self, name: &str, env_override: Option<&str>) -> Result<PathBuf> {
+        if let Some(path) = self.0.get(name) {
+            return Ok(path.clone());
+        }
+
+        let path = env_override
+            .and_then(std::env::var_os)
+            .map(PathBuf::from)
+            .filter(|e| e.is_absolute())
+            .or_else(|| find_in_path(name))
+            .ok_or_else(|| Error::ToolNotFound(name.to_string()))?;
+
+        self.0.insert(name.to_string(), path.clone());
+        Ok(path)
+    }
+
+    /// Rewrites an invocation to only refer to resolved tools.
+    ///
+    /// External subcommands are invoked directly the same way cargo would,
+    /// because cargo itself would look them up in `PATH`.
+    pub fn pin(&mut self, mut invocation: Invocation) -> Result<Invocation> {
+        let cargo = self.resolve("cargo", Some("CARGO"))?;
+        let rustc = self.resolve("rustc", Some("RUSTC"))?;
+
+        let subcommand = invocation.args.first().map(String::as_str);
+        invocation.program = match subcommand {
+            // Standalone tools, not run through cargo
+            _ if invocation.program != "cargo" => {
+                let program = invocation.program.clone();
+                self.resolve(program.as_str(), None)?
+            }
+            Some(subcommand) if !BUILTIN_CARGO_COMMANDS.contains(&subcommand) => {
+                let executable = format!("cargo-{subcommand}");
+                self.resolve(executable.as_str(), None)?
+            }
+            _ => cargo.clone(),
+        }
+        .to_string_lossy()
+        .into_owned();
+
+        invocation
+            .env
+            .insert("CARGO".into(), cargo.to_string_lossy().into_owned());
+        // A regulation may compile with a specific rustc
+        invocation
+            .env
+            .entry("RUSTC".into())
+            .or_insert_with(|| rustc.to_string_lossy().into_owned());
+
+        Ok(invocation)
+    }
+}
+
+fn find_in_path(name: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    let file_name = format!("{name}{}", std::env::consts::EXE_SUFFIX);
+    std::env::split_paths(&path)
+        .map(|e| e.join(file_name.as_str()))
+        .find(|e| e.is_file())
+}
@@ -0,0 +1,67 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A timestamped log of the whole run, written with `--log-file`.
+///
+/// Unlike the per-check logs, it contains all checks in the order they ran,
+/// their output as it arrived and markers where each check starts and ends.
+#[derive(Debug, Clone)]
+pub struct Transcript {
+    file: Arc<Mutex<File>>,
+}
+
+impl Transcript {
+    pub fn create(path: &Path) -> std::io::Result<Transcript> {
+        Ok(Transcript {
+            file: Arc::new(Mutex::new(File::create(path)?)),
+        })
+    }
+
+    /// Appends a line prefixed with the current time.
+    pub fn line(&self, text: &str) {
+        let mut file = self.file.lock().expect("transcript writers must not panic");
+        // A transcript that cannot be written must not fail the checks
+        let _ = writeln!(file, "{} {text}", timestamp(SystemTime::now()));
+    }
+
+    /// Appends each line of a check's output, marked with the ID of the check.
+    pub fn output(&self, id: &str, data: &[u8]) {
+        for line in String::from_utf8_lossy(data).lines() {
+            self.line(format!("{id} | {line}").as_str());
+        }
+    }
+}
+
+/// The time in UTC as `2024-01-31T12:00:00.000Z`.
+fn timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (days, time_of_day) = (seconds / 86400, seconds % 86400);
+
+    // Howard Hinnant's civil_from_days
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        time_of_day / 3600,
+        time_of_day / 60 % 60,
+        time_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
@@ -0,0 +1,219 @@
+use std::io::{BufRead, IsTerminal, Write};
+
+use cargo_metadata::Metadata;
+use cargo_metadata::camino::Utf8PathBuf;
+use toml_edit::{Array, ArrayOfTables, DocumentMut, Item, Table, value};
+
+use crate::Result;
+use crate::config::{CUSTOMS_FILE_NAME, CustomsFile, parse_customs_toml};
+
+const DEFAULT_JOBS: &[&str] = &["fmt", "clippy", "test"];
+const DEFAULT_PLATFORM_TARGETS: &[&str] = &["host"];
+const DEFAULT_BUILD_TARGETS: &[&str] = &["all"];
+
+/// Marks in `target/customs` that the user declined the wizard, so it is not offered again.
+pub const DECLINED_FILE_NAME: &str = "wizard-declined";
+
+/// Whether the wizard may ask questions, which needs a person at a terminal and never happens in CI.
+pub fn is_interactive() -> bool {
+    std::io::stdin().is_terminal() && std::io::stderr().is_terminal()
+}
+
+/// Whether the user declined the wizard in an earlier run.
+pub fn is_declined(metadata: &Metadata) -> bool {
+    declined_marker(metadata).is_file()
+}
+
+fn declined_marker(metadata: &Metadata) -> Utf8PathBuf {
+    metadata
+        .target_directory
+        .join("customs")
+        .join(DECLINED_FILE_NAME)
+}
+
+/// Asks which jobs, platform targets and build targets to enforce and writes them to a new customs file in the workspace root.
+///
+/// The user may decline, which is remembered until `cargo customs clean-state --all`.
+pub fn setup(metadata: &Metadata) -> Result<()> {
+    let path = metadata.workspace_root.join(CUSTOMS_FILE_NAME);
+    eprintln!("No customs file found in the workspace.");
+    if !confirm(format!("Create {path} now?").as_str())? {
+        let marker = declined_marker(metadata);
+        if let Some(parent) = marker.parent() {
+            std::fs::create_dir_all(parent.as_std_path())?;
+        }
+        std::fs::write(marker.as_std_path(), "")?;
+        eprintln!("Skipped, see `cargo customs add --help` to add regulations later.");
+        return Ok(());
+    }
+
+    // The root package takes regulations, members of a virtual workspace only inherit a default.
+    let virtual_workspace = metadata.root_package().is_none();
+    loop {
+        let jobs = ask("Jobs to enforce", DEFAULT_JOBS)?;
+        let platform_targets = ask(
+            "Platform targets, `host` for the current one",
+            DEFAULT_PLATFORM_TARGETS,
+        )?;
+        let build_targets = ask(
+            "Build targets, e.g. `lib` or `bin:name`",
+            DEFAULT_BUILD_TARGETS,
+        )?;
+
+        let data = document(
+            jobs.as_slice(),
+            platform_targets.as_slice(),
+            build_targets.as_slice(),
+            virtual_workspace,
+        );
+
+        // Unknown jobs are asked for again instead of leaving a broken file behind
+        if let Err(e) = parse_customs_toml::<CustomsFile>(path.as_std_path(), data.as_str()) {
+            eprintln!("{e}");
+            continue;
+        }
+
+        std::fs::write(path.as_std_path(), data)?;
+        eprintln!("Wrote {path}");
+        if virtual_workspace {
+            inherit_default(metadata)?;
+        }
+        return Ok(());
+    }
+}
+
+/// A customs file with a regulation, or a default for the members of a virtual workspace.
+fn document(
+    jobs: &[String],
+    platform_targets: &[String],
+    build_targets: &[String],
+    virtual_workspace: bool,
+) -> String {
+    let mut table = Table::new();
+    table.insert("jobs", value(jobs.iter().collect::<Array>()));
+    table.insert(
+        "platform-targets",
+        value(platform_targets.iter().collect::<Array>()),
+    );
+    table.insert(
+        "build-targets",
+        value(build_targets.iter().collect::<Array>()),
+    );
+
+    let mut document = DocumentMut::new();
+    match virtual_workspace {
+        true => {
+            document.insert("default", Item::Table(table));
+        }
+        false => {
+            let mut regulations = ArrayOfTables::new();
+            regulations.push(table);
+            document.insert("regulation", Item::ArrayOfTables(regulations));
+        }
+    }
+    document.to_string()
+}
+
+/// Offers to let all members inherit the default of the workspace root with an empty `[[regulation]]`.
+fn inherit_default(metadata: &Metadata) -> Result<()> {
+    let members = metadata
+        .workspace_packages()
+        .into_iter()
+        .filter_map(|e| e.manifest_path.parent())
+        .map(|e| e.join(CUSTOMS_FILE_NAME))
+        .filter(|e| !e.exists())
+        .collect::<Vec<_>>();
+    if members.is_empty()
+        || !confirm("Enforce the default for all members without a customs file?")?
+    {
+        eprintln!(
+            "Members inherit this default with an empty `[[regulation]]` in their own {CUSTOMS_FILE_NAME}."
+        );
+        return Ok(());
+    }
+
+    for path in members {
+        std::fs::write(path.as_std_path(), "[[regulation]]\n")?;
+        eprintln!("Wrote {path}");
+    }
+    Ok(())
+}
+
+fn confirm(question: &str) -> Result<bool> {
+    let answer = prompt(format!("{question} [Y/n] ").as_str())?;
+    Ok(matches!(answer.to_lowercase().as_str(), "" | "y" | "yes"))
+}
+
+/// A list separated by commas or spaces, the defaults if the answer is empty.
+fn ask(question: &str, defaults: &[&str]) -> Result<Vec<String>> {
+    let answer = prompt(format!("{question} [{}]: ", defaults.join(", ")).as_str())?;
+    Ok(parse_list(answer.as_str(), defaults))
+}
+
+fn parse_list(answer: &str, defaults: &[&str]) -> Vec<String> {
+    let values = answer
+        .split([',', ' '])
+        .filter(|e| !e.is_empty())
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    match values.is_empty() {
+        true => defaults.iter().map(|e| e.to_string()).collect(),
+        false => values,
+    }
+}
+
+fn prompt(text: &str) -> Result<String> {
+    eprint!("{text}");
+    std::io::stderr().flush()?;
+    let mut line = String::new();
+    std::io::stdin().lock().read_line(&mut line)?;
+    Ok(line.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|e| e.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_list_splits_on_commas_and_spaces() {
+        assert_eq!(
+            parse_list("fmt, clippy test", DEFAULT_JOBS),
+            strings(&["fmt", "clippy", "test"])
+        );
+        assert_eq!(parse_list("", DEFAULT_BUILD_TARGETS), strings(&["all"]));
+    }
+
+    #[test]
+    fn document_has_a_regulation_for_a_package() {
+        let data = document(
+            &strings(&["fmt", "test"]),
+            &strings(&["host"]),
+            &strings(&["lib"]),
+            false,
+        );
+        assert_eq!(
+            data,
+            "[[regulation]]\njobs = [\"fmt\", \"test\"]\nplatform-targets = [\"host\"]\nbuild-targets = [\"lib\"]\n"
+        );
+        assert!(parse_customs_toml::<CustomsFile>(Path::new(CUSTOMS_FILE_NAME), &data).is_ok());
+    }
+
+    #[test]
+    fn document_has_a_default_for_a_virtual_workspace() {
+        let data = document(
+            &strings(&["fmt"]),
+            &strings(&["host"]),
+            &strings(&["all"]),
+            true,
+        );
+        assert_eq!(
+            data,
+            "[default]\njobs = [\"fmt\"]\nplatform-targets = [\"host\"]\nbuild-targets = [\"all\"]\n"
+        );
+    }
+}
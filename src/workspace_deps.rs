@@ -0,0 +1,63 @@
+use cargo_metadata::{DependencyKind, Metadata};
+
+use crate::{Error, Result};
+
+/// Checks the dependencies of the package in `directory` on other workspace members.
+///
+/// Their version requirements must match the version of the member, and path dependencies
+/// of publishable packages need a version, as crates.io drops the path.
+pub fn check(metadata: &Metadata, directory: &std::path::Path) -> Result<()> {
+    let members = metadata.workspace_packages();
+    let Some(package) = members
+        .iter()
+        .find(|e| e.manifest_path.parent().is_some_and(|e| e == directory))
+    else {
+        return Err(Error::CheckFailed(format!(
+            "workspace-deps (no workspace member in {})",
+            directory.display()
+        )));
+    };
+    let publishable = package.publish.as_ref().is_none_or(|e| !e.is_empty());
+
+    let mut problems = 0;
+    for dependency in package.dependencies.iter() {
+        let Some(member) = members.iter().find(|e| {
+            e.name.as_str() == dependency.name
+                && dependency
+                    .path
+                    .as_ref()
+                    .is_none_or(|path| e.manifest_path.parent() == Some(path.as_path()))
+        }) else {
+            continue;
+        };
+
+        // Without a version, cargo fills in a wildcard
+        let unversioned = dependency.req.comparators.is_empty();
+        if unversioned {
+            if dependency.path.is_some()
+                && publishable
+                && dependency.kind != DependencyKind::Development
+            {
+                println!(
+                    "{}: path dependency on {} has no version",
+                    package.name, member.name
+                );
+                problems += 1;
+            }
+        } else if !dependency.req.matches(&member.version) {
+            println!(
+                "{}: requires {} {}, but the workspace has {}",
+                package.name, member.name, dependency.req, member.version
+            );
+            problems += 1;
+        }
+    }
+
+    if problems > 0 {
+        return Err(Error::CheckFailed(format!(
+            "workspace-deps ({problems} problems)"
+        )));
+    }
+
+    Ok(())
+}
@@ -1,6 +1,69 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
 use anyhow::Result;
+use assert_cmd::Command;
 use assert_cmd::cargo::cargo_bin_cmd;
 
+/// A directory for the files of a single test, emptied first so nothing of an earlier run remains.
+fn fresh_directory(name: &str) -> Result<PathBuf> {
+    let directory = std::env::temp_dir()
+        .join("customs-tests")
+        .join(format!("{name}-{}", std::process::id()));
+    if directory.exists() {
+        std::fs::remove_dir_all(&directory)?;
+    }
+    std::fs::create_dir_all(&directory)?;
+    Ok(directory)
+}
+
+/// `PATH` with the tools of `bin` taking precedence.
+fn path_with_tools(bin: PathBuf) -> Result<OsString> {
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    Ok(std::env::join_paths(
+        std::iter::once(bin).chain(std::env::split_paths(&path)),
+    )?)
+}
+
+/// Customs on a fixture with tools of its `bin` directory taking precedence.
+fn customs_with_tools(fixture: &str) -> Result<Command> {
+    let path = path_with_tools(std::fs::canonicalize(format!("./tests/{fixture}/bin"))?)?;
+
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir(format!("./tests/{fixture}"))
+        .env("PATH", path);
+    Ok(cmd)
+}
+
+/// Customs on the `parallel` workspace, whose `typos` and `nap` jobs each take a second.
+fn parallel_customs() -> Result<Command> {
+    customs_with_tools("parallel")
+}
+
+/// The lines each check began and ended on in a log file.
+fn spans(log: &str) -> HashMap<String, (usize, usize)> {
+    let mut spans = HashMap::new();
+    for (line, text) in log.lines().enumerate() {
+        let mut words = text.split(' ').skip(1);
+        match (words.next(), words.next()) {
+            (Some("begin"), Some(id)) => {
+                spans.insert(id.to_string(), (line, usize::MAX));
+            }
+            (Some("end"), Some(id)) => {
+                spans.entry(id.to_string()).or_insert((0, 0)).1 = line;
+            }
+            _ => {}
+        }
+    }
+    spans
+}
+
+fn overlap(spans: &HashMap<String, (usize, usize)>, a: &str, b: &str) -> bool {
+    let (a, b) = (spans[a], spans[b]);
+    a.0 < b.1 && b.0 < a.1
+}
+
 #[test]
 fn test_customs_help_works_outside_of_crate() -> Result<()> {
     let mut cmd = cargo_bin_cmd!("cargo-customs");
@@ -44,6 +107,49 @@ fn test_customs_runs_on_workspace() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_customs_runs_checks_in_parallel_with_jobs() -> Result<()> {
+    let log_file = fresh_directory("jobs")?.join("run.log");
+
+    parallel_customs()?
+        .args(["--jobs", "4", "--no-package-fence", "--log-file"])
+        .arg(&log_file)
+        .assert()
+        .success();
+
+    let spans = spans(&std::fs::read_to_string(&log_file)?);
+    assert_eq!(spans.len(), 4);
+    // Nothing to build
+    assert!(
+        overlap(&spans, "one/typos/host/all", "two/typos/host/all"),
+        "{spans:?}"
+    );
+    // Building in the same target directory
+    assert!(
+        !overlap(&spans, "one/nap/host/all", "two/nap/host/all"),
+        "{spans:?}"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_prefixes_streamed_output_of_concurrent_checks() -> Result<()> {
+    let output = parallel_customs()?
+        .args(["--jobs", "2", "-v"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let output = String::from_utf8(output)?;
+    for package in ["one", "two"] {
+        assert!(output.contains(&format!("{package}/typos/host/all: typos done\n")));
+        assert!(output.contains(&format!("{package}/nap/host/all: nap done\n")));
+    }
+    Ok(())
+}
+
 #[test]
 fn test_customs_runs_on_crate_in_workspace() -> Result<()> {
     let mut cmd = cargo_bin_cmd!("cargo-customs");
@@ -62,4 +168,2493 @@ fn test_customs_runs_on_sub_folder_in_workspace_that_is_not_a_crate() -> Result<
     Ok(())
 }
 
+#[test]
+fn test_customs_plan_lists_checks_without_running() -> Result<()> {
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("./tests/workspace")
+        .args(["plan", "--format", "json"]);
+
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let plan: serde_json::Value = serde_json::from_slice(&output)?;
+    let ids = plan["checks"]
+        .as_array()
+        .expect("checks must be a list")
+        .iter()
+        .map(|e| e["id"].as_str().expect("id must be a string"))
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        ids,
+        [
+            "bar/fmt/host/all",
+            "bar/build/host/all",
+            "foo/fmt/host/all",
+            "foo/build/host/all",
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_implicit_regulations_apply_to_packages_without_customs() -> Result<()> {
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("./tests/workspace/no-customs")
+        .args(["plan", "--implicit"]);
+
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let plan: serde_json::Value = serde_json::from_slice(&output)?;
+    let ids = plan["checks"]
+        .as_array()
+        .expect("checks must be a list")
+        .iter()
+        .map(|e| e["id"].as_str().expect("id must be a string"))
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        ids,
+        [
+            "no-customs/check/host/all",
+            "no-customs/clippy/host/all",
+            "no-customs/test/host/all",
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_dry_run_prints_command_lines() -> Result<()> {
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("./tests/workspace")
+        .args(["--package", "foo", "--dry-run"]);
+
+    let output = String::from_utf8(cmd.assert().success().get_output().stdout.clone())?;
+    assert_eq!(
+        output,
+        "foo/fmt/host/all: cargo fmt\nfoo/build/host/all: cargo build --all-targets\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_command_line_overrides_extend_regulations() -> Result<()> {
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("./tests/workspace").args([
+        "--package",
+        "foo",
+        "--dry-run",
+        "--add-job",
+        "check",
+        "--set",
+        "locked=true",
+    ]);
+
+    let output = String::from_utf8(cmd.assert().success().get_output().stdout.clone())?;
+    assert_eq!(
+        output,
+        "foo/fmt/host/all: cargo fmt\n\
+         foo/check/host/all: cargo check --all-targets --locked\n\
+         foo/build/host/all: cargo build --all-targets --locked\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_cleans_each_package_once_per_platform_target() -> Result<()> {
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("./tests/workspace").args([
+        "--package",
+        "foo",
+        "--dry-run",
+        "--set",
+        "jobs=[\"clean\"]",
+        "--set",
+        "build-targets=[\"lib\", \"tests\"]",
+        "--set",
+        "feature-sets=[[], [\"a\"]]",
+    ]);
+
+    let output = String::from_utf8(cmd.assert().success().get_output().stdout.clone())?;
+    assert_eq!(output, "foo/clean/host/all: cargo clean -p foo\n");
+    Ok(())
+}
+
+#[test]
+fn test_customs_runs_only_selected_regulations() -> Result<()> {
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("./tests/named")
+        .args(["--dry-run", "--regulation", "basics"]);
+
+    let output = String::from_utf8(cmd.assert().success().get_output().stdout.clone())?;
+    assert_eq!(
+        output,
+        "named/fmt/host/all: cargo fmt\nnamed/build/host/all: cargo build --all-targets\n"
+    );
+
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("./tests/named")
+        .args(["--dry-run", "--regulation", "embedded"]);
+    cmd.assert().failure();
+    Ok(())
+}
+
+#[test]
+fn test_customs_suite_runs_only_its_regulations() -> Result<()> {
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("./tests/suite")
+        .args(["--dry-run", "suite", "smoke"]);
+
+    let output = String::from_utf8(cmd.assert().success().get_output().stdout.clone())?;
+    assert_eq!(
+        output,
+        "suite/fmt/host/all: cargo fmt\nsuite/build/host/all: cargo build --all-targets\n"
+    );
+
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("./tests/suite").args(["suite", "nightly"]);
+    cmd.assert().failure();
+    Ok(())
+}
+
+#[test]
+fn test_customs_keep_going_prints_summary_of_all_checks() -> Result<()> {
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("./tests/workspace").arg("--keep-going");
+
+    let output = String::from_utf8(cmd.assert().success().get_output().stderr.clone())?;
+    let summary = output
+        .split_once("Summary:\n")
+        .map(|e| e.1)
+        .expect("summary is printed");
+    let rows = summary
+        .lines()
+        .map(|e| e.split_whitespace().take(5).collect::<Vec<_>>().join(" "))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        rows,
+        [
+            "Package Platform Build target Job",
+            "bar host all fmt passed",
+            "bar host all build passed",
+            "foo host all fmt passed",
+            "foo host all build passed",
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_dry_run_prints_env_of_command_lines() -> Result<()> {
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("./tests/workspace").args([
+        "--package",
+        "foo",
+        "--dry-run",
+        "--set",
+        "rustflags=[\"-D warnings\"]",
+    ]);
+
+    let output = String::from_utf8(cmd.assert().success().get_output().stdout.clone())?;
+    assert_eq!(
+        output,
+        "foo/fmt/host/all: RUSTFLAGS='-D warnings' cargo fmt\n\
+         foo/build/host/all: RUSTFLAGS='-D warnings' cargo build --all-targets\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_dry_run_skips_cached_results() -> Result<()> {
+    let directory = fresh_directory("dry-run-cache")?;
+    let workspace = directory.join("workspace");
+    let cache = directory.join("cache");
+    std::fs::create_dir_all(workspace.join("src"))?;
+    std::fs::write(
+        workspace.join("Cargo.toml"),
+        "[package]\nname = \"cached\"\nversion = \"0.1.0\"\nedition = \"2024\"\n",
+    )?;
+    std::fs::write(
+        workspace.join("Customs.toml"),
+        "[[regulation]]\njobs = [\"fmt\"]\nbuild-targets = [\"all\"]\nplatform-targets = [\"host\"]\n",
+    )?;
+    std::fs::write(workspace.join(".gitignore"), "/target\n/Cargo.lock\n")?;
+    std::fs::write(workspace.join("src/lib.rs"), "pub fn cached() {}\n")?;
+    let git = |args: &[&str]| -> Result<()> {
+        let status = std::process::Command::new("git")
+            .args([
+                "-c",
+                "user.name=customs",
+                "-c",
+                "user.email=customs@example.com",
+            ])
+            .args(args)
+            .current_dir(&workspace)
+            // Set when run from a git hook, but meant for the repository of customs
+            .env_remove("GIT_INDEX_FILE")
+            .status()?;
+        assert!(status.success(), "git {args:?} failed");
+        Ok(())
+    };
+    git(&["init", "-q"])?;
+    git(&["add", "."])?;
+    git(&["commit", "-q", "-m", "initial"])?;
+
+    let customs = |dry_run: bool| {
+        let mut cmd = cargo_bin_cmd!("cargo-customs");
+        cmd.current_dir(&workspace)
+            .env_remove("GIT_INDEX_FILE")
+            .arg("--result-cache")
+            .arg(&cache);
+        if dry_run {
+            cmd.arg("--dry-run");
+        }
+        cmd
+    };
+    customs(false).assert().success();
+
+    let output = customs(true).assert().success().get_output().stdout.clone();
+    assert_eq!(
+        String::from_utf8(output)?,
+        "cached/fmt/host/all (skipped: already succeeded for this commit)\n"
+    );
+
+    // Other global rustflags may change the result, the check runs again
+    let output = customs(true)
+        .env("RUSTFLAGS", "--cfg customs_fingerprint")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    assert_eq!(
+        String::from_utf8(output)?,
+        "cached/fmt/host/all: cargo fmt\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_cargo_config_is_passed_to_every_check() -> Result<()> {
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("./tests/workspace").args([
+        "--package",
+        "foo",
+        "--dry-run",
+        "--cargo-config",
+        "profile.dev.debug=0",
+    ]);
+
+    let output = String::from_utf8(cmd.assert().success().get_output().stdout.clone())?;
+    assert_eq!(
+        output,
+        "foo/fmt/host/all: cargo --config profile.dev.debug=0 fmt\n\
+         foo/build/host/all: cargo --config profile.dev.debug=0 build --all-targets\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_cargo_config_is_passed_to_jobs_run_by_customs() -> Result<()> {
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("./tests/workspace").args([
+        "plan",
+        "--package",
+        "foo",
+        "--add-job",
+        "reproducible",
+        "--cargo-config",
+        "profile.dev.debug=0",
+    ]);
+
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let plan: serde_json::Value = serde_json::from_slice(&output)?;
+    let check = plan["checks"]
+        .as_array()
+        .expect("checks must be a list")
+        .iter()
+        .find(|e| e["job"]["name"] == "reproducible")
+        .expect("reproducible check must be planned");
+    assert_eq!(
+        check["command"]["args"]
+            .as_array()
+            .expect("args must be a list")[..3],
+        ["--cargo-config", "profile.dev.debug=0", "reproducible"]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_cargo_config_is_rejected_for_hermetic_external_subcommands() -> Result<()> {
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("./tests/workspace").args([
+        "plan",
+        "--package",
+        "foo",
+        "--hermetic",
+        "--cargo-config",
+        "profile.dev.debug=0",
+    ]);
+
+    let output = cmd.assert().failure().get_output().stderr.clone();
+    assert!(
+        String::from_utf8(output)?
+            .contains("Cannot pass --cargo-config to check 'foo/fmt/host/all'")
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_expands_features_as_axis() -> Result<()> {
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("./tests/workspace").args([
+        "--package",
+        "foo",
+        "--dry-run",
+        "--set",
+        r#"feature-sets=["default", "--no-default-features", "a,b"]"#,
+    ]);
+
+    let output = String::from_utf8(cmd.assert().success().get_output().stdout.clone())?;
+    assert_eq!(
+        output,
+        "foo/fmt/host/all: cargo fmt\n\
+         foo/build/host/all: cargo build --all-targets\n\
+         foo/fmt/host/all/no-default-features: cargo fmt\n\
+         foo/build/host/all/no-default-features: cargo build --all-targets --no-default-features\n\
+         foo/fmt/host/all/a+b: cargo fmt\n\
+         foo/build/host/all/a+b: cargo build --all-targets --features a,b\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_marks_disabled_default_features_only_to_tell_checks_apart() -> Result<()> {
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("./tests/workspace").args([
+        "--package",
+        "foo",
+        "--dry-run",
+        "--set",
+        "default-features=false",
+    ]);
+
+    let output = String::from_utf8(cmd.assert().success().get_output().stdout.clone())?;
+    assert_eq!(
+        output,
+        "foo/fmt/host/all: cargo fmt\n\
+         foo/build/host/all: cargo build --all-targets --no-default-features\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_plan_shows_env_of_checks() -> Result<()> {
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("./tests/lonely-crate")
+        .args(["plan", "--show-env"]);
+
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let output = String::from_utf8(output)?;
+    assert!(output.contains("lonely-crate/check/host/lib\n  CUSTOMS_BUILD_TARGET=lib\n"));
+    assert!(output.contains("  cargo check --lib\n"));
+    Ok(())
+}
+
+#[test]
+fn test_customs_hermetic_plan_only_uses_absolute_paths() -> Result<()> {
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("./tests/workspace")
+        .args(["plan", "--hermetic"]);
+
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let plan: serde_json::Value = serde_json::from_slice(&output)?;
+    for check in plan["checks"].as_array().expect("checks must be a list") {
+        let program = check["command"]["program"]
+            .as_str()
+            .expect("program must be a string");
+        assert!(std::path::Path::new(program).is_absolute(), "{program}");
+    }
+    Ok(())
+}
+
+#[test]
+fn test_customs_hermetic_header_shows_pinned_cargo() -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let directory = fresh_directory("pinned-cargo")?;
+    let cargo = directory.join("cargo");
+    std::fs::write(
+        &cargo,
+        "#!/bin/sh\n[ \"$1\" = --version ] && echo \"cargo 0.0.0-pinned\" && exit\nexec cargo \"$@\"\n",
+    )?;
+    std::fs::set_permissions(&cargo, std::fs::Permissions::from_mode(0o755))?;
+
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("./tests/workspace")
+        .env("CARGO", &cargo)
+        .args(["--hermetic", "--package", "foo"]);
+
+    let output = cmd.assert().success().get_output().stderr.clone();
+    assert!(String::from_utf8(output)?.contains("cargo 0.0.0-pinned"));
+    Ok(())
+}
+
+#[test]
+fn test_customs_asks_rustup_only_for_targets_it_manages() -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let directory = fresh_directory("unasked-rustup")?;
+    let calls = directory.join("calls");
+    let rustup = directory.join("rustup");
+    std::fs::write(
+        &rustup,
+        format!("#!/bin/sh\necho \"$@\" >> {}\nexit 1\n", calls.display()),
+    )?;
+    std::fs::set_permissions(&rustup, std::fs::Permissions::from_mode(0o755))?;
+    let path = path_with_tools(directory.clone())?;
+
+    // Only the host
+    cargo_bin_cmd!("cargo-customs")
+        .current_dir("./tests/workspace")
+        .env("PATH", &path)
+        .args(["plan", "--package", "foo"])
+        .assert()
+        .success();
+    assert!(!calls.exists());
+
+    // A failing rustup is no reason to fail the plan
+    cargo_bin_cmd!("cargo-customs")
+        .current_dir("./tests/workspace")
+        .env("PATH", &path)
+        .args(["plan", "--package", "foo"])
+        .args(["--set", "platform-targets=[\"thumbv7em-none-eabihf\"]"])
+        .assert()
+        .success();
+    assert!(calls.exists());
+    Ok(())
+}
+
+#[test]
+fn test_customs_skips_regulations_for_other_host_os() -> Result<()> {
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("./tests/host-os");
+
+    let output = cmd.assert().success().get_output().stderr.clone();
+    assert!(
+        String::from_utf8(output)?.contains("host-os/test/host/all: not applicable to host OS")
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_forbid_skips_fails_on_skipped_checks() -> Result<()> {
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("./tests/host-os")
+        .args(["--forbid-skip-reason", "host-os"]);
+
+    cmd.assert().failure();
+    Ok(())
+}
+
+#[test]
+fn test_customs_problem_matcher_is_valid_json() -> Result<()> {
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("/tmp").arg("problem-matcher");
+
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let matcher: serde_json::Value = serde_json::from_slice(&output)?;
+    assert_eq!(matcher["problemMatcher"][0]["owner"], "cargo-customs");
+    Ok(())
+}
+
+#[test]
+fn test_customs_exec_runs_command_once_per_distinct_rendering() -> Result<()> {
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("./tests/workspace")
+        .args(["exec", "--", "echo", "{package}", "{target}"]);
+
+    let output = cmd.assert().success().get_output().stdout.clone();
+    assert_eq!(String::from_utf8(output)?, "bar host\nfoo host\n");
+    Ok(())
+}
+
+#[test]
+fn test_customs_exports_check_description_to_env() -> Result<()> {
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("./tests/workspace/foo").args([
+        "exec",
+        "--",
+        "sh",
+        "-c",
+        "echo $CUSTOMS_PACKAGE $CUSTOMS_TARGET $CUSTOMS_JOB $CUSTOMS_CHECK_ID",
+        "{job}",
+    ]);
+
+    let output = cmd.assert().success().get_output().stdout.clone();
+    assert_eq!(
+        String::from_utf8(output)?,
+        "foo host fmt foo/fmt/host/all\nfoo host build foo/build/host/all\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_shows_output_of_successful_checks_only_when_verbose() -> Result<()> {
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("./tests/workspace/foo");
+    let output = cmd.assert().success().get_output().stderr.clone();
+    assert!(!String::from_utf8(output)?.contains("Finished"));
+
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("./tests/workspace/foo").arg("-v");
+    let output = cmd.assert().success().get_output().stderr.clone();
+    assert!(String::from_utf8(output)?.contains("Finished"));
+    Ok(())
+}
+
+#[test]
+fn test_customs_reports_invalid_customs_file_with_path_and_hint() -> Result<()> {
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("./tests/broken-customs");
+
+    let output = cmd.assert().failure().get_output().stderr.clone();
+    let stderr = String::from_utf8(output)?;
+    assert!(stderr.contains("broken-customs/Customs.toml"), "{stderr}");
+    assert!(stderr.contains("use `[[regulation]]`"), "{stderr}");
+    Ok(())
+}
+
 // For a workspace, it is not required that every member has a customs file, but we should warn.
+
+#[test]
+fn test_customs_prunes_build_targets_the_package_does_not_have() -> Result<()> {
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("./tests/lonely-crate").args(["plan"]);
+
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let plan: serde_json::Value = serde_json::from_slice(&output)?;
+    let check = plan["checks"]
+        .as_array()
+        .expect("checks must be a list")
+        .iter()
+        .find(|e| e["id"] == "lonely-crate/build/host/bins")
+        .expect("bins must be planned");
+    assert_eq!(check["skipped"], "pruned");
+    Ok(())
+}
+
+#[test]
+fn test_customs_log_file_records_all_checks_in_order() -> Result<()> {
+    let directory = fresh_directory("log-file")?;
+    let log_file = directory.join("run.log");
+
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("./tests/workspace")
+        .args(["--package", "foo", "--log-file"])
+        .arg(&log_file);
+    cmd.assert().success();
+
+    let log = std::fs::read_to_string(&log_file)?;
+    let markers = log
+        .lines()
+        .filter_map(|e| e.split_once(' ').map(|e| e.1))
+        .filter(|e| e.starts_with("begin ") || e.starts_with("end "))
+        // Durations vary
+        .map(|e| e.split(" after ").next().unwrap_or_default())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        markers,
+        [
+            "begin foo/fmt/host/all",
+            "end foo/fmt/host/all passed",
+            "begin foo/build/host/all",
+            "end foo/build/host/all passed",
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_writes_prometheus_metrics() -> Result<()> {
+    let directory = fresh_directory("metrics")?;
+    let metrics_file = directory.join("customs.prom");
+
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("./tests/workspace")
+        .args(["--package", "foo", "--metrics-file"])
+        .arg(&metrics_file);
+    cmd.assert().success();
+
+    let metrics = std::fs::read_to_string(&metrics_file)?;
+    assert!(metrics.contains("customs_checks{status=\"passed\"} 2\n"));
+    assert!(metrics.contains(
+        "customs_check_duration_seconds{id=\"foo/fmt/host/all\",package=\"foo\",target=\"host\",\
+         build_target=\"all\",job=\"fmt\",status=\"passed\"} "
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_customs_warns_if_metrics_cannot_be_written() -> Result<()> {
+    let directory = fresh_directory("unwritable-metrics")?;
+    // A file where the directory of the metrics file would be
+    std::fs::write(directory.join("file"), "")?;
+
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("./tests/workspace")
+        .args(["--package", "foo", "--metrics-file"])
+        .arg(directory.join("file/customs.prom"));
+
+    let output = cmd.assert().success().get_output().stderr.clone();
+    assert!(String::from_utf8(output)?.contains("warning: writing metrics to"));
+    Ok(())
+}
+
+#[test]
+fn test_customs_logs_output_of_checks_in_flat_files() -> Result<()> {
+    let target_directory = fresh_directory("logs")?;
+
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("./tests/workspace")
+        .env("CARGO_TARGET_DIR", &target_directory)
+        .args(["--package", "foo"]);
+    cmd.assert().success();
+
+    let logs = target_directory.join("customs/logs");
+    assert!(logs.join("foo_build_host_all.log").is_file());
+    for entry in std::fs::read_dir(&logs)? {
+        assert!(entry?.file_type()?.is_file());
+    }
+    Ok(())
+}
+
+#[test]
+fn test_customs_writes_badge_of_suite() -> Result<()> {
+    let directory = fresh_directory("badge")?;
+    let badge_file = directory.join("badge.json");
+
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("./tests/suite")
+        .arg("--badge-file")
+        .arg(&badge_file)
+        .args(["suite", "smoke"]);
+    cmd.assert().success();
+
+    let badge: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&badge_file)?)?;
+    assert_eq!(
+        badge,
+        serde_json::json!({
+            "schemaVersion": 1,
+            "label": "customs smoke",
+            "message": "passing",
+            "color": "brightgreen",
+        })
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_reports_results_by_regulation_and_tag() -> Result<()> {
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("./tests/named").arg("--no-ci");
+
+    let output = String::from_utf8(cmd.assert().success().get_output().stderr.clone())?;
+    let summary = |group: &str| {
+        output
+            .lines()
+            .find_map(|e| e.trim().strip_prefix(group))
+            .map(|e| e.trim().to_string())
+    };
+    assert_eq!(
+        summary("regulation basics:").as_deref(),
+        Some("all green (2 passed)")
+    );
+    assert_eq!(
+        summary("tag lint:").as_deref(),
+        Some("all green (2 passed)")
+    );
+    assert_eq!(
+        summary("regulation checked:").as_deref(),
+        Some("all green (1 passed)")
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_shards_run_each_check_once() -> Result<()> {
+    let executed = |shard: &str| -> Result<Vec<String>> {
+        let mut cmd = cargo_bin_cmd!("cargo-customs");
+        cmd.current_dir("./tests/workspace")
+            .args(["plan", "--shard", shard]);
+        let output = cmd.assert().success().get_output().stdout.clone();
+        let plan: serde_json::Value = serde_json::from_slice(&output)?;
+        Ok(plan["checks"]
+            .as_array()
+            .expect("checks must be a list")
+            .iter()
+            .filter(|e| e["skipped"].is_null())
+            .map(|e| e["id"].as_str().expect("id must be a string").to_string())
+            .collect())
+    };
+
+    let mut all = [executed("1/2")?, executed("2/2")?].concat();
+    all.sort();
+    assert_eq!(
+        all,
+        [
+            "bar/build/host/all",
+            "bar/fmt/host/all",
+            "foo/build/host/all",
+            "foo/fmt/host/all",
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_compare_lists_new_failures_and_fixes() -> Result<()> {
+    let directory = fresh_directory("compare")?;
+    let run = |outcomes: &str| {
+        format!(
+            r#"{{"rustc_version": "rustc 1.0.0", "cargo_version": "cargo 1.0.0", "outcomes": {outcomes}}}"#
+        )
+    };
+    std::fs::write(
+        directory.join("a.json"),
+        run(
+            r#"{"x/test/host/all": {"passed": true, "seconds": 1.0}, "y/test/host/all": {"passed": false, "seconds": 1.0}}"#,
+        ),
+    )?;
+    std::fs::write(
+        directory.join("b.json"),
+        run(
+            r#"{"x/test/host/all": {"passed": false, "seconds": 1.0}, "y/test/host/all": {"passed": true, "seconds": 1.0}}"#,
+        ),
+    )?;
+
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.args(["compare"])
+        .arg(directory.join("a.json"))
+        .arg(directory.join("b.json"));
+
+    let output = String::from_utf8(cmd.assert().success().get_output().stdout.clone())?;
+    assert_eq!(output, "failed x/test/host/all\nfixed  y/test/host/all\n");
+    Ok(())
+}
+
+#[test]
+fn test_customs_budget_skips_checks_without_recorded_duration() -> Result<()> {
+    let mut cmd = cargo_bin_cmd!("cargo-customs");
+    cmd.current_dir("./tests/workspace/foo")
+        .args(["plan", "--budget", "0s"]);
+
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let plan: serde_json::Value = serde_json::from_slice(&output)?;
+    for check in plan["checks"].as_array().expect("checks must be a list") {
+        assert_eq!(check["skipped"], "budget", "{}", check["id"]);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_customs_runs_checks_of_a_package_one_after_another() -> Result<()> {
+    let log_file = fresh_directory("package-fence")?.join("run.log");
+
+    parallel_customs()?
+        .args(["--jobs", "4", "--log-file"])
+        .arg(&log_file)
+        .assert()
+        .success();
+
+    let spans = spans(&std::fs::read_to_string(&log_file)?);
+    assert!(overlap(&spans, "one/typos/host/all", "two/typos/host/all"));
+    for package in ["one", "two"] {
+        let typos = format!("{package}/typos/host/all");
+        let nap = format!("{package}/nap/host/all");
+        assert!(!overlap(&spans, &typos, &nap), "{spans:?}");
+    }
+    Ok(())
+}
+
+#[test]
+fn test_customs_no_package_fence_runs_checks_of_a_package_at_the_same_time() -> Result<()> {
+    let log_file = fresh_directory("no-package-fence")?.join("run.log");
+
+    parallel_customs()?
+        .args(["--jobs", "4", "--no-package-fence", "--log-file"])
+        .arg(&log_file)
+        .assert()
+        .success();
+
+    let spans = spans(&std::fs::read_to_string(&log_file)?);
+    assert!(
+        overlap(&spans, "one/typos/host/all", "one/nap/host/all"),
+        "{spans:?}"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_plan_diff_ignores_recorded_durations() -> Result<()> {
+    let plan = cargo_bin_cmd!("cargo-customs")
+        .current_dir("./tests/lonely-crate")
+        .args(["plan", "--format", "json"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let plan: serde_json::Value = serde_json::from_slice(&plan)?;
+    let durations = plan["checks"]
+        .as_array()
+        .expect("checks must be a list")
+        .iter()
+        .map(|e| (e["id"].as_str().unwrap_or_default().to_string(), 1.0))
+        .collect::<HashMap<_, _>>();
+    std::fs::create_dir_all("./tests/lonely-crate/target/customs")?;
+    std::fs::write(
+        "./tests/lonely-crate/target/customs/durations.json",
+        serde_json::to_string(&durations)?,
+    )?;
+
+    let output = cargo_bin_cmd!("cargo-customs")
+        .current_dir("./tests/lonely-crate")
+        .args(["plan-diff", "HEAD"])
+        // Set when run from a git hook, but meant for the repository, not the worktree
+        .env_remove("GIT_INDEX_FILE")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    assert_eq!(String::from_utf8(output)?, "");
+    Ok(())
+}
+
+#[test]
+fn test_customs_canary_never_touches_the_lock_file() -> Result<()> {
+    let lock_file = "./tests/lonely-crate/Cargo.lock";
+    let before = std::fs::metadata(lock_file)?.modified()?;
+
+    let output = cargo_bin_cmd!("cargo-customs")
+        .current_dir("./tests/lonely-crate")
+        .args([
+            "canary",
+            "--dependency=unknown",
+            "--version=2.0.0",
+            "--",
+            "--locked",
+        ])
+        .assert()
+        .failure()
+        .get_output()
+        .stderr
+        .clone();
+
+    let output = String::from_utf8(output)?;
+    assert!(
+        output.contains("canary of unknown (no update to 2.0.0)"),
+        "{output}"
+    );
+
+    assert_eq!(std::fs::metadata(lock_file)?.modified()?, before);
+    Ok(())
+}
+
+#[test]
+fn test_customs_isolated_cargo_home_stays_out_of_the_target_directory() -> Result<()> {
+    let target_directory = fresh_directory("isolated-cargo-home")?;
+
+    cargo_bin_cmd!("cargo-customs")
+        .current_dir("./tests/lonely-crate")
+        .env("CARGO_TARGET_DIR", &target_directory)
+        .args(["--set", "isolated-cargo-home=true"])
+        .assert()
+        .success();
+
+    assert!(target_directory.join("customs").is_dir());
+    assert!(!target_directory.join("customs/cargo-home").exists());
+    Ok(())
+}
+
+#[test]
+fn test_customs_budget_prefers_failed_checks_then_short_ones() -> Result<()> {
+    let target_directory = fresh_directory("budget")?;
+    let output_directory = target_directory.join("customs");
+    std::fs::create_dir_all(&output_directory)?;
+    std::fs::write(
+        output_directory.join("durations.json"),
+        r#"{"lonely-crate/check/host/lib": 4.0, "lonely-crate/check/host/bins": 5.0, "lonely-crate/test/host/all": 1.0}"#,
+    )?;
+    // Never passed, so only known from the last run
+    std::fs::write(
+        output_directory.join("run.json"),
+        r#"{"outcomes": {"lonely-crate/build/host/lib": {"passed": false, "seconds": 6.0}}}"#,
+    )?;
+
+    let output = cargo_bin_cmd!("cargo-customs")
+        .current_dir("./tests/lonely-crate")
+        .env("CARGO_TARGET_DIR", &target_directory)
+        .args(["plan", "--budget", "11s"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let plan: serde_json::Value = serde_json::from_slice(&output)?;
+    let selected = plan["checks"]
+        .as_array()
+        .expect("checks must be a list")
+        .iter()
+        .filter(|e| e["skipped"].is_null())
+        .filter_map(|e| e["id"].as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        selected,
+        [
+            "lonely-crate/check/host/lib",
+            "lonely-crate/build/host/lib",
+            "lonely-crate/test/host/all",
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_caches_metadata_in_configured_target_directory() -> Result<()> {
+    let workspace = fresh_directory("metadata-cache")?;
+    std::fs::write(
+        workspace.join("Cargo.toml"),
+        "[package]\nname = \"cached\"\nversion = \"0.1.0\"\nedition = \"2024\"\n",
+    )?;
+    std::fs::create_dir_all(workspace.join("src"))?;
+    std::fs::write(workspace.join("src/lib.rs"), "")?;
+    std::fs::write(
+        workspace.join("Customs.toml"),
+        "[[regulation]]\njobs = [\"fmt\"]\nplatform-targets = [\"host\"]\nbuild-targets = [\"all\"]\n",
+    )?;
+    std::fs::create_dir_all(workspace.join(".cargo"))?;
+    let config = workspace.join(".cargo/config.toml");
+    std::fs::write(&config, "[build]\ntarget-dir = \"elsewhere\"\n")?;
+
+    let plan = |directory: &str| -> Result<String> {
+        let output = cargo_bin_cmd!("cargo-customs")
+            .current_dir(workspace.join(directory))
+            .env("RUST_LOG", "debug")
+            .args(["plan"])
+            .assert()
+            .success()
+            .get_output()
+            .stderr
+            .clone();
+        Ok(String::from_utf8(output)?)
+    };
+    let cached = |log: &str| log.contains("Using cached metadata");
+
+    assert!(!cached(&plan(".")?));
+    assert!(workspace.join("elsewhere/customs/metadata.json").is_file());
+    // Each directory has its own entry
+    assert!(!cached(&plan("src")?));
+    assert!(cached(&plan(".")?));
+    assert!(cached(&plan("src")?));
+
+    std::fs::write(&config, "[build]\ntarget-dir = \"elsewhere\"\njobs = 1\n")?;
+    assert!(!cached(&plan(".")?));
+    Ok(())
+}
+
+#[test]
+fn test_customs_records_failed_snapshots_of_streamed_checks() -> Result<()> {
+    let target_directory = fresh_directory("snapshots")?;
+
+    customs_with_tools("snapshots")?
+        .env("CARGO_TARGET_DIR", &target_directory)
+        .arg("-v")
+        .assert()
+        .failure();
+
+    let pending = std::fs::read_to_string(target_directory.join("customs/insta-pending"))?;
+    assert!(pending.ends_with("snapshots"), "{pending}");
+    Ok(())
+}
+
+#[test]
+fn test_customs_docs_rs_always_builds_with_nightly() -> Result<()> {
+    let output = cargo_bin_cmd!("cargo-customs")
+        .current_dir("./tests/workspace")
+        .args(["plan", "--package", "foo", "--add-job", "docs-rs"])
+        .args(["--set", "toolchain=\"stable\""])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let plan: serde_json::Value = serde_json::from_slice(&output)?;
+    let toolchain = |id: &str| {
+        plan["checks"]
+            .as_array()
+            .expect("checks must be a list")
+            .iter()
+            .find(|e| e["id"] == id)
+            .map(|e| e["effective_toolchain"].clone())
+    };
+    assert_eq!(toolchain("foo/docs-rs/host/all"), Some("nightly".into()));
+    assert_eq!(toolchain("foo/build/host/all"), Some("stable".into()));
+    Ok(())
+}
+
+#[test]
+fn test_customs_required_features_are_resolved_transitively() -> Result<()> {
+    let output = cargo_bin_cmd!("cargo-customs")
+        .current_dir("./tests/required-features")
+        .args(["plan", "--format", "json"])
+        .assert()
+        .success()
+        .get_output()
+        .clone();
+
+    // `default` enables `extra` through `full`, so the bin is not left out
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(
+        !stderr.contains("without the features they require"),
+        "{stderr}"
+    );
+
+    let plan: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    let features = plan["checks"]
+        .as_array()
+        .expect("checks must be a list")
+        .iter()
+        .map(|e| e["features"].clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        features,
+        [serde_json::json!([]), serde_json::json!(["full"])]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_reports_mutants_summary_of_failed_check() -> Result<()> {
+    let target_directory = fresh_directory("mutants")?;
+
+    let output = customs_with_tools("mutants")?
+        .env("CARGO_TARGET_DIR", &target_directory)
+        .assert()
+        .failure()
+        .get_output()
+        .clone();
+
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(
+        stderr.contains("mutants/mutants/host/all: 3 mutants tested in 2s: 1 missed, 2 caught"),
+        "{stderr}"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_skips_customs_files_of_third_party_code() -> Result<()> {
+    // Ignored by the .gitignore of the fixture, hence not part of the repository
+    std::fs::write(
+        "./tests/exclusions/ignored/Customs.toml",
+        "[[regulation]]\njobs = [\"fmt\"]\nbuild-targets = [\"all\"]\nplatform-targets = [\"host\"]\n",
+    )?;
+
+    let output = cargo_bin_cmd!("cargo-customs")
+        .current_dir("./tests/exclusions")
+        .args(["plan", "--format", "json"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    // Only the member in `crates/vendor`, not the vendored crate, the ignored one or the submodule
+    let plan: serde_json::Value = serde_json::from_slice(&output)?;
+    let ids = plan["checks"]
+        .as_array()
+        .expect("checks must be a list")
+        .iter()
+        .map(|e| e["id"].as_str().expect("id must be a string"))
+        .collect::<Vec<_>>();
+    assert_eq!(ids, ["own/fmt/host/all"]);
+    Ok(())
+}
+
+#[test]
+fn test_customs_reproducible_builds_in_a_directory_per_package() -> Result<()> {
+    let workspace = fresh_directory("reproducible")?;
+    std::fs::write(
+        workspace.join("Cargo.toml"),
+        "[package]\nname = \"unbuildable\"\nversion = \"0.1.0\"\nedition = \"2024\"\n",
+    )?;
+    std::fs::create_dir_all(workspace.join("src"))?;
+    std::fs::write(workspace.join("src/lib.rs"), "pub fn unbuildable( {}\n")?;
+
+    cargo_bin_cmd!("cargo-customs")
+        .current_dir(&workspace)
+        .arg("reproducible")
+        .assert()
+        .failure();
+
+    // Left behind by the failed build
+    assert!(
+        workspace
+            .join("target/customs/reproducible/unbuildable")
+            .is_dir()
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_missing_stdin_file_fails_only_its_check() -> Result<()> {
+    let target_directory = fresh_directory("stdin-file")?;
+
+    let output = customs_with_tools("parallel")?
+        .env("CARGO_TARGET_DIR", &target_directory)
+        .args(["--package", "one", "--keep-going"])
+        .args(["--set", "jobs={typos={stdin=\"file:missing\"},nap={}}"])
+        .assert()
+        .failure()
+        .get_output()
+        .stderr
+        .clone();
+
+    let output = String::from_utf8(output)?;
+    assert!(output.contains("nap    passed"), "{output}");
+    assert!(output.contains("typos  failed"), "{output}");
+    Ok(())
+}
+
+/// A package without a customs file in a fresh directory.
+fn fresh_package(name: &str) -> Result<PathBuf> {
+    let directory = fresh_directory(name)?;
+    std::fs::write(
+        directory.join("Cargo.toml"),
+        format!("[package]\nname = \"{name}\"\nversion = \"0.1.0\"\nedition = \"2024\"\n"),
+    )?;
+    std::fs::create_dir_all(directory.join("src"))?;
+    std::fs::write(directory.join("src/lib.rs"), "")?;
+    Ok(directory)
+}
+
+#[test]
+fn test_customs_add_creates_and_extends_customs_file() -> Result<()> {
+    let package = fresh_package("add")?;
+
+    for job in ["fmt", "clippy"] {
+        cargo_bin_cmd!("cargo-customs")
+            .current_dir(&package)
+            .args(["add", "--job", job, "--target", "host"])
+            .assert()
+            .success();
+    }
+
+    assert_eq!(
+        std::fs::read_to_string(package.join("Customs.toml"))?,
+        "[[regulation]]\njobs = [\"fmt\"]\nplatform-targets = [\"host\"]\n\n\
+         [[regulation]]\njobs = [\"clippy\"]\nplatform-targets = [\"host\"]\n"
+    );
+    Ok(())
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_customs_add_leaves_json_customs_file_alone() -> Result<()> {
+    let package = fresh_package("add-json")?;
+    std::fs::write(package.join("Customs.json"), "{\"regulation\": []}")?;
+
+    let output = cargo_bin_cmd!("cargo-customs")
+        .current_dir(&package)
+        .args(["add", "--job", "fmt"])
+        .assert()
+        .failure()
+        .get_output()
+        .stderr
+        .clone();
+
+    assert!(String::from_utf8(output)?.contains("only a Customs.toml is edited"));
+    assert!(!package.join("Customs.toml").exists());
+    Ok(())
+}
+
+#[cfg(feature = "yaml")]
+#[test]
+fn test_customs_accepts_yaml_customs_file() -> Result<()> {
+    let package = fresh_package("yaml")?;
+    std::fs::write(
+        package.join("Customs.yaml"),
+        "regulation:\n  - jobs: [fmt, check]\n    platform-targets: [host]\n    build-targets: [lib]\n",
+    )?;
+
+    let output = cargo_bin_cmd!("cargo-customs")
+        .current_dir(&package)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    assert_eq!(
+        String::from_utf8(output)?,
+        "yaml/fmt/host/lib: cargo fmt\nyaml/check/host/lib: cargo check --lib\n"
+    );
+
+    let output = cargo_bin_cmd!("cargo-customs")
+        .current_dir(&package)
+        .args(["add", "--job", "fmt"])
+        .assert()
+        .failure()
+        .get_output()
+        .stderr
+        .clone();
+    assert!(String::from_utf8(output)?.contains("only a Customs.toml is edited"));
+    assert!(!package.join("Customs.toml").exists());
+    Ok(())
+}
+
+#[test]
+fn test_customs_config_file_is_passed_only_to_its_regulation() -> Result<()> {
+    let package = fresh_package("config-file")?;
+    std::fs::write(
+        package.join("Customs.toml"),
+        "[[regulation]]\njobs = [\"check\"]\nplatform-targets = [\"host\"]\nbuild-targets = [\"all\"]\n\
+         config-file = \".cargo/embedded-config.toml\"\n\n\
+         [[regulation]]\njobs = [\"build\"]\nplatform-targets = [\"host\"]\nbuild-targets = [\"all\"]\n",
+    )?;
+
+    let output = cargo_bin_cmd!("cargo-customs")
+        .current_dir(&package)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let config = package.join(".cargo/embedded-config.toml");
+    assert_eq!(
+        String::from_utf8(output)?,
+        format!(
+            "config-file/check/host/all: cargo check --all-targets --config {}\n\
+             config-file/build/host/all: cargo build --all-targets\n",
+            config.display()
+        )
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_warns_about_shadowed_and_duplicate_regulations() -> Result<()> {
+    let package = fresh_package("lint")?;
+    std::fs::write(
+        package.join("Customs.toml"),
+        "[[regulation]]\njobs = [\"check\", \"build\"]\nplatform-targets = [\"host\"]\nbuild-targets = [\"all\"]\n\n\
+         [[regulation]]\njobs = [\"build\", \"build\"]\nplatform-targets = [\"host\"]\nbuild-targets = [\"all\"]\n",
+    )?;
+
+    let output = cargo_bin_cmd!("cargo-customs")
+        .current_dir(&package)
+        .env("RUST_LOG", "warn")
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .get_output()
+        .stderr
+        .clone();
+
+    let output = String::from_utf8(output)?;
+    let customs = package.join("Customs.toml");
+    assert!(output.contains(&format!(
+        "{}:6:1: job 'build' is listed more than once",
+        customs.display()
+    )));
+    assert!(output.contains(&format!(
+        "{}:6:1: regulation is shadowed by the regulation at line 1",
+        customs.display()
+    )));
+    assert!(!output.contains(":1:1:"));
+    Ok(())
+}
+
+#[test]
+fn test_customs_orders_jobs_of_a_regulation_for_artifact_reuse() -> Result<()> {
+    let package = fresh_package("reuse")?;
+    std::fs::write(
+        package.join("Customs.toml"),
+        "[[regulation]]\njobs = [\"test\", \"clippy\", \"check\"]\nplatform-targets = [\"host\"]\nbuild-targets = [\"lib\", \"bins\"]\n",
+    )?;
+
+    let output = cargo_bin_cmd!("cargo-customs")
+        .current_dir(&package)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_eq!(
+        String::from_utf8(output)?,
+        "reuse/check/host/lib: cargo check --lib\n\
+         reuse/clippy/host/lib: cargo clippy --lib\n\
+         reuse/test/host/lib: cargo test --lib\n\
+         reuse/check/host/bins (skipped: package has no such build target)\n\
+         reuse/clippy/host/bins (skipped: package has no such build target)\n\
+         reuse/test/host/bins (skipped: package has no such build target)\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_passes_compiler_options_of_regulation_as_environment() -> Result<()> {
+    let package = fresh_package("compiler")?;
+    std::fs::write(
+        package.join("Customs.toml"),
+        "[[regulation]]\njobs = [\"check\"]\nplatform-targets = [\"host\"]\nbuild-targets = [\"all\"]\n\
+         rustc-wrapper = \"sccache\"\nrustc = \"bin/rustc\"\ncfgs = [\"loom\"]\n",
+    )?;
+
+    let output = cargo_bin_cmd!("cargo-customs")
+        .current_dir(&package)
+        .env_remove("RUSTFLAGS")
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_eq!(
+        String::from_utf8(output)?,
+        format!(
+            "compiler/check/host/all/cfg=loom: RUSTC={} RUSTC_WRAPPER=sccache RUSTFLAGS='--cfg loom' cargo check --all-targets\n",
+            package.join("bin/rustc").display()
+        )
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_no_std_preset_checks_without_default_features_on_bare_metal() -> Result<()> {
+    let package = fresh_package("no-std")?;
+    std::fs::write(
+        package.join("Customs.toml"),
+        "[[regulation]]\npreset = \"no-std\"\n",
+    )?;
+
+    let output = cargo_bin_cmd!("cargo-customs")
+        .current_dir(&package)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_eq!(
+        String::from_utf8(output)?,
+        "no-std/check/thumbv6m-none-eabi/lib/no-std: \
+         cargo check --lib --target=thumbv6m-none-eabi --no-default-features\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_compares_public_api_against_baseline() -> Result<()> {
+    let package = fresh_package("public-api")?;
+    std::fs::create_dir_all(package.join("baseline"))?;
+    std::fs::write(
+        package.join("Customs.toml"),
+        "[[regulation]]\nplatform-targets = [\"host\"]\nbuild-targets = [\"lib\"]\n\
+         jobs = { semver-checks = { baseline = \"1.0.0\" }, public-api = { baseline = \"0.9.0\" } }\n\n\
+         [[regulation]]\nplatform-targets = [\"host\"]\nbuild-targets = [\"lib\"]\n\
+         jobs = { semver-checks = { baseline = \"baseline\" } }\n",
+    )?;
+
+    let output = cargo_bin_cmd!("cargo-customs")
+        .current_dir(&package)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_eq!(
+        String::from_utf8(output)?,
+        format!(
+            "public-api/public-api/host/lib: cargo public-api diff 0.9.0 --deny=changed --deny=removed\n\
+             public-api/semver-checks/host/lib: cargo semver-checks check-release --baseline-version 1.0.0\n\
+             public-api/semver-checks/host/lib#2: cargo semver-checks check-release --baseline-root {}\n",
+            package.join("baseline").display()
+        )
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_runs_typos_in_the_package_directory() -> Result<()> {
+    let output = customs_with_tools("typos")?
+        .assert()
+        .failure()
+        .get_output()
+        .clone();
+
+    let package = std::fs::canonicalize("./tests/typos")?;
+    assert_eq!(
+        String::from_utf8(output.stdout)?,
+        format!(
+            "typos --format brief in {}\nsrc/lib.rs:1:5: `Teh` -> `The`\n",
+            package.display()
+        )
+    );
+    Ok(())
+}
+
+/// A package in a fresh directory whose customs file runs `cargo <tool>`, a shell script in its `bin` directory,
+/// along with the `PATH` to find it.
+fn package_with_tool(name: &str, tool: &str, script: &str) -> Result<(PathBuf, OsString)> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let package = fresh_package(name)?;
+    std::fs::write(
+        package.join("Customs.toml"),
+        format!(
+            "[[regulation]]\njobs = [\"{tool}\"]\nplatform-targets = [\"host\"]\nbuild-targets = [\"all\"]\n"
+        ),
+    )?;
+    let bin = package.join("bin");
+    std::fs::create_dir_all(&bin)?;
+    let file = bin.join(format!("cargo-{tool}"));
+    std::fs::write(&file, format!("#!/bin/sh\n{script}"))?;
+    std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o755))?;
+    Ok((package, path_with_tools(bin)?))
+}
+
+#[test]
+fn test_customs_passes_color_choice_to_checks() -> Result<()> {
+    let (package, path) = package_with_tool(
+        "color",
+        "color",
+        "echo \"CARGO_TERM_COLOR=$CARGO_TERM_COLOR\"\n",
+    )?;
+
+    for (choice, escapes) in [("always", true), ("never", false)] {
+        let output = cargo_bin_cmd!("cargo-customs")
+            .current_dir(&package)
+            .env("PATH", &path)
+            .env_remove("CARGO_TERM_COLOR")
+            .args(["--verbose", "--color", choice])
+            .assert()
+            .success()
+            .get_output()
+            .clone();
+
+        let stdout = String::from_utf8(output.stdout)?;
+        assert!(
+            stdout.contains(&format!("CARGO_TERM_COLOR={choice}\n")),
+            "{stdout}"
+        );
+        assert_eq!(String::from_utf8(output.stderr)?.contains('\x1b'), escapes);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_customs_isolated_tmp_is_a_directory_per_check_removed_afterwards() -> Result<()> {
+    let (package, path) = package_with_tool(
+        "tmp",
+        "scratch",
+        "echo \"$TMPDIR\"\ntouch \"$TMPDIR/scratch\" || exit 1\n",
+    )?;
+    let customs = package.join("Customs.toml");
+    std::fs::write(
+        &customs,
+        std::fs::read_to_string(&customs)? + "isolated-tmp = true\n",
+    )?;
+
+    let output = cargo_bin_cmd!("cargo-customs")
+        .current_dir(&package)
+        .env("PATH", &path)
+        .arg("--verbose")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let tmp = package.join("target/customs/tmp/tmp_scratch_host_all");
+    assert_eq!(String::from_utf8(output)?, format!("{}\n", tmp.display()));
+    assert!(!tmp.exists());
+    Ok(())
+}
+
+#[test]
+fn test_customs_blesses_ui_tests_on_the_host_only() -> Result<()> {
+    let package = fresh_package("ui")?;
+    std::fs::create_dir_all(package.join("tests"))?;
+    std::fs::write(package.join("tests/ui.rs"), "")?;
+    std::fs::write(
+        package.join("Customs.toml"),
+        "[[regulation]]\njobs = [\"test\"]\nplatform-targets = [\"host\", \"thumbv7em-none-eabihf\"]\n\
+         build-targets = [\"ui-test:ui\"]\n",
+    )?;
+
+    let output = cargo_bin_cmd!("cargo-customs")
+        .current_dir(&package)
+        .args(["--dry-run", "--bless"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_eq!(
+        String::from_utf8(output)?,
+        "ui/test/host/ui-test:ui: TRYBUILD=overwrite cargo test --test=ui\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_reduces_regulations_for_local_runs() -> Result<()> {
+    let package = fresh_package("local")?;
+    std::fs::write(
+        package.join("Customs.toml"),
+        "[[regulation]]\njobs = [\"check\"]\nplatform-targets = [\"host\", \"thumbv7em-none-eabihf\"]\n\
+         build-targets = [\"lib\"]\nlocal-reduction = [\"first-platform-target\"]\n",
+    )?;
+    let customs = |args: &[&str]| -> Result<String> {
+        let output = cargo_bin_cmd!("cargo-customs")
+            .current_dir(&package)
+            .arg("--dry-run")
+            .args(args)
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+        Ok(String::from_utf8(output)?)
+    };
+
+    assert_eq!(
+        customs(&["--no-ci"])?,
+        "local/check/host/lib: cargo check --lib\n\
+         local/check/thumbv7em-none-eabihf/lib (skipped: reduced for local runs, run with --full)\n"
+    );
+    let everything = "local/check/host/lib: cargo check --lib\n\
+         local/check/thumbv7em-none-eabihf/lib: cargo check --lib --target=thumbv7em-none-eabihf\n";
+    assert_eq!(customs(&["--no-ci", "--full"])?, everything);
+    assert_eq!(customs(&["--ci"])?, everything);
+    Ok(())
+}
+
+#[test]
+fn test_customs_groups_output_of_checks_on_github_actions() -> Result<()> {
+    let (package, path) = package_with_tool("ci", "ok", "echo ok\n")?;
+    let customs = |args: &[&str]| -> Result<String> {
+        let output = cargo_bin_cmd!("cargo-customs")
+            .current_dir(&package)
+            .env("PATH", &path)
+            .env("GITHUB_ACTIONS", "true")
+            .args(args)
+            .assert()
+            .success()
+            .get_output()
+            .stderr
+            .clone();
+        Ok(String::from_utf8(output)?)
+    };
+
+    let detected = customs(&[])?;
+    assert!(
+        detected.contains("::group::Checking ci/ok/host/all\n"),
+        "{detected}"
+    );
+    assert!(detected.contains("::endgroup::\n"), "{detected}");
+
+    let disabled = customs(&["--no-ci"])?;
+    assert!(!disabled.contains("::group::"), "{disabled}");
+    Ok(())
+}
+
+#[test]
+fn test_customs_locked_and_offline_per_regulation_and_globally() -> Result<()> {
+    let package = fresh_package("lock-flags")?;
+    std::fs::write(
+        package.join("Customs.toml"),
+        "[[regulation]]\njobs = [\"build\"]\nplatform-targets = [\"host\"]\nbuild-targets = [\"lib\"]\nlocked = true\n\n\
+         [[regulation]]\njobs = [\"check\", \"fmt\"]\nplatform-targets = [\"host\"]\nbuild-targets = [\"lib\"]\noffline = true\n",
+    )?;
+    let customs = |args: &[&str]| -> Result<String> {
+        let output = cargo_bin_cmd!("cargo-customs")
+            .current_dir(&package)
+            .arg("--dry-run")
+            .args(args)
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+        Ok(String::from_utf8(output)?)
+    };
+
+    assert_eq!(
+        customs(&[])?,
+        "lock-flags/build/host/lib: cargo build --lib --locked\n\
+         lock-flags/fmt/host/lib: cargo fmt\n\
+         lock-flags/check/host/lib: cargo check --lib --offline\n"
+    );
+    assert_eq!(
+        customs(&["--locked"])?,
+        "lock-flags/build/host/lib: cargo build --lib --locked\n\
+         lock-flags/fmt/host/lib: cargo fmt\n\
+         lock-flags/check/host/lib: cargo check --lib --locked --offline\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_import_appends_regulations_of_workflow() -> Result<()> {
+    let package = fresh_package("import")?;
+    let workflow = package.join("ci.yml");
+    std::fs::write(
+        &workflow,
+        "jobs:\n  ci:\n    steps:\n\
+         \x20     - run: cargo fmt --check\n\
+         \x20     - run: cargo build --target thumbv7em-none-eabihf && cargo check --target thumbv7em-none-eabihf\n\
+         \x20     - run: cargo test --target ${{ matrix.target }}\n\
+         \x20     - run: cargo publish\n",
+    )?;
+
+    cargo_bin_cmd!("cargo-customs")
+        .current_dir(&package)
+        .arg("import")
+        .arg("--from")
+        .arg(&workflow)
+        .assert()
+        .success();
+
+    assert_eq!(
+        std::fs::read_to_string(package.join("Customs.toml"))?,
+        "[[regulation]]\nplatform-targets = [\"host\"]\nbuild-targets = [\"all\"]\n\n\
+         [regulation.jobs]\nfmt = { args = [\"--check\"] }\n\n\
+         [[regulation]]\njobs = [\"build\", \"check\"]\nplatform-targets = [\"thumbv7em-none-eabihf\"]\n\
+         build-targets = [\"lib\", \"bins\"]\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_overrides_jobs_on_particular_platform_targets() -> Result<()> {
+    let package = fresh_package("target-overrides")?;
+    std::fs::write(
+        package.join("Customs.toml"),
+        "[[regulation]]\njobs = { test = { args = [\"--quiet\"] } }\n\
+         platform-targets = [\"host\", \"thumbv7em-none-eabihf\"]\nbuild-targets = [\"lib\"]\n\n\
+         [regulation.job-overrides.\"thumbv7em-none-eabihf\".test]\n\
+         cargo-args = [\"--no-run\"]\nargs = [\"--nocapture\"]\n",
+    )?;
+
+    let output = cargo_bin_cmd!("cargo-customs")
+        .current_dir(&package)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_eq!(
+        String::from_utf8(output)?,
+        "target-overrides/test/host/lib: cargo test --lib -- --quiet\n\
+         target-overrides/test/thumbv7em-none-eabihf/lib: \
+         cargo test --lib --target=thumbv7em-none-eabihf --no-run -- --quiet --nocapture\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_overrides_jobs_on_particular_build_targets() -> Result<()> {
+    let package = fresh_package("build-target-overrides")?;
+    std::fs::create_dir_all(package.join("tests"))?;
+    std::fs::write(package.join("tests/it.rs"), "")?;
+    std::fs::write(
+        package.join("Customs.toml"),
+        "[[regulation]]\nplatform-targets = [\"host\"]\nbuild-targets = [\"lib\", \"tests\"]\n\n\
+         [regulation.jobs.test]\nargs = [\"--quiet\"]\n\n\
+         [regulation.jobs.test.build-target-overrides]\n\
+         tests = { cargo-args = [\"--no-fail-fast\"], args = [\"--ignored\"], replace-args = true }\n",
+    )?;
+
+    let output = cargo_bin_cmd!("cargo-customs")
+        .current_dir(&package)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_eq!(
+        String::from_utf8(output)?,
+        "build-target-overrides/test/host/lib: cargo test --lib -- --quiet\n\
+         build-target-overrides/test/host/tests: cargo test --tests --no-fail-fast -- --ignored\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_checks_proc_macros_for_the_host_only() -> Result<()> {
+    let package = fresh_package("proc-macro")?;
+    let manifest = package.join("Cargo.toml");
+    std::fs::write(
+        &manifest,
+        std::fs::read_to_string(&manifest)? + "\n[lib]\nproc-macro = true\n",
+    )?;
+    std::fs::write(
+        package.join("Customs.toml"),
+        "[[regulation]]\njobs = [\"check\"]\nplatform-targets = [\"host\", \"thumbv7em-none-eabihf\"]\n\
+         build-targets = [\"lib\"]\n",
+    )?;
+
+    let output = cargo_bin_cmd!("cargo-customs")
+        .current_dir(&package)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_eq!(
+        String::from_utf8(output)?,
+        "proc-macro/check/host/lib: cargo check --lib\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_retries_only_failed_tests_and_reports_them_as_flaky() -> Result<()> {
+    let package = fresh_package("flaky")?;
+    std::fs::write(
+        package.join("src/lib.rs"),
+        "#[test]\nfn flaky() {\n\
+         \x20   let marker = std::path::Path::new(env!(\"CARGO_MANIFEST_DIR\")).join(\"attempted\");\n\
+         \x20   if !marker.exists() {\n\
+         \x20       std::fs::write(&marker, \"\").unwrap();\n\
+         \x20       panic!(\"first attempt\");\n\
+         \x20   }\n}\n\n\
+         #[test]\nfn stable() {}\n",
+    )?;
+    std::fs::write(
+        package.join("Customs.toml"),
+        "[[regulation]]\njobs = { test = { retry-failed-tests = 2 } }\nplatform-targets = [\"host\"]\n\
+         build-targets = [\"lib\"]\n",
+    )?;
+
+    let output = cargo_bin_cmd!("cargo-customs")
+        .current_dir(&package)
+        .assert()
+        .success()
+        .get_output()
+        .stderr
+        .clone();
+
+    let stderr = String::from_utf8(output)?;
+    assert!(
+        stderr.contains("flaky/test/host/lib: flaky tests passed on retry: flaky\n"),
+        "{stderr}"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_passes_test_threads_to_libtest_and_nextest() -> Result<()> {
+    let package = fresh_package("test-threads")?;
+    std::fs::write(
+        package.join("Customs.toml"),
+        "[[regulation]]\nplatform-targets = [\"host\"]\nbuild-targets = [\"lib\"]\n\
+         jobs = { test = { test-threads = 1 }, nextest = { test-threads = 4 } }\n",
+    )?;
+
+    let output = cargo_bin_cmd!("cargo-customs")
+        .current_dir(&package)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_eq!(
+        String::from_utf8(output)?,
+        "test-threads/test/host/lib: cargo test --lib -- --test-threads=1\n\
+         test-threads/nextest/host/lib: cargo nextest run --test-threads=4 --lib\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_expands_boards_into_targets_features_and_runners() -> Result<()> {
+    let package = fresh_package("boards")?;
+    let manifest = package.join("Cargo.toml");
+    std::fs::write(
+        &manifest,
+        std::fs::read_to_string(&manifest)? + "\n[features]\nstm32 = []\nrp = []\n",
+    )?;
+    std::fs::write(
+        package.join("boards.toml"),
+        "[nucleo-f446]\ntarget = \"thumbv7em-none-eabihf\"\nfeatures = [\"stm32\"]\n\
+         runner = \"probe-rs run --chip STM32F446RETx\"\n\n\
+         [rp2040]\ntarget = \"thumbv6m-none-eabi\"\nfeatures = [\"rp\"]\n",
+    )?;
+    std::fs::write(
+        package.join("Customs.toml"),
+        "[[regulation]]\njobs = [\"test\"]\nboards = [\"nucleo-f446\", \"rp2040\"]\nbuild-targets = [\"lib\"]\n",
+    )?;
+
+    let output = cargo_bin_cmd!("cargo-customs")
+        .current_dir(&package)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_eq!(
+        String::from_utf8(output)?,
+        "boards/test/thumbv7em-none-eabihf/lib/stm32/board=nucleo-f446: \
+         CARGO_TARGET_THUMBV7EM_NONE_EABIHF_RUNNER='probe-rs run --chip STM32F446RETx' \
+         cargo test --lib --target=thumbv7em-none-eabihf --features stm32\n\
+         boards/test/thumbv6m-none-eabi/lib/rp/board=rp2040: \
+         cargo test --lib --target=thumbv6m-none-eabi --features rp\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_runs_each_binary_and_example_with_arguments() -> Result<()> {
+    let package = fresh_package("run")?;
+    std::fs::create_dir_all(package.join("src/bin"))?;
+    std::fs::create_dir_all(package.join("examples"))?;
+    for file in ["src/bin/tool.rs", "examples/demo.rs"] {
+        std::fs::write(package.join(file), "fn main() {}\n")?;
+    }
+    std::fs::write(
+        package.join("Customs.toml"),
+        "[[regulation]]\njobs = { run = { args = [\"--help\"] } }\nplatform-targets = [\"host\"]\n\
+         build-targets = [\"bins\", \"examples\"]\n",
+    )?;
+
+    let output = cargo_bin_cmd!("cargo-customs")
+        .current_dir(&package)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_eq!(
+        String::from_utf8(output)?,
+        "run/run/host/bin:tool: cargo run --bin=tool -- --help\n\
+         run/run/host/example:demo: cargo run --example=demo -- --help\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_workspace_deps_reports_path_dependencies_without_version() -> Result<()> {
+    let workspace = fresh_directory("workspace-deps")?;
+    let files = [
+        (
+            "Cargo.toml",
+            "[workspace]\nmembers = [\"a\", \"b\"]\nresolver = \"3\"\n",
+        ),
+        (
+            "a/Cargo.toml",
+            "[package]\nname = \"a\"\nversion = \"0.2.0\"\nedition = \"2024\"\n",
+        ),
+        (
+            "b/Cargo.toml",
+            "[package]\nname = \"b\"\nversion = \"0.1.0\"\nedition = \"2024\"\n\n\
+             [build-dependencies]\na = { path = \"../a\" }\n\n\
+             [dev-dependencies]\na = { path = \"../a\" }\n",
+        ),
+        (
+            "b/Customs.toml",
+            "[[regulation]]\njobs = [\"workspace-deps\"]\nplatform-targets = [\"host\"]\nbuild-targets = [\"all\"]\n",
+        ),
+        ("a/src/lib.rs", ""),
+        ("b/src/lib.rs", ""),
+    ];
+    for (file, content) in files {
+        let path = workspace.join(file);
+        std::fs::create_dir_all(path.parent().expect("files are in the workspace"))?;
+        std::fs::write(path, content)?;
+    }
+
+    let output = cargo_bin_cmd!("cargo-customs")
+        .current_dir(workspace.join("b"))
+        .assert()
+        .failure()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_eq!(
+        String::from_utf8(output)?,
+        "b: path dependency on a has no version\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_manifest_policy_reports_each_violation() -> Result<()> {
+    let package = fresh_package("manifest-policy")?;
+    let manifest = package.join("Cargo.toml");
+    std::fs::write(
+        &manifest,
+        std::fs::read_to_string(&manifest)?.replace(
+            "[package]\n",
+            "[package]\nlicense = \"MIT\"\nauthors = [\"someone\"]\n",
+        ),
+    )?;
+    std::fs::write(
+        package.join("Customs.toml"),
+        "[[regulation]]\nplatform-targets = [\"host\"]\nbuild-targets = [\"all\"]\n\n\
+         [regulation.jobs.manifest-policy]\n\
+         args = [\"--require=license,repository\", \"--ban=authors\", \"--edition=2021\", \"--workspace-lints\"]\n",
+    )?;
+
+    let output = cargo_bin_cmd!("cargo-customs")
+        .current_dir(&package)
+        .assert()
+        .failure()
+        .get_output()
+        .stdout
+        .clone();
+
+    let manifest = manifest.display();
+    assert_eq!(
+        String::from_utf8(output)?,
+        format!(
+            "{manifest}: `repository` is required\n\
+             {manifest}: `authors` must not be set\n\
+             {manifest}: edition 2024 instead of 2021\n\
+             {manifest}: `[lints] workspace = true` is required\n"
+        )
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_ignores_rust_version_per_regulation_and_globally() -> Result<()> {
+    let package = fresh_package("rust-version")?;
+    std::fs::write(
+        package.join("Customs.toml"),
+        "[[regulation]]\njobs = [\"check\"]\nplatform-targets = [\"host\"]\nbuild-targets = [\"lib\"]\n\
+         toolchain = \"1.80\"\nignore-rust-version = true\n\n\
+         [[regulation]]\njobs = [\"build\", \"fmt\"]\nplatform-targets = [\"host\"]\nbuild-targets = [\"lib\"]\n",
+    )?;
+    let customs = |args: &[&str]| -> Result<String> {
+        let output = cargo_bin_cmd!("cargo-customs")
+            .current_dir(&package)
+            .arg("--dry-run")
+            .args(args)
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+        Ok(String::from_utf8(output)?)
+    };
+
+    assert_eq!(
+        customs(&[])?,
+        "rust-version/check/host/lib: RUSTUP_TOOLCHAIN=1.80 cargo check --lib --ignore-rust-version\n\
+         rust-version/fmt/host/lib: cargo fmt\n\
+         rust-version/build/host/lib: cargo build --lib\n"
+    );
+    assert_eq!(
+        customs(&["--ignore-rust-version"])?,
+        "rust-version/check/host/lib: RUSTUP_TOOLCHAIN=1.80 cargo check --lib --ignore-rust-version\n\
+         rust-version/fmt/host/lib: cargo fmt\n\
+         rust-version/build/host/lib: cargo build --lib --ignore-rust-version\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_sets_cargo_verbosity_per_job() -> Result<()> {
+    let package = fresh_package("cargo-verbosity")?;
+    std::fs::write(
+        package.join("Customs.toml"),
+        "[[regulation]]\nplatform-targets = [\"host\"]\nbuild-targets = [\"lib\"]\n\
+         jobs = { check = { cargo-verbosity = \"quiet\" }, build = { cargo-verbosity = \"very-verbose\" }, \
+         doc = { cargo-verbosity = \"normal\" } }\n",
+    )?;
+
+    let output = cargo_bin_cmd!("cargo-customs")
+        .current_dir(&package)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_eq!(
+        String::from_utf8(output)?,
+        "cargo-verbosity/check/host/lib: cargo check --lib -q\n\
+         cargo-verbosity/build/host/lib: cargo build --lib -vv\n\
+         cargo-verbosity/doc/host/lib: RUSTDOCFLAGS='-D rustdoc::broken_intra_doc_links' cargo doc --lib\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_checks_with_the_toolchain_it_was_launched_with() -> Result<()> {
+    let package = fresh_package("launch-toolchain")?;
+    std::fs::write(
+        package.join("Customs.toml"),
+        "[[regulation]]\njobs = [\"check\"]\nplatform-targets = [\"host\"]\nbuild-targets = [\"lib\"]\n\n\
+         [[regulation]]\njobs = [\"build\"]\nplatform-targets = [\"host\"]\nbuild-targets = [\"lib\"]\n\
+         toolchain = \"nightly\"\n",
+    )?;
+    let customs = |source: &str| -> Result<String> {
+        let output = cargo_bin_cmd!("cargo-customs")
+            .current_dir(&package)
+            .env("RUSTUP_TOOLCHAIN", "stable")
+            .env("RUSTUP_TOOLCHAIN_SOURCE", source)
+            .arg("--dry-run")
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+        Ok(String::from_utf8(output)?)
+    };
+
+    // `cargo +stable customs`
+    assert_eq!(
+        customs("cli")?,
+        "launch-toolchain/check/host/lib: RUSTUP_TOOLCHAIN=stable cargo check --lib\n\
+         launch-toolchain/build/host/lib: RUSTUP_TOOLCHAIN=nightly cargo build --lib\n"
+    );
+    // The default toolchain of rustup, resolved per package
+    assert_eq!(
+        customs("default")?,
+        "launch-toolchain/check/host/lib: cargo check --lib\n\
+         launch-toolchain/build/host/lib: RUSTUP_TOOLCHAIN=nightly cargo build --lib\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_reports_long_and_silent_checks() -> Result<()> {
+    let (package, path) = package_with_tool("heartbeat", "sleep", "sleep 1.5\n")?;
+
+    let output = cargo_bin_cmd!("cargo-customs")
+        .current_dir(&package)
+        .env("PATH", &path)
+        .args(["--heartbeat", "0.5s", "--stall-warning", "1s"])
+        .assert()
+        .success()
+        .get_output()
+        .stderr
+        .clone();
+
+    let stderr = String::from_utf8(output)?;
+    assert!(
+        stderr.contains("heartbeat/sleep/host/all: still running ("),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains("heartbeat/sleep/host/all: no output for "),
+        "{stderr}"
+    );
+    assert!(stderr.contains(", it may hang\n"), "{stderr}");
+    Ok(())
+}
+
+#[test]
+fn test_customs_gates_coverage_by_minimum_and_ratchet() -> Result<()> {
+    let (package, path) = package_with_tool(
+        "coverage",
+        "llvm-cov",
+        "echo \"{\\\"data\\\":[{\\\"totals\\\":{\\\"lines\\\":{\\\"count\\\":100,\\\"covered\\\":$(cat covered)}}}]}\"\n",
+    )?;
+    std::fs::write(
+        package.join("Customs.toml"),
+        "[[regulation]]\nplatform-targets = [\"host\"]\nbuild-targets = [\"all\"]\n\
+         jobs = { coverage = { min-coverage = 80, coverage-ratchet = true } }\n",
+    )?;
+    let customs = |covered: u8| -> Result<(bool, String)> {
+        std::fs::write(package.join("covered"), covered.to_string())?;
+        let output = cargo_bin_cmd!("cargo-customs")
+            .current_dir(&package)
+            .env("PATH", &path)
+            .output()?;
+        Ok((output.status.success(), String::from_utf8(output.stderr)?))
+    };
+
+    let (success, stderr) = customs(90)?;
+    assert!(success, "{stderr}");
+    assert!(
+        stderr.contains("coverage/coverage/host/all: line coverage 90.0%\n"),
+        "{stderr}"
+    );
+
+    let (success, stderr) = customs(85)?;
+    assert!(!success);
+    assert!(
+        stderr.contains("line coverage dropped from 90.0% to 85.0%"),
+        "{stderr}"
+    );
+
+    let (success, stderr) = customs(70)?;
+    assert!(!success);
+    assert!(
+        stderr.contains("line coverage 70.0% is below 80%"),
+        "{stderr}"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_passes_checks_with_notes_on_success_exit_codes() -> Result<()> {
+    let (package, path) = package_with_tool(
+        "findings",
+        "findings",
+        "echo \"unused dependency\"\nexit $(cat code)\n",
+    )?;
+    std::fs::write(
+        package.join("Customs.toml"),
+        "[[regulation]]\nplatform-targets = [\"host\"]\nbuild-targets = [\"all\"]\n\
+         jobs = { findings = { success-exit-codes = [0, 2] } }\n",
+    )?;
+    let customs = |code: u8| -> Result<(bool, String, String)> {
+        std::fs::write(package.join("code"), code.to_string())?;
+        let output = cargo_bin_cmd!("cargo-customs")
+            .current_dir(&package)
+            .env("PATH", &path)
+            .output()?;
+        Ok((
+            output.status.success(),
+            String::from_utf8(output.stdout)?,
+            String::from_utf8(output.stderr)?,
+        ))
+    };
+
+    let (success, stdout, stderr) = customs(2)?;
+    assert!(success, "{stderr}");
+    assert!(
+        stderr.contains("findings/findings/host/all: passed with notes"),
+        "{stderr}"
+    );
+    assert!(stdout.contains("unused dependency"), "{stdout}");
+
+    let (success, _, stderr) = customs(3)?;
+    assert!(!success, "{stderr}");
+    assert!(!stderr.contains("passed with notes"), "{stderr}");
+    Ok(())
+}
+
+#[test]
+fn test_customs_logs_stdout_and_stderr_separately_and_interleaved() -> Result<()> {
+    let (package, path) = package_with_tool(
+        "streams",
+        "streams",
+        "echo first\nsleep 0.2\necho second >&2\nsleep 0.2\necho third\n",
+    )?;
+
+    cargo_bin_cmd!("cargo-customs")
+        .current_dir(&package)
+        .env("PATH", &path)
+        .assert()
+        .success();
+
+    let logs = package.join("target/customs/logs");
+    let read = |extension: &str| {
+        std::fs::read_to_string(logs.join(format!("streams_streams_host_all.{extension}")))
+    };
+    assert_eq!(read("log")?, "first\nsecond\nthird\n");
+    assert_eq!(read("stdout.log")?, "first\nthird\n");
+    assert_eq!(read("stderr.log")?, "second\n");
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_customs_runs_checks_at_lower_priority_in_background() -> Result<()> {
+    let (package, path) =
+        package_with_tool("background", "niceness", "echo \"niceness $(nice)\"\n")?;
+    let niceness = |background: bool| -> Result<i32> {
+        let mut cmd = cargo_bin_cmd!("cargo-customs");
+        cmd.current_dir(&package).env("PATH", &path);
+        if background {
+            cmd.arg("--background");
+        }
+        cmd.assert().success();
+        let log = std::fs::read_to_string(
+            package.join("target/customs/logs/background_niceness_host_all.log"),
+        )?;
+        Ok(log
+            .trim()
+            .strip_prefix("niceness ")
+            .expect("tool prints its niceness")
+            .parse()?)
+    };
+
+    // Already at the lowest priority when the tests run in background
+    let normal = niceness(false)?.min(18);
+    assert!(niceness(true)? > normal);
+    Ok(())
+}
+
+#[test]
+fn test_customs_only_compiles_tests_with_compile_only() -> Result<()> {
+    let package = fresh_package("compile-only")?;
+    std::fs::write(
+        package.join("Customs.toml"),
+        "[[regulation]]\nplatform-targets = [\"host\"]\nbuild-targets = [\"lib\"]\n\
+         jobs = { clippy = { compile-only = true }, test = { compile-only = true } }\n",
+    )?;
+
+    let output = cargo_bin_cmd!("cargo-customs")
+        .current_dir(&package)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    // Only applies to jobs running tests
+    assert_eq!(
+        String::from_utf8(output)?,
+        "compile-only/clippy/host/lib: cargo clippy --lib\n\
+         compile-only/test/host/lib: cargo test --lib --no-run\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_exports_a_trace_with_a_span_per_check() -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let (package, path) = package_with_tool("traced", "traced", "exit $(cat code)\n")?;
+    let trace = package.join("trace.json");
+    // Records the request instead of sending it
+    let curl = package.join("bin/curl");
+    std::fs::write(
+        &curl,
+        format!(
+            "#!/bin/sh\necho \"$@\" > {0}.args\ncat > {0}\n",
+            trace.display()
+        ),
+    )?;
+    std::fs::set_permissions(&curl, std::fs::Permissions::from_mode(0o755))?;
+    let customs = |code: u8| -> Result<serde_json::Value> {
+        std::fs::write(package.join("code"), code.to_string())?;
+        cargo_bin_cmd!("cargo-customs")
+            .current_dir(&package)
+            .env("PATH", &path)
+            .args(["--otlp-endpoint", "http://collector:4318/"])
+            .output()?;
+        Ok(serde_json::from_slice(&std::fs::read(&trace)?)?)
+    };
+
+    let request = customs(0)?;
+    assert!(
+        std::fs::read_to_string(package.join("trace.json.args"))?
+            .trim_end()
+            .ends_with("http://collector:4318/v1/traces")
+    );
+    let spans = &request["resourceSpans"][0]["scopeSpans"][0]["spans"];
+    assert_eq!(spans[0]["name"], "customs");
+    assert_eq!(spans[0]["status"]["code"], 1);
+    assert_eq!(spans[1]["name"], "traced/traced/host/all");
+    assert_eq!(spans[1]["parentSpanId"], spans[0]["spanId"]);
+    assert_eq!(spans[1]["traceId"], spans[0]["traceId"]);
+    let attributes = spans[1]["attributes"]
+        .as_array()
+        .expect("attributes are a list")
+        .iter()
+        .map(|e| (e["key"].clone(), e["value"]["stringValue"].clone()))
+        .collect::<Vec<_>>();
+    assert!(attributes.contains(&("customs.job".into(), "traced".into())));
+    assert!(attributes.contains(&("customs.status".into(), "passed".into())));
+
+    let request = customs(1)?;
+    let spans = &request["resourceSpans"][0]["scopeSpans"][0]["spans"];
+    assert_eq!(spans[0]["status"]["code"], 2);
+    assert_eq!(spans[1]["status"]["code"], 2);
+    Ok(())
+}
+
+#[test]
+fn test_customs_runs_each_generator_once() -> Result<()> {
+    let workspace = fresh_directory("generator-once")?;
+    std::fs::write(
+        workspace.join("Cargo.toml"),
+        "[workspace]\nmembers = [\"a\", \"b\"]\nresolver = \"3\"\n",
+    )?;
+    for name in ["a", "b"] {
+        std::fs::create_dir_all(workspace.join(name).join("src"))?;
+        std::fs::write(
+            workspace.join(name).join("Cargo.toml"),
+            format!("[package]\nname = \"{name}\"\nversion = \"0.1.0\"\nedition = \"2024\"\n"),
+        )?;
+        std::fs::write(workspace.join(name).join("src/lib.rs"), "")?;
+    }
+    // Counts its runs in `generated`
+    let generated = |directory: &Path, customs: &str| -> Result<()> {
+        std::fs::write(directory.join("generated.toml"), customs)?;
+        std::fs::write(
+            directory.join("Customs.toml"),
+            "generator = \"echo run >> generated && cat generated.toml\"\n",
+        )?;
+        Ok(())
+    };
+    generated(
+        &workspace,
+        "[default]\nplatform-targets = [\"host\"]\nbuild-targets = [\"lib\"]\njobs = [\"fmt\"]\n",
+    )?;
+    generated(&workspace.join("a"), "[[regulation]]\n")?;
+    std::fs::write(workspace.join("b/Customs.toml"), "[[regulation]]\n")?;
+
+    let output = cargo_bin_cmd!("cargo-customs")
+        .current_dir(&workspace)
+        .args(["--workspace", "--dry-run"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    assert_eq!(
+        String::from_utf8(output)?,
+        "a/fmt/host/lib: cargo fmt\nb/fmt/host/lib: cargo fmt\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(workspace.join("generated"))?,
+        "run\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(workspace.join("a/generated"))?,
+        "run\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_customs_library_run_is_cancellable() -> Result<()> {
+    use cargo_customs::{Cancellation, CheckOutcome, Console, Executor, Options, PlannedCheck};
+    use cargo_metadata::camino::Utf8Path;
+
+    /// Cancels the run while its first check runs.
+    struct CancelledByFirstCheck;
+
+    impl Executor for CancelledByFirstCheck {
+        fn execute(
+            &self,
+            _check: &PlannedCheck,
+            _console: &Console,
+            _output_directory: &Utf8Path,
+            cancellation: &Cancellation,
+        ) -> CheckOutcome {
+            cancellation.cancel();
+            CheckOutcome {
+                result: Err(cargo_customs::Error::Cancelled),
+                tests: Default::default(),
+            }
+        }
+    }
+
+    let mut options = Options::parse(["--package", "foo"])?;
+    options.directory = Some("tests/workspace".into());
+    let plan = options.plan()?;
+    let ids = plan
+        .checks
+        .iter()
+        .map(|e| e.id.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(ids, ["foo/fmt/host/all", "foo/build/host/all"]);
+
+    let outcome = options.execute(&plan, &CancelledByFirstCheck, &Cancellation::default());
+    assert!(matches!(
+        outcome.result,
+        Err(cargo_customs::Error::Cancelled)
+    ));
+    // No further check starts
+    assert_eq!(
+        outcome.checks.keys().collect::<Vec<_>>(),
+        ["foo/fmt/host/all"]
+    );
+    assert!(outcome.checks["foo/fmt/host/all"].cancelled);
+    assert!(!outcome.checks["foo/fmt/host/all"].passed);
+    Ok(())
+}
+
+#[test]
+fn test_customs_library_runs_checks_with_a_custom_executor() -> Result<()> {
+    use std::sync::Mutex;
+
+    use cargo_customs::{Cancellation, CheckOutcome, Console, Executor, Options, PlannedCheck};
+    use cargo_metadata::camino::Utf8Path;
+
+    /// Records the commands instead of running them, failing all `build` checks.
+    #[derive(Default)]
+    struct Recording {
+        commands: Mutex<Vec<String>>,
+    }
+
+    impl Executor for Recording {
+        fn execute(
+            &self,
+            check: &PlannedCheck,
+            _console: &Console,
+            _output_directory: &Utf8Path,
+            _cancellation: &Cancellation,
+        ) -> CheckOutcome {
+            self.commands.lock().unwrap().push(format!(
+                "{} {}",
+                check.command.program,
+                check.command.args.join(" ")
+            ));
+            let result = match check.check.job.name.as_str() {
+                "build" => Err(cargo_customs::Error::CheckFailed(check.id.clone())),
+                _ => Ok(()),
+            };
+            CheckOutcome {
+                result,
+                tests: Default::default(),
+            }
+        }
+    }
+
+    let mut options = Options::parse(["--package", "foo", "--keep-going"])?;
+    options.directory = Some("tests/workspace".into());
+    let plan = options.plan()?;
+    let executor = Recording::default();
+    let outcome = options.execute(&plan, &executor, &Cancellation::default());
+
+    assert_eq!(
+        *executor.commands.lock().unwrap(),
+        ["cargo fmt", "cargo build --all-targets"]
+    );
+    assert!(
+        matches!(outcome.result, Err(cargo_customs::Error::CheckFailed(id)) if id == "foo/build/host/all")
+    );
+    assert!(outcome.checks["foo/fmt/host/all"].passed);
+    assert!(!outcome.checks["foo/build/host/all"].passed);
+    assert!(!outcome.checks["foo/build/host/all"].cancelled);
+    Ok(())
+}
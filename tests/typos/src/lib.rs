@@ -0,0 +1,4 @@
+/// Teh answer.
+pub fn answer() -> u32 {
+    42
+}